@@ -835,9 +835,7 @@ impl MarketCoinOps for EthCoin {
     }
 
     fn address_from_pubkey_str(&self, pubkey: &str) -> Result<String, String> {
-        let pubkey_bytes = try_s!(hex::decode(pubkey));
-        let addr = try_s!(addr_from_raw_pubkey(&pubkey_bytes));
-        Ok(format!("{:#02x}", addr))
+        addr_from_pubkey_str(pubkey)
     }
 }
 
@@ -1925,6 +1923,12 @@ impl MmCoin for EthCoin {
     }
 }
 
+pub fn addr_from_pubkey_str(pubkey: &str) -> Result<String, String> {
+    let pubkey_bytes = try_s!(hex::decode(pubkey));
+    let addr = try_s!(addr_from_raw_pubkey(&pubkey_bytes));
+    Ok(format!("{:#02x}", addr))
+}
+
 fn addr_from_raw_pubkey(pubkey: &[u8]) -> Result<Address, String> {
     let pubkey = try_s!(PublicKey::parse_slice(pubkey, None).map_err(|e| ERRL!("{:?}", e)));
     let eth_public = Public::from(&pubkey.serialize()[1..65]);
@@ -439,6 +439,24 @@ impl CoinsContext {
     }
 }
 
+/// Returns the coin configuration from the `coins` config or Null if the coin is not configured
+pub fn coin_conf (ctx: &MmArc, ticker: &str) -> Json {
+    match ctx.conf["coins"].as_array() {
+        Some (coins) => coins.iter().find (|coin| coin["coin"].as_str() == Some (ticker)) .cloned().unwrap_or (Json::Null),
+        None => Json::Null,
+    }
+}
+
+/// Derives the address of the pubkey from the coin configuration, so the coin doesn't have to be enabled
+pub fn address_by_coin_conf_and_pubkey_str (ticker: &str, conf: &Json, pubkey: &str) -> Result<String, String> {
+    if conf.is_null() {return ERR! ("Coin {} is not found in the coins config", ticker)}
+    if conf["etomic"].is_null() {
+        utxo::address_by_conf_and_pubkey_str (ticker, conf, pubkey)
+    } else {
+        eth::addr_from_pubkey_str (pubkey)
+    }
+}
+
 /// Adds a new currency into the list of currencies configured.
 ///
 /// Returns an error if the currency already exists. Initializing the same currency twice is a bad habit
@@ -451,9 +469,7 @@ pub async fn lp_coininit (ctx: &MmArc, ticker: &str, req: &Json) -> Result<MmCoi
     {   let coins = try_s! (cctx.coins.sleeplock (77) .await);
         if coins.get (ticker) .is_some() {return ERR! ("Coin {} already initialized", ticker)}   }
 
-    let coins_en = if let Some (coins) = ctx.conf["coins"].as_array() {
-        coins.iter().find (|coin| coin["coin"].as_str() == Some (ticker)) .unwrap_or (&Json::Null)
-    } else {&Json::Null};
+    let coins_en = &coin_conf (ctx, ticker);
 
     if coins_en.is_null() {
         ctx.log.log ("😅", &[&("coin" as &str), &ticker, &("no-conf" as &str)],
@@ -1817,19 +1817,32 @@ fn read_native_mode_conf(_filename: &dyn AsRef<Path>) -> Result<(Option<u16>, St
     unimplemented!()
 }
 
+fn checksum_type_from_ticker(ticker: &str) -> ChecksumType {
+    if ticker == "GRS" {
+        ChecksumType::DGROESTL512
+    } else if ticker == "SMART" {
+        ChecksumType::KECCAK256
+    } else {
+        ChecksumType::DSHA256
+    }
+}
+
+/// Derives the address of the pubkey using the address prefixes of the coin config
+pub fn address_by_conf_and_pubkey_str(ticker: &str, conf: &Json, pubkey: &str) -> Result<String, String> {
+    let pubkey_bytes = try_s!(hex::decode(pubkey));
+    let pub_addr_prefix = conf["pubtype"].as_u64().unwrap_or (if ticker == "BTC" {0} else {60}) as u8;
+    let pub_t_addr_prefix = conf["taddr"].as_u64().unwrap_or (0) as u8;
+    let addr = try_s!(address_from_raw_pubkey(&pubkey_bytes, pub_addr_prefix, pub_t_addr_prefix, checksum_type_from_ticker(ticker)));
+    Ok(addr.to_string())
+}
+
 pub async fn utxo_coin_from_conf_and_request(
     ticker: &str,
     conf: &Json,
     req: &Json,
     priv_key: &[u8],
 ) -> Result<UtxoCoin, String> {
-    let checksum_type = if ticker == "GRS" {
-        ChecksumType::DGROESTL512
-    } else if ticker == "SMART" {
-        ChecksumType::KECCAK256
-    } else {
-        ChecksumType::DSHA256
-    };
+    let checksum_type = checksum_type_from_ticker(ticker);
 
     let pub_addr_prefix = conf["pubtype"].as_u64().unwrap_or (if ticker == "BTC" {0} else {60}) as u8;
     let wif_prefix = conf["wiftype"].as_u64().unwrap_or (if ticker == "BTC" {128} else {188}) as u8;
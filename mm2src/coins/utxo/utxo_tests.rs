@@ -177,6 +177,18 @@ fn test_addresses_from_script() {
     assert_eq!(expected_addr, actual_addr);
 }
 
+#[test]
+fn test_address_by_conf_and_pubkey_str() {
+    let pubkey = "02031d4256c4bc9f99ac88bf3dba21773132281f65f9bf23a59928bce08961e2f3";
+    let conf = json!({"coin": "RICK", "asset": "RICK", "rpcport": 25435});
+    let address = unwrap!(address_by_conf_and_pubkey_str("RICK", &conf, pubkey));
+    assert_eq!("R9o9xTocqr6CeEDGDH6mEYpwLoMz6jNjMW", address);
+
+    let conf = json!({"coin": "BTC", "pubtype": 0, "p2shtype": 5, "wiftype": 128});
+    let address = unwrap!(address_by_conf_and_pubkey_str("BTC", &conf, pubkey));
+    assert_eq!("1WxswvLF2HdaDr4k77e92VjaXuPQA8Uji", address);
+}
+
 #[test]
 fn test_kmd_interest() {
     let value = 64605500822;
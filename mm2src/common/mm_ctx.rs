@@ -100,6 +100,8 @@ pub struct MmCtx {
     pub coins_needed_for_kick_start: Mutex<HashSet<String>>,
     /// The context belonging to the `lp_swap` mod: `SwapsContext`.
     pub swaps_ctx: Mutex<Option<Arc<dyn Any + 'static + Send + Sync>>>,
+    /// The context belonging to the `lp_autoprice` mod: `AutopriceContext`.
+    pub autoprice_ctx: Mutex<Option<Arc<dyn Any + 'static + Send + Sync>>>,
 }
 impl MmCtx {
     pub fn with_log_state (log: LogState) -> MmCtx {
@@ -128,6 +130,7 @@ impl MmCtx {
             secp256k1_key_pair: Constructible::default(),
             coins_needed_for_kick_start: Mutex::new (HashSet::new()),
             swaps_ctx: Mutex::new (None),
+            autoprice_ctx: Mutex::new (None),
         }
     }
 
@@ -0,0 +1,298 @@
+/******************************************************************************
+ * Copyright © 2014-2019 The SuperNET Developers.                             *
+ *                                                                            *
+ * See the AUTHORS, DEVELOPER-AGREEMENT and LICENSE files at                  *
+ * the top-level directory of this distribution for the individual copyright  *
+ * holder information and the developer policies on copyright and licensing.  *
+ *                                                                            *
+ * Unless otherwise agreed in a custom licensing agreement, no part of the    *
+ * SuperNET software, including this file may be copied, modified, propagated *
+ * or distributed except according to the terms contained in the LICENSE file *
+ *                                                                            *
+ * Removal or modification of this copyright notice is prohibited.            *
+ *                                                                            *
+ ******************************************************************************/
+
+//
+//  lp_autoprice.rs
+//  marketmaker
+//
+
+//! Periodic repricing of my maker orders by the reference price from an external price source.
+//! The native replacement of the `portfolio::lp_autoprice` which depends on the C `LP_priceinfo` structures.
+//! The live prices are loaded with the `lp_btcprice` of the `price_providers` shared with the `portfolio`.
+//! The autoprice settings are saved to the "ORDERS/MY/AUTOPRICE" db dir and restored by the `autoprice_kick_start`.
+
+use bigdecimal::BigDecimal;
+use coins::coin_conf;
+use common::{json_dir_entries, now_ms, slurp};
+use common::executor::Timer;
+use common::mm_ctx::{from_ctx, MmArc};
+use common::mm_number::MmNumber;
+use futures::compat::Future01CompatExt;
+use gstuff::now_float;
+use http::Response;
+use serde_json::{self as json, Value as Json};
+use std::collections::HashMap;
+use std::fs::{DirEntry, write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::mm2::lp_ordermatch::reprice_my_maker_orders;
+use crate::mm2::price_providers::{lp_btcprice, Coins, CoinId, PricingProvider, PriceUnit};
+
+/// The min interval between the price source requests, in seconds
+const MIN_AUTOPRICE_INTERVAL: u64 = 30;
+
+fn default_autoprice_interval() -> u64 { 60 }
+
+/// The source of the coin USD prices
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", content = "data")]
+pub enum PriceSource {
+    /// The coins are identified by the "name" field of the coins config, e.g. "komodo"
+    CoinGecko,
+    /// The coins are identified by their tickers
+    CoinMarketCap { api_key: String },
+    /// The JSON file mapping the coin tickers to their prices, e.g. {"KMD": 1.5, "BTC": 9000}.
+    /// The file is read on every update, so it can be changed by an external script.
+    File { path: String },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct AutopriceConf {
+    base: String,
+    rel: String,
+    /// Added to the reference price, in percent
+    #[serde(default)]
+    margin: MmNumber,
+    price_source: PriceSource,
+    /// How often the orders are repriced, in seconds
+    #[serde(default = "default_autoprice_interval")]
+    interval: u64,
+}
+
+impl AutopriceConf {
+    /// Returns the reference price of the pair and the order price with the margin applied
+    fn prices(&self, prices: &HashMap<String, MmNumber>) -> Result<(MmNumber, MmNumber), String> {
+        let base_price = try_s!(prices.get(&self.base).ok_or(ERRL!("No {} price", self.base)));
+        let rel_price = try_s!(prices.get(&self.rel).ok_or(ERRL!("No {} price", self.rel)));
+        if *rel_price <= MmNumber::default() {
+            return ERR!("{} price must be greater than zero", self.rel);
+        }
+        let reference_price = base_price / rel_price;
+        let price = &reference_price * &(MmNumber::from(100) + self.margin.clone()) / MmNumber::from(100);
+        Ok((reference_price, price))
+    }
+}
+
+struct AutopriceContext {
+    /// The autoprice configurations with the time of the last update
+    pairs: Mutex<HashMap<(String, String), (AutopriceConf, u64)>>,
+}
+
+impl AutopriceContext {
+    /// Obtains a reference to this crate context, creating it if necessary.
+    fn from_ctx(ctx: &MmArc) -> Result<Arc<AutopriceContext>, String> {
+        Ok(try_s!(from_ctx(&ctx.autoprice_ctx, move || {
+            Ok(AutopriceContext {
+                pairs: Mutex::new(HashMap::default()),
+            })
+        })))
+    }
+}
+
+fn my_autoprice_dir(ctx: &MmArc) -> PathBuf {
+    ctx.dbdir().join("ORDERS").join("MY").join("AUTOPRICE")
+}
+
+fn my_autoprice_file_path(ctx: &MmArc, base: &str, rel: &str) -> PathBuf {
+    my_autoprice_dir(ctx).join(format!("{}_{}.json", base, rel))
+}
+
+fn save_my_autoprice(ctx: &MmArc, conf: &AutopriceConf) {
+    let path = my_autoprice_file_path(ctx, &conf.base, &conf.rel);
+    let content = unwrap!(json::to_vec(conf));
+    unwrap!(write(&path, &content));
+}
+
+/// Restores the saved autoprice settings, the pairs are repriced on the first `lp_autoprice_loop` iteration
+pub fn autoprice_kick_start(ctx: &MmArc) -> Result<(), String> {
+    let autoprice_ctx = try_s!(AutopriceContext::from_ctx(ctx));
+    let mut pairs = try_s!(autoprice_ctx.pairs.lock());
+    let entries: Vec<DirEntry> = try_s!(json_dir_entries(&my_autoprice_dir(ctx)));
+
+    entries.iter().for_each(|entry| {
+        match json::from_slice::<AutopriceConf>(&unwrap!(slurp(&entry.path()))) {
+            Ok(conf) => { pairs.insert((conf.base.clone(), conf.rel.clone()), (conf, 0)); },
+            Err(e) => log!("Error " (e) " loading the autoprice settings " [entry.path()]),
+        }
+    });
+    Ok(())
+}
+
+/// Returns the USD prices of the coins from the JSON file by their tickers
+fn prices_from_file(path: &str, tickers: &[&str]) -> Result<HashMap<String, MmNumber>, String> {
+    let content = try_s!(slurp(&path));
+    if content.is_empty() { return ERR!("Price file {} is empty or doesn't exist", path) }
+    let prices: HashMap<String, BigDecimal> = try_s!(json::from_slice(&content));
+    Ok(prices.into_iter()
+        .filter(|(ticker, _)| tickers.contains(&&ticker[..]))
+        .map(|(ticker, price)| (ticker, price.into()))
+        .collect())
+}
+
+/// Returns the USD prices of the coins by their tickers
+async fn fetch_prices(ctx: &MmArc, source: &PriceSource, tickers: &[&str]) -> Result<HashMap<String, MmNumber>, String> {
+    let provider = match source {
+        PriceSource::CoinGecko => PricingProvider::CoinGecko,
+        PriceSource::CoinMarketCap { api_key } => PricingProvider::CoinMarketCap(api_key.clone()),
+        PriceSource::File { path } => return prices_from_file(path, tickers),
+    };
+    // the `lp_btcprice` identifies the coins by the "name" field of the coins config
+    let mut tickers_by_id = HashMap::new();
+    for ticker in tickers {
+        let conf = coin_conf(ctx, ticker);
+        let name = try_s!(conf["name"].as_str().ok_or(ERRL!("No 'name' field in the {} config", ticker)));
+        tickers_by_id.insert(CoinId(name.to_owned()), ticker.to_string());
+    }
+    let coins = Arc::new(Coins {
+        ids: Mutex::new(tickers_by_id.keys().map(|id| (id.clone(), now_float())).collect()),
+    });
+    let external_prices = try_s!(lp_btcprice(ctx.weak(), &provider, PriceUnit::UsDollar, &coins).compat().await);
+
+    let mut prices = HashMap::new();
+    for (id, price) in external_prices.prices {
+        if let Some(ticker) = tickers_by_id.get(&id) {
+            let price: BigDecimal = try_s!(price.to_string().parse());
+            prices.insert(ticker.clone(), price.into());
+        }
+    }
+    Ok(prices)
+}
+
+/// Fetches the prices of the pair and reprices my maker orders
+async fn autoprice_pair(ctx: &MmArc, conf: &AutopriceConf) -> Result<(MmNumber, usize), String> {
+    let prices = try_s!(fetch_prices(ctx, &conf.price_source, &[&conf.base[..], &conf.rel[..]]).await);
+    let (reference_price, price) = try_s!(conf.prices(&prices));
+    let repriced = try_s!(reprice_my_maker_orders(ctx, &conf.base, &conf.rel, &reference_price, &price));
+    Ok((price, repriced))
+}
+
+/// Sets the automatic repricing of my maker orders of the pair, the orders are repriced immediately
+pub async fn autoprice(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    let conf: AutopriceConf = try_s!(json::from_value(req));
+    if conf.base == conf.rel { return ERR!("Base and rel must be different coins") }
+    if conf.margin <= MmNumber::from(BigDecimal::from(-100)) {
+        return ERR!("Margin must be greater than -100%");
+    }
+    if conf.interval < MIN_AUTOPRICE_INTERVAL {
+        return ERR!("Interval must be at least {} seconds", MIN_AUTOPRICE_INTERVAL);
+    }
+    let (price, repriced) = try_s!(autoprice_pair(&ctx, &conf).await);
+
+    let autoprice_ctx = try_s!(AutopriceContext::from_ctx(&ctx));
+    let mut pairs = try_s!(autoprice_ctx.pairs.lock());
+    save_my_autoprice(&ctx, &conf);
+    pairs.insert((conf.base.clone(), conf.rel.clone()), (conf, now_ms() / 1000));
+    let res = try_s!(json::to_vec(&json!({
+        "result": {
+            "price": BigDecimal::from(price),
+            "repriced": repriced,
+        }
+    })));
+    Ok(try_s!(Response::builder().body(res)))
+}
+
+pub async fn lp_autoprice_loop(ctx: MmArc) {
+    loop {
+        if ctx.is_stopping() { break }
+        let autoprice_ctx = unwrap!(AutopriceContext::from_ctx(&ctx));
+        let now = now_ms() / 1000;
+        let due: Vec<AutopriceConf> = unwrap!(autoprice_ctx.pairs.lock()).values()
+            .filter(|(conf, last_update)| last_update + conf.interval <= now)
+            .map(|(conf, _)| conf.clone())
+            .collect();
+        for conf in due {
+            match autoprice_pair(&ctx, &conf).await {
+                Ok((price, repriced)) => log!("Autoprice " (conf.base) "/" (conf.rel) " " (price) ", repriced " (repriced) " orders"),
+                Err(e) => ctx.log.log("", &[&"autoprice"], &format!("{}/{} error {}", conf.base, conf.rel, e)),
+            }
+            // the failed updates are retried after the interval as well to avoid hitting the price source limits
+            if let Some((_, last_update)) = unwrap!(autoprice_ctx.pairs.lock()).get_mut(&(conf.base.clone(), conf.rel.clone())) {
+                *last_update = now;
+            }
+        }
+        Timer::sleep(1.).await;
+    }
+}
+
+#[cfg(test)]
+mod lp_autoprice_tests {
+    use common::block_on;
+    use common::mm_ctx::MmCtxBuilder;
+    use std::fs;
+    use super::*;
+
+    #[test]
+    fn test_autoprice_conf_prices() {
+        let conf: AutopriceConf = unwrap!(json::from_value(json!({
+            "base": "KMD",
+            "rel": "BTC",
+            "margin": 10,
+            "price_source": {"type": "File", "data": {"path": "prices.json"}},
+        })));
+        assert_eq!(60, conf.interval);
+
+        let mut prices = HashMap::new();
+        prices.insert("KMD".to_owned(), MmNumber::from(1));
+        assert!(conf.prices(&prices).is_err());
+
+        prices.insert("BTC".to_owned(), MmNumber::from(10000));
+        let (reference_price, price) = unwrap!(conf.prices(&prices));
+        assert_eq!(MmNumber::from(1) / MmNumber::from(10000), reference_price);
+        assert_eq!(MmNumber::from(11) / MmNumber::from(100000), price);
+    }
+
+    #[test]
+    fn test_fetch_prices_from_file() {
+        let ctx = MmCtxBuilder::default().into_mm_arc();
+        let path = std::env::temp_dir().join("test_fetch_prices_from_file.json");
+        unwrap!(fs::write(&path, r#"{"KMD": 1.5, "BTC": "9000", "ETH": 200}"#));
+        let source = PriceSource::File { path: unwrap!(path.to_str()).to_owned() };
+        let prices = unwrap!(block_on(fetch_prices(&ctx, &source, &["KMD", "BTC"])));
+        unwrap!(fs::remove_file(&path));
+
+        assert_eq!(2, prices.len());
+        assert_eq!(MmNumber::from(unwrap!("1.5".parse::<BigDecimal>())), prices["KMD"]);
+        assert_eq!(MmNumber::from(9000), prices["BTC"]);
+    }
+
+    #[test]
+    fn test_autoprice_kick_start() {
+        let dbdir = std::env::temp_dir().join("test_autoprice_kick_start");
+        let _ = fs::remove_dir_all(&dbdir);
+        let conf = json!({"dbdir": unwrap!(dbdir.to_str())});
+        let ctx = MmCtxBuilder::new().with_conf(conf.clone()).into_mm_arc();
+        unwrap!(fs::create_dir_all(my_autoprice_dir(&ctx)));
+        let autoprice: AutopriceConf = unwrap!(json::from_value(json!({
+            "base": "KMD",
+            "rel": "BTC",
+            "margin": 10,
+            "price_source": {"type": "File", "data": {"path": "prices.json"}},
+        })));
+        save_my_autoprice(&ctx, &autoprice);
+
+        // the settings survive the restart
+        let restarted = MmCtxBuilder::new().with_conf(conf).into_mm_arc();
+        unwrap!(autoprice_kick_start(&restarted));
+        unwrap!(fs::remove_dir_all(&dbdir));
+
+        let autoprice_ctx = unwrap!(AutopriceContext::from_ctx(&restarted));
+        let pairs = unwrap!(autoprice_ctx.pairs.lock());
+        let (loaded, last_update) = unwrap!(pairs.get(&("KMD".to_owned(), "BTC".to_owned())));
+        assert_eq!(autoprice.price_source, loaded.price_source);
+        assert_eq!(autoprice.margin, loaded.margin);
+        assert_eq!(0, *last_update);
+    }
+}
@@ -46,9 +46,10 @@ use crate::common::executor::{spawn, Timer};
 use crate::common::{slurp_url, MM_VERSION};
 use crate::common::mm_ctx::{MmCtx, MmArc};
 use crate::common::privkey::key_pair_from_seed;
+use crate::mm2::lp_autoprice::{autoprice_kick_start, lp_autoprice_loop};
 use crate::mm2::lp_network::{lp_command_q_loop, start_seednode_loop, start_client_p2p_loop};
-use crate::mm2::lp_ordermatch::{lp_ordermatch_loop, lp_trade_command, migrate_saved_orders, orders_kick_start};
-use crate::mm2::lp_swap::swap_kick_starts;
+use crate::mm2::lp_ordermatch::{init_ordermatch_context, lp_ordermatch_loop, lp_trade_command, migrate_saved_orders, orders_kick_start};
+use crate::mm2::lp_swap::{check_swap_events_sink, init_swaps_context, lp_swaps_recovery_loop, swap_kick_starts, watched_swaps_kick_start};
 use crate::mm2::rpc::{spawn_rpc};
 
 /// Process a previously queued command that wasn't handled by the RPC `dispatcher`.  
@@ -1030,6 +1031,7 @@ fn fix_directories(ctx: &MmCtx) -> Result<(), String> {
     if !ensure_dir_is_writable(&dbdir.join ("SWAPS").join ("STATS")) {return ERR!("SWAPS/STATS db dir is not writable")}
     if !ensure_dir_is_writable(&dbdir.join ("SWAPS").join ("STATS").join ("MAKER")) {return ERR!("SWAPS/STATS/MAKER db dir is not writable")}
     if !ensure_dir_is_writable(&dbdir.join ("SWAPS").join ("STATS").join ("TAKER")) {return ERR!("SWAPS/STATS/TAKER db dir is not writable")}
+    if !ensure_dir_is_writable(&dbdir.join ("SWAPS").join ("WATCHED")) {return ERR!("SWAPS/WATCHED db dir is not writable")}
     if !ensure_dir_is_writable(&dbdir.join ("TRANSACTIONS")) {return ERR!("TRANSACTIONS db dir is not writable")}
     if !ensure_dir_is_writable(&dbdir.join ("GTC")) {return ERR!("GTC db dir is not writable")}
     if !ensure_dir_is_writable(&dbdir.join ("PRICES")) {return ERR!("PRICES db dir is not writable")}
@@ -1038,6 +1040,9 @@ fn fix_directories(ctx: &MmCtx) -> Result<(), String> {
     if !ensure_dir_is_writable(&dbdir.join ("ORDERS").join ("MY")) {return ERR!("ORDERS/MY db dir is not writable")}
     if !ensure_dir_is_writable(&dbdir.join ("ORDERS").join ("MY").join ("MAKER")) {return ERR!("ORDERS/MY/MAKER db dir is not writable")}
     if !ensure_dir_is_writable(&dbdir.join ("ORDERS").join ("MY").join ("TAKER")) {return ERR!("ORDERS/MY/TAKER db dir is not writable")}
+    if !ensure_dir_is_writable(&dbdir.join ("ORDERS").join ("MY").join ("HISTORY")) {return ERR!("ORDERS/MY/HISTORY db dir is not writable")}
+    if !ensure_dir_is_writable(&dbdir.join ("ORDERS").join ("MY").join ("CONDITIONAL")) {return ERR!("ORDERS/MY/CONDITIONAL db dir is not writable")}
+    if !ensure_dir_is_writable(&dbdir.join ("ORDERS").join ("MY").join ("AUTOPRICE")) {return ERR!("ORDERS/MY/AUTOPRICE db dir is not writable")}
     try_s!(ensure_file_is_writable(&dbdir.join ("GTC").join ("orders")));
     Ok(())
 }
@@ -1059,8 +1064,12 @@ fn fix_directories(ctx: &MmCtx) -> Result<(), String> {
     writeable_dir! (dbdir.join ("SWAPS") .join ("MY"));
     writeable_dir! (dbdir.join ("SWAPS") .join ("STATS") .join ("MAKER"));
     writeable_dir! (dbdir.join ("SWAPS") .join ("STATS") .join ("TAKER"));
+    writeable_dir! (dbdir.join ("SWAPS") .join ("WATCHED"));
     writeable_dir! (dbdir.join ("ORDERS") .join ("MY") .join ("MAKER"));
     writeable_dir! (dbdir.join ("ORDERS") .join ("MY") .join ("TAKER"));
+    writeable_dir! (dbdir.join ("ORDERS") .join ("MY") .join ("HISTORY"));
+    writeable_dir! (dbdir.join ("ORDERS") .join ("MY") .join ("CONDITIONAL"));
+    writeable_dir! (dbdir.join ("ORDERS") .join ("MY") .join ("AUTOPRICE"));
     Ok(())
 }
 
@@ -1201,6 +1210,9 @@ pub async fn lp_init (mypubport: u16, ctx: MmArc) -> Result<(), String> {
 
     try_s! (fix_directories (&ctx));
     #[cfg(feature = "native")] {try_s! (migrate_db (&ctx));}
+    try_s! (init_swaps_context (&ctx));
+    try_s! (check_swap_events_sink (&ctx));
+    try_s! (init_ordermatch_context (&ctx));
 
     fn simple_ip_extractor (ip: &str) -> Result<IpAddr, String> {
         let ip = ip.trim();
@@ -1327,7 +1339,12 @@ pub async fn lp_init (mypubport: u16, ctx: MmArc) -> Result<(), String> {
         // an order and start new swap that might get started 2 times because of kick-start
         let mut coins_needed_for_kick_start = swap_kick_starts (ctx.clone());
         coins_needed_for_kick_start.extend(try_s!(orders_kick_start(&ctx)));
+        coins_needed_for_kick_start.extend(try_s!(watched_swaps_kick_start(&ctx)));
+        try_s!(autoprice_kick_start(&ctx));
         *(try_s!(ctx.coins_needed_for_kick_start.lock())) = coins_needed_for_kick_start;
+
+        let ctxʹ = ctx.clone();
+        try_s!(std::thread::Builder::new().name("swaps_recovery".into()).spawn(move || lp_swaps_recovery_loop(ctxʹ)));
     }
 
     let ctxʹ = ctx.clone();
@@ -1336,6 +1353,9 @@ pub async fn lp_init (mypubport: u16, ctx: MmArc) -> Result<(), String> {
     let ctxʹ = ctx.clone();
     spawn (async move {lp_command_q_loop (ctxʹ) .await});
 
+    let ctxʹ = ctx.clone();
+    spawn (async move {lp_autoprice_loop (ctxʹ) .await});
+
     #[cfg(not(feature = "native"))] {if 1==1 {return Ok(())}}  // TODO: Gradually move this point further down.
 
     let ctx_id = try_s! (ctx.ffi_handle());
@@ -40,7 +40,7 @@ use std::thread;
 use std::time::Duration;
 
 use crate::mm2::lp_native_dex::lp_command_process;
-use crate::mm2::lp_ordermatch::lp_post_price_recv;
+use crate::mm2::lp_ordermatch::{lp_orderbook_snapshot_recv, lp_orderbook_snapshot_request_recv, lp_post_price_recv};
 use crate::mm2::lp_swap::save_stats_swap_status;
 use crate::mm2::rpc::lp_signatures::lp_notify_recv;
 
@@ -67,6 +67,8 @@ fn dispatcher (req: Json, ctx: MmArc) -> DispatcherRes {
     };
     DispatcherRes::Match (match &method[..] {  // Sorted alphanumerically (on the first latter) for readability.
         "notify" => lp_notify_recv (ctx, req),  // Invoked usually from the `lp_command_q_loop`
+        "orderbook_snapshot" => lp_orderbook_snapshot_recv (&ctx, req),
+        "orderbook_snapshot_request" => lp_orderbook_snapshot_request_recv (&ctx, req),
         "postprice" => lp_post_price_recv (&ctx, req),
         _ => return DispatcherRes::NoMatch (req)
     })
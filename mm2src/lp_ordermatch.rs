@@ -23,9 +23,9 @@
 
 use bigdecimal::BigDecimal;
 use bitcrypto::sha256;
-use coins::{lp_coinfindᵃ, MmCoinEnum, TradeInfo};
+use coins::{address_by_coin_conf_and_pubkey_str, coin_conf, lp_coinfindᵃ, MmCoinEnum, TradeInfo};
 use coins::utxo::{compressed_pub_key_from_priv_raw, ChecksumType};
-use common::{bits256, json_dir_entries, now_ms, new_uuid,
+use common::{bits256, json_dir_entries, now_ms, new_uuid, read_dir,
   remove_file, rpc_response, rpc_err_response, write, HyRes};
 use common::executor::{spawn, Timer};
 use common::mm_ctx::{from_ctx, MmArc, MmWeak};
@@ -42,15 +42,17 @@ use num_traits::identities::Zero;
 use primitives::hash::{H256};
 use rpc::v1::types::{H256 as H256Json};
 use serde_json::{self as json, Value as Json};
-use std::collections::HashSet;
+use std::cmp::Ordering;
+use std::collections::{HashSet, VecDeque};
 use std::collections::hash_map::{Entry, HashMap};
 use std::fs::DirEntry;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
-use crate::mm2::lp_swap::{dex_fee_amount, get_locked_amount, is_pubkey_banned, MakerSwap,
-                          run_maker_swap, run_taker_swap, TakerSwap};
+use crate::mm2::lp_swap::{active_swaps_count_for_pair, ban_pubkey, dex_fee_amount, get_locked_amount, init_successful_swaps,
+                          is_pubkey_banned, MakerSwap, max_taker_vol_from_available, run_maker_swap, run_taker_swap, successful_swaps_count,
+                          TakerSwap};
 
 #[cfg(test)]
 #[cfg(feature = "native")]
@@ -76,6 +78,9 @@ struct TakerRequest {
     method: String,
     sender_pubkey: H256Json,
     dest_pub_key: H256Json,
+    /// Whether the request volume can be split across several maker orders
+    #[serde(default)]
+    allow_partial_fill: bool,
 }
 
 impl TakerRequest {
@@ -94,11 +99,54 @@ impl TakerRequest {
     }
 }
 
+/// Time in force of the taker order, defines what happens when the order is not matched during `ORDERMATCH_TIMEOUT`
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+enum OrderType {
+    /// The order is converted to the maker order and stays on the orderbook until it's cancelled
+    GoodTillCancelled,
+    /// The order must be filled completely or expire
+    FillOrKill,
+    /// The order is filled as much as possible, the remaining volume expires
+    ImmediateOrCancel,
+}
+
+impl Default for OrderType {
+    fn default() -> OrderType { OrderType::GoodTillCancelled }
+}
+
+/// Defines which of the equally priced maker reservations is connected first
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+enum TieBreaker {
+    /// The reservation that was received first
+    Age,
+    /// The reservation from the maker having more successful swaps with our node
+    Reputation,
+}
+
+impl Default for TieBreaker {
+    fn default() -> TieBreaker { TieBreaker::Age }
+}
+
+/// Default time in milliseconds to collect the maker reservations before connecting to the best priced ones
+const DEFAULT_RESERVATIONS_WINDOW: u64 = 3000;
+
+fn default_reservations_window() -> u64 { DEFAULT_RESERVATIONS_WINDOW }
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 struct TakerOrder {
     created_at: u64,
     request: TakerRequest,
-    matches: HashMap<Uuid, TakerMatch>
+    matches: HashMap<Uuid, TakerMatch>,
+    #[serde(default)]
+    order_type: OrderType,
+    /// Reservations received since the last ordermatch loop iteration, the best priced are connected first
+    #[serde(skip)]
+    pending_reservations: Vec<MakerReserved>,
+    /// Time in milliseconds since the order creation when the reservations are only collected
+    #[serde(default)]
+    reservations_window: u64,
+    #[serde(default)]
+    tie_breaker: TieBreaker,
 }
 
 /// Result of match_reserved function
@@ -112,10 +160,37 @@ enum MatchReservedResult {
 
 impl TakerOrder {
     fn is_cancellable(&self) -> bool {
-        self.matches.is_empty()
+        !self.has_ongoing_matches()
+    }
+
+    /// Whether there are matches that are not connected yet
+    fn has_ongoing_matches(&self) -> bool {
+        self.matches.values().any(|order_match| order_match.connected.is_none())
+    }
+
+    /// The amount of request base coin covered by the reservation
+    fn reserved_base_amount(&self, reserved: &MakerReserved) -> MmNumber {
+        match self.request.action {
+            TakerAction::Buy => reserved.get_base_amount(),
+            TakerAction::Sell => reserved.get_rel_amount(),
+        }
+    }
+
+    /// The amount of request base coin that is not matched yet
+    fn remaining_base_amount(&self) -> MmNumber {
+        let matched = self.matches.values().fold(
+            MmNumber::default(),
+            |matched, order_match| matched + self.reserved_base_amount(&order_match.reserved)
+        );
+        self.request.get_base_amount() - matched
     }
 
     fn match_reserved(&self, reserved: &MakerReserved) -> MatchReservedResult {
+        // the FillOrKill order is never reserved partially even if it's loaded with the partial fills allowed
+        if self.request.allow_partial_fill && self.order_type != OrderType::FillOrKill {
+            return self.match_reserved_partially(reserved);
+        }
+
         let my_base_amount: MmNumber = self.request.get_base_amount();
         let my_rel_amount: MmNumber = self.request.get_rel_amount();
         let other_base_amount: MmNumber = reserved.get_base_amount();
@@ -136,6 +211,112 @@ impl TakerOrder {
             }
         }
     }
+
+    /// The reservation can cover any part of the remaining volume if the price is not worse than requested
+    fn match_reserved_partially(&self, reserved: &MakerReserved) -> MatchReservedResult {
+        let my_base_amount: MmNumber = self.request.get_base_amount();
+        let my_rel_amount: MmNumber = self.request.get_rel_amount();
+        let other_base_amount: MmNumber = reserved.get_base_amount();
+        let other_rel_amount: MmNumber = reserved.get_rel_amount();
+        let remaining = self.remaining_base_amount();
+        let zero = MmNumber::default();
+
+        let matched = match self.request.action {
+            // other_rel / other_base must not be higher than my_rel / my_base
+            TakerAction::Buy => self.request.base == reserved.base && self.request.rel == reserved.rel
+                && other_base_amount > zero && other_base_amount <= remaining
+                && &other_rel_amount * &my_base_amount <= &my_rel_amount * &other_base_amount,
+            // other_base / other_rel must not be lower than my_rel / my_base
+            TakerAction::Sell => self.request.base == reserved.rel && self.request.rel == reserved.base
+                && other_rel_amount > zero && other_rel_amount <= remaining
+                && &my_rel_amount * &other_rel_amount <= &other_base_amount * &my_base_amount,
+        };
+
+        if matched {
+            MatchReservedResult::Matched
+        } else {
+            MatchReservedResult::NotMatched
+        }
+    }
+}
+
+/// The price of the maker order requests of at least `min_volume` base
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct VolumeTier {
+    pub min_volume: MmNumber,
+    pub price: MmNumber,
+}
+
+/// Defines the price at which the maker order answers the taker requests
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", content = "data")]
+pub enum MakerStrategy {
+    /// The order price is used for every request
+    FixedPrice,
+    /// The price is the reference price shifted by the spread in percent and kept within the band
+    PriceBand {
+        reference_price: MmNumber,
+        spread: MmNumber,
+        min_price: MmNumber,
+        max_price: MmNumber,
+    },
+    /// The price of the largest tier reached by the requested volume, the order price is used below the tiers
+    VolumeTiers(Vec<VolumeTier>),
+}
+
+impl Default for MakerStrategy {
+    fn default() -> MakerStrategy { MakerStrategy::FixedPrice }
+}
+
+impl MakerStrategy {
+    fn validate(&self) -> Result<(), String> {
+        let min_price = MmNumber::from(BigRational::new(1.into(), 100000000.into()));
+        match self {
+            MakerStrategy::FixedPrice => (),
+            MakerStrategy::PriceBand { reference_price, min_price: band_min, max_price: band_max, .. } => {
+                if *reference_price <= MmNumber::default() {
+                    return ERR!("Reference price must be greater than zero");
+                }
+                if *band_min < min_price {
+                    return ERR!("Min price is too low, minimum is 0.00000001");
+                }
+                if band_min > band_max {
+                    return ERR!("Min price {} is larger than max price {}", band_min, band_max);
+                }
+            },
+            MakerStrategy::VolumeTiers(tiers) => {
+                if tiers.is_empty() {
+                    return ERR!("Volume tiers must not be empty");
+                }
+                if tiers.iter().any(|tier| tier.price < min_price) {
+                    return ERR!("Tier price is too low, minimum is 0.00000001");
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Returns the price of the request of the `volume` base
+    fn price(&self, order_price: &MmNumber, volume: &MmNumber) -> MmNumber {
+        match self {
+            MakerStrategy::FixedPrice => order_price.clone(),
+            MakerStrategy::PriceBand { reference_price, spread, min_price, max_price } => {
+                let price = reference_price * &(MmNumber::from(100) + spread.clone()) / MmNumber::from(100);
+                if price < *min_price {
+                    min_price.clone()
+                } else if price > *max_price {
+                    max_price.clone()
+                } else {
+                    price
+                }
+            },
+            MakerStrategy::VolumeTiers(tiers) => tiers.iter()
+                .filter(|tier| *volume >= tier.min_volume)
+                .max_by(|a, b| a.min_volume.partial_cmp(&b.min_volume).unwrap_or(Ordering::Equal))
+                .map(|tier| tier.price.clone())
+                .unwrap_or(order_price.clone()),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -159,6 +340,8 @@ pub struct MakerOrder {
     matches: HashMap<Uuid, MakerMatch>,
     started_swaps: Vec<Uuid>,
     uuid: Uuid,
+    #[serde(default)]
+    strategy: MakerStrategy,
 }
 
 fn zero_rat() -> BigRational { BigRational::zero() }
@@ -176,6 +359,19 @@ impl MakerOrder {
         !self.has_ongoing_matches()
     }
 
+    /// Returns the price of the request of the `volume` base defined by the order strategy
+    fn price_for_volume(&self, volume: &MmNumber) -> MmNumber {
+        self.strategy.price(&self.price_rat.clone().into(), volume)
+    }
+
+    /// Returns the price published by the price pings, the price band orders are matched at the band price
+    fn advertised_price(&self) -> MmNumber {
+        match &self.strategy {
+            MakerStrategy::PriceBand { .. } => self.price_for_volume(&MmNumber::default()),
+            MakerStrategy::FixedPrice | MakerStrategy::VolumeTiers(_) => self.price_rat.clone().into(),
+        }
+    }
+
     fn has_ongoing_matches(&self) -> bool {
         for (_, order_match) in self.matches.iter() {
             // if there's at least 1 ongoing match the order is not cancellable
@@ -185,16 +381,65 @@ impl MakerOrder {
         }
         return false;
     }
+
+    /// Sum of base amounts reserved by the order matches
+    fn reserved_amount(&self) -> MmNumber {
+        MmNumber::from(self.max_base_vol_rat.clone()) - self.available_amount()
+    }
+
+    /// Changes the order price and volumes in place keeping the uuid and existing matches.
+    /// The new max volume can't be lower than the amount that is already reserved by the matches.
+    /// The new price of the price band order is its new reference price, the order gets the resulting band price.
+    fn apply_update(&mut self, update: &MakerOrderUpdate) -> Result<(), String> {
+        let min_price = MmNumber::from(BigRational::new(1.into(), 100000000.into()));
+        let price: MmNumber = update.price.clone().unwrap_or(self.price_rat.clone().into());
+        if price < min_price {
+            return ERR!("Price is too low, minimum is 0.00000001");
+        }
+
+        let max_vol: MmNumber = update.volume.clone().unwrap_or(self.max_base_vol_rat.clone().into());
+        let reserved = self.reserved_amount();
+        if max_vol < reserved {
+            return ERR!("New volume {} is lower than the amount {} reserved by ongoing matches", max_vol, reserved);
+        }
+
+        let min_vol: MmNumber = update.min_volume.clone().unwrap_or(self.min_base_vol_rat.clone().into());
+        if min_vol < MmNumber::default() {
+            return ERR!("Min volume {} must not be negative", min_vol);
+        }
+        if min_vol > max_vol {
+            return ERR!("Min volume {} is larger than max volume {}", min_vol, max_vol);
+        }
+
+        let price = match &mut self.strategy {
+            MakerStrategy::PriceBand { reference_price, .. } => {
+                if update.price.is_some() {
+                    *reference_price = price.clone();
+                }
+                self.strategy.price(&price, &MmNumber::default())
+            },
+            MakerStrategy::FixedPrice | MakerStrategy::VolumeTiers(_) => price,
+        };
+        self.price = price.clone().into();
+        self.price_rat = price.into();
+        self.max_base_vol = max_vol.clone().into();
+        self.max_base_vol_rat = max_vol.into();
+        self.min_base_vol = min_vol.clone().into();
+        self.min_base_vol_rat = min_vol.into();
+        Ok(())
+    }
 }
 
 impl Into<MakerOrder> for TakerOrder {
     fn into(self) -> MakerOrder {
+        // the maker order is created with the volume that is not matched yet
+        let remaining = self.remaining_base_amount();
         let order = match self.request.action {
             TakerAction::Sell => MakerOrder {
                 price: &self.request.rel_amount / &self.request.base_amount,
                 price_rat: (self.request.get_rel_amount() / self.request.get_base_amount()).into(),
-                max_base_vol_rat: self.request.get_base_amount().into(),
-                max_base_vol: self.request.base_amount,
+                max_base_vol_rat: remaining.clone().into(),
+                max_base_vol: remaining.into(),
                 min_base_vol_rat: BigRational::from_integer(0.into()),
                 min_base_vol: 0.into(),
                 created_at: now_ms(),
@@ -203,21 +448,26 @@ impl Into<MakerOrder> for TakerOrder {
                 matches: HashMap::new(),
                 started_swaps: Vec::new(),
                 uuid: self.request.uuid,
+                strategy: MakerStrategy::FixedPrice,
             },
             // The "buy" taker order is recreated with reversed pair as Maker order is always considered as "sell"
-            TakerAction::Buy => MakerOrder {
-                price: &self.request.base_amount / &self.request.rel_amount,
-                price_rat: (self.request.get_base_amount() / self.request.get_rel_amount()).into(),
-                max_base_vol_rat: self.request.get_rel_amount().into(),
-                max_base_vol: self.request.rel_amount,
-                min_base_vol: 0.into(),
-                min_base_vol_rat: BigRational::from_integer(0.into()),
-                created_at: now_ms(),
-                base: self.request.rel,
-                rel: self.request.base,
-                matches: HashMap::new(),
-                started_swaps: Vec::new(),
-                uuid: self.request.uuid,
+            TakerAction::Buy => {
+                let remaining_rel = remaining * self.request.get_rel_amount() / self.request.get_base_amount();
+                MakerOrder {
+                    price: &self.request.base_amount / &self.request.rel_amount,
+                    price_rat: (self.request.get_base_amount() / self.request.get_rel_amount()).into(),
+                    max_base_vol_rat: remaining_rel.clone().into(),
+                    max_base_vol: remaining_rel.into(),
+                    min_base_vol: 0.into(),
+                    min_base_vol_rat: BigRational::from_integer(0.into()),
+                    created_at: now_ms(),
+                    base: self.request.rel,
+                    rel: self.request.base,
+                    matches: HashMap::new(),
+                    started_swaps: Vec::new(),
+                    uuid: self.request.uuid,
+                    strategy: MakerStrategy::FixedPrice,
+                }
             },
         };
         order
@@ -231,6 +481,16 @@ struct TakerConnect {
     method: String,
     sender_pubkey: H256Json,
     dest_pub_key: H256Json,
+    /// Taker order can start several swaps when it's partially filled, so each of them gets its own uuid
+    #[serde(default)]
+    swap_uuid: Option<Uuid>,
+}
+
+impl TakerConnect {
+    fn swap_uuid(&self) -> Uuid {
+        // the nodes that don't support partial fills use the taker order uuid for the swap
+        self.swap_uuid.unwrap_or(self.taker_order_uuid)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -273,12 +533,269 @@ struct MakerConnected {
     dest_pub_key: H256Json,
 }
 
+/// The max number of orderbook updates kept in the history.
+/// The subscribers that fall behind further than that are resynchronized with a snapshot.
+const MAX_ORDERBOOK_UPDATES: usize = 10000;
+/// The max time the orderbook_updates RPC waits for new updates, in seconds
+const MAX_ORDERBOOK_UPDATES_TIMEOUT: u64 = 60;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum OrderbookUpdateKind {
+    Added,
+    Updated,
+    Removed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct OrderbookUpdate {
+    seq: u64,
+    kind: OrderbookUpdateKind,
+    base: String,
+    rel: String,
+    uuid: Uuid,
+    /// The new state of the order, None if the order is removed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order: Option<PricePingRequest>,
+}
+
+/// The incremental changes of the orderbook.
+/// Must be locked after the `OrdermatchContext::orderbook` so the snapshot and the sequence number are consistent.
+#[derive(Default)]
+struct OrderbookUpdates {
+    /// The sequence number of the latest update
+    seq: u64,
+    history: VecDeque<OrderbookUpdate>,
+}
+
+impl OrderbookUpdates {
+    fn push(&mut self, kind: OrderbookUpdateKind, base: &str, rel: &str, uuid: Uuid, order: Option<PricePingRequest>) {
+        self.seq += 1;
+        self.history.push_back(OrderbookUpdate {
+            seq: self.seq,
+            kind,
+            base: base.into(),
+            rel: rel.into(),
+            uuid,
+            order,
+        });
+        if self.history.len() > MAX_ORDERBOOK_UPDATES {
+            self.history.pop_front();
+        }
+    }
+
+    /// Returns the updates of the base/rel pair (in both directions) made after the `from_seq`
+    /// or None if some of them are not available anymore and the subscriber has to start from a snapshot.
+    fn updates_since(&self, base: &str, rel: &str, from_seq: u64) -> Option<Vec<OrderbookUpdate>> {
+        if from_seq > self.seq {
+            return None;
+        }
+        match self.history.front() {
+            Some(oldest) if oldest.seq > from_seq + 1 => return None,
+            None if from_seq < self.seq => return None,
+            _ => (),
+        }
+        Some(self.history.iter().filter(|update| update.seq > from_seq &&
+            ((update.base == base && update.rel == rel) || (update.base == rel && update.rel == base))
+        ).cloned().collect())
+    }
+}
+
+/// The trading limits of the node configured in the MM2 conf, e.g.
+/// "allowed_pairs": ["RICK/MORTY"], "denied_pairs": ["RICK/BTC"], "max_open_volume": {"RICK": "100"}, "max_swaps_per_pair": 2
+#[derive(Debug, Default, Deserialize)]
+struct PairLimits {
+    /// Only these pairs can be traded if set, the pairs are matched in both directions
+    #[serde(default)]
+    allowed_pairs: Option<Vec<String>>,
+    /// These pairs can't be traded, the pairs are matched in both directions
+    #[serde(default)]
+    denied_pairs: Vec<String>,
+    /// Max volume of the coin that can be offered by my open orders at the same time
+    #[serde(default)]
+    max_open_volume: HashMap<String, MmNumber>,
+    /// Max number of the concurrent swaps of the pair
+    #[serde(default)]
+    max_swaps_per_pair: Option<usize>,
+}
+
+impl PairLimits {
+    fn from_conf(conf: &Json) -> Result<PairLimits, String> {
+        if conf.is_null() {
+            return Ok(PairLimits::default());
+        }
+        let limits: PairLimits = try_s!(json::from_value(conf.clone()));
+        let pairs = limits.allowed_pairs.iter().flatten().chain(limits.denied_pairs.iter());
+        for pair in pairs {
+            let mut split = pair.split('/');
+            match (split.next(), split.next(), split.next()) {
+                (Some(base), Some(rel), None) if !base.is_empty() && !rel.is_empty() => (),
+                _ => return ERR!("The pair {} must be in the BASE/REL format", pair),
+            }
+        }
+        Ok(limits)
+    }
+
+    fn is_pair_allowed(&self, base: &str, rel: &str) -> bool {
+        let is_listed = |pairs: &[String]| pairs.iter().any(|pair| *pair == format!("{}/{}", base, rel) || *pair == format!("{}/{}", rel, base));
+        if is_listed(&self.denied_pairs) {
+            return false;
+        }
+        match &self.allowed_pairs {
+            Some(allowed) => is_listed(allowed),
+            None => true,
+        }
+    }
+
+    fn check_pair(&self, base: &str, rel: &str) -> Result<(), String> {
+        if !self.is_pair_allowed(base, rel) {
+            return ERR!("Trading of {}/{} pair is not allowed by the node configuration", base, rel);
+        }
+        Ok(())
+    }
+
+    /// Checks that my open orders volume of the coin doesn't exceed the limit with the new order volume added
+    fn check_open_volume(&self, coin: &str, open_volume: MmNumber, new_volume: &MmNumber) -> Result<(), String> {
+        if let Some(max) = self.max_open_volume.get(coin) {
+            let total = open_volume + new_volume.clone();
+            if total > *max {
+                return ERR!("Open volume {} of {} would exceed the max {}", total, coin, max);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_swaps_count(&self, base: &str, rel: &str, active_swaps: usize) -> Result<(), String> {
+        match self.max_swaps_per_pair {
+            Some(max) if active_swaps >= max => ERR!("There are already {} swaps of {}/{} pair, max is {}", active_swaps, base, rel, max),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The volume of the coin offered by my open orders. The maker orders offer their base,
+/// the taker orders offer the base when selling and the rel when buying.
+fn my_open_volume<'a>(
+    maker_orders: impl Iterator<Item=&'a MakerOrder>,
+    taker_orders: impl Iterator<Item=&'a TakerOrder>,
+    coin: &str,
+) -> MmNumber {
+    let maker_volume = maker_orders.filter(|order| order.base == coin)
+        .fold(MmNumber::default(), |total, order| total + order.available_amount());
+    taker_orders.fold(maker_volume, |total, order| {
+        let remaining = order.remaining_base_amount();
+        match order.request.action {
+            TakerAction::Sell if order.request.base == coin => total + remaining,
+            TakerAction::Buy if order.request.rel == coin =>
+                total + remaining * order.request.get_rel_amount() / order.request.get_base_amount(),
+            _ => total,
+        }
+    })
+}
+
+/// Checks the new order of the pair offering the `volume` of the `coin` against the pair limits of the node.
+/// The maker orders that are replaced by the new one are excluded from the open volume.
+fn check_pair_limits(
+    ctx: &MmArc,
+    base: &str,
+    rel: &str,
+    coin: &str,
+    volume: &MmNumber,
+    is_replaced: impl Fn(&MakerOrder) -> bool,
+) -> Result<(), String> {
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    try_s!(ordermatch_ctx.pair_limits.check_pair(base, rel));
+    let maker_volume = {
+        let maker_orders = try_s!(ordermatch_ctx.my_maker_orders.lock());
+        my_open_volume(maker_orders.values().filter(|order| !is_replaced(order)), std::iter::empty(), coin)
+    };
+    let taker_volume = {
+        let taker_orders = try_s!(ordermatch_ctx.my_taker_orders.lock());
+        my_open_volume(std::iter::empty(), taker_orders.values(), coin)
+    };
+    try_s!(ordermatch_ctx.pair_limits.check_open_volume(coin, maker_volume + taker_volume, volume));
+    Ok(())
+}
+
+/// Checks that the new swap of the pair doesn't exceed the max concurrent swaps of the node
+fn check_pair_swaps_limit(ctx: &MmArc, base: &str, rel: &str) -> Result<(), String> {
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    let active_swaps = try_s!(active_swaps_count_for_pair(ctx, base, rel));
+    try_s!(ordermatch_ctx.pair_limits.check_swaps_count(base, rel, active_swaps));
+    Ok(())
+}
+
+/// The window of the taker requests rate limit, in milliseconds
+const TAKER_REQUESTS_WINDOW: u64 = 60000;
+/// The max number of the requests we process from the same taker during the `TAKER_REQUESTS_WINDOW`
+const MAX_TAKER_REQUESTS_PER_WINDOW: usize = 30;
+/// The max number of my maker orders reserved by the same taker and not connected yet
+const MAX_RESERVATIONS_PER_TAKER: usize = 3;
+/// The taker pubkey is banned when it exceeds the limits this many times
+const MAX_TAKER_VIOLATIONS: u32 = 10;
+
+/// The recent requests of the taker and the number of the times it exceeded the limits
+#[derive(Debug, Default)]
+struct TakerRequestsCounter {
+    /// The timestamps of the requests received during the `TAKER_REQUESTS_WINDOW`, in milliseconds
+    requests: VecDeque<u64>,
+    violations: u32,
+}
+
+impl TakerRequestsCounter {
+    /// Registers the request received at `now`, returns false if the taker exceeds the rate limit
+    fn register_request(&mut self, now: u64) -> bool {
+        while let Some(received_at) = self.requests.front() {
+            if received_at + TAKER_REQUESTS_WINDOW > now { break }
+            self.requests.pop_front();
+        }
+        if self.requests.len() >= MAX_TAKER_REQUESTS_PER_WINDOW {
+            return false;
+        }
+        self.requests.push_back(now);
+        true
+    }
+
+    /// Registers the limit violation, returns true if the taker has to be banned
+    fn register_violation(&mut self) -> bool {
+        self.violations += 1;
+        self.violations >= MAX_TAKER_VIOLATIONS
+    }
+}
+
+/// The number of my maker orders matches reserved by the taker and not connected yet
+fn taker_reservations_count(my_orders: &HashMap<Uuid, MakerOrder>, taker_pubkey: &H256Json) -> usize {
+    my_orders.values()
+        .flat_map(|order| order.matches.values())
+        .filter(|order_match| order_match.connected.is_none() && &order_match.request.sender_pubkey == taker_pubkey)
+        .count()
+}
+
 struct OrdermatchContext {
     pub my_maker_orders: Mutex<HashMap<Uuid, MakerOrder>>,
     pub my_taker_orders: Mutex<HashMap<Uuid, TakerOrder>>,
     pub my_cancelled_orders: Mutex<HashMap<Uuid, MakerOrder>>,
     /// A map from (base, rel)
     pub orderbook: Mutex<HashMap<(String, String), HashMap<Uuid, PricePingRequest>>>,
+    pub orderbook_updates: Mutex<OrderbookUpdates>,
+    pub my_conditional_orders: Mutex<HashMap<Uuid, ConditionalOrder>>,
+    pub pair_limits: PairLimits,
+    /// The nonces of the recently received ordermatch messages with their timestamps
+    pub message_nonces: Mutex<HashMap<String, u64>>,
+    /// The nodes that have sent the signed ordermatch messages, their unsigned messages are never accepted
+    pub signed_senders: Mutex<HashSet<H256Json>>,
+    /// The time of the last snapshot request of the (base, rel) pair
+    pub snapshot_requests: Mutex<HashMap<(String, String), u64>>,
+    /// The time of the last snapshot response to the (node, base, rel)
+    pub snapshot_responses: Mutex<HashMap<(H256Json, String, String), u64>>,
+    /// The rate limit counters of the taker requests by the sender pubkey
+    taker_requests: Mutex<HashMap<H256Json, TakerRequestsCounter>>,
+}
+
+/// Creates the ordermatch context at startup, so the invalid pair limits fail the start instead of the RPC calls
+pub fn init_ordermatch_context(ctx: &MmArc) -> Result<(), String> {
+    try_s!(OrdermatchContext::from_ctx(ctx));
+    Ok(())
 }
 
 impl OrdermatchContext {
@@ -290,6 +807,14 @@ impl OrdermatchContext {
                 my_maker_orders: Mutex::new (HashMap::default()),
                 my_cancelled_orders: Mutex::new (HashMap::default()),
                 orderbook: Mutex::new (HashMap::default()),
+                orderbook_updates: Mutex::new (OrderbookUpdates::default()),
+                my_conditional_orders: Mutex::new (HashMap::default()),
+                pair_limits: try_s! (PairLimits::from_conf (&ctx.conf)),
+                message_nonces: Mutex::new (HashMap::default()),
+                signed_senders: Mutex::new (HashSet::default()),
+                snapshot_requests: Mutex::new (HashMap::default()),
+                snapshot_responses: Mutex::new (HashMap::default()),
+                taker_requests: Mutex::new (HashMap::default()),
             })
         })))
     }
@@ -321,7 +846,10 @@ fn lp_connect_start_bob(ctx: MmArc, maker_match: MakerMatch) {
         let taker_amount = maker_match.reserved.get_rel_amount().into();
         let privkey = &ctx.secp256k1_key_pair().private().secret;
         let my_persistent_pub = unwrap!(compressed_pub_key_from_priv_raw(&privkey[..], ChecksumType::DSHA256));
-        let uuid = maker_match.request.uuid.to_string();
+        let uuid = match &maker_match.connect {
+            Some(connect) => connect.swap_uuid(),
+            None => maker_match.request.uuid,
+        }.to_string();
 
         log!("Entering the maker_swap_loop " (maker_coin.ticker()) "/" (taker_coin.ticker()));
         let maker_swap = MakerSwap::new(
@@ -370,7 +898,7 @@ fn lp_connected_alice(ctx: MmArc, taker_match: TakerMatch) {
         let my_persistent_pub = unwrap!(compressed_pub_key_from_priv_raw(&privkey[..], ChecksumType::DSHA256));
         let maker_amount = taker_match.reserved.get_base_amount().into();
         let taker_amount = taker_match.reserved.get_rel_amount().into();
-        let uuid = taker_match.reserved.taker_order_uuid.to_string();
+        let uuid = taker_match.connect.swap_uuid().to_string();
 
         log!("Entering the taker_swap_loop " (maker_coin.ticker()) "/" (taker_coin.ticker()));
         let taker_swap = TakerSwap::new(
@@ -387,9 +915,143 @@ fn lp_connected_alice(ctx: MmArc, taker_match: TakerMatch) {
     });
 }
 
+const ORDERMATCH_TIMEOUT: u64 = 30000;
+
+/// Checks the request rate and the number of the concurrent reservations of the taker.
+/// The taker exceeding the limits repeatedly is banned.
+fn check_taker_request_limits(
+    ctx: &MmArc,
+    ordermatch_ctx: &OrdermatchContext,
+    my_orders: &HashMap<Uuid, MakerOrder>,
+    taker_pubkey: &H256Json,
+) -> Result<(), String> {
+    let now = now_ms();
+    let mut counters = try_s!(ordermatch_ctx.taker_requests.lock());
+    // forget the takers that didn't send the requests during the window, including their violations
+    counters.retain(|_, counter| counter.requests.back().map_or(false, |last| last + TAKER_REQUESTS_WINDOW > now));
+    let counter = counters.entry(taker_pubkey.clone()).or_insert_with(TakerRequestsCounter::default);
+
+    let error = if !counter.register_request(now) {
+        ERRL!("taker {} exceeded {} requests per {} ms", hex::encode(&taker_pubkey.0), MAX_TAKER_REQUESTS_PER_WINDOW, TAKER_REQUESTS_WINDOW)
+    } else if taker_reservations_count(my_orders, taker_pubkey) >= MAX_RESERVATIONS_PER_TAKER {
+        ERRL!("taker {} already has {} reservations", hex::encode(&taker_pubkey.0), MAX_RESERVATIONS_PER_TAKER)
+    } else {
+        return Ok(());
+    };
+
+    if counter.register_violation() {
+        log!("Banning the taker " [taker_pubkey] " for exceeding the request limits " (MAX_TAKER_VIOLATIONS) " times");
+        ban_pubkey(ctx, taker_pubkey.clone().into());
+        counters.remove(taker_pubkey);
+    }
+    Err(error)
+}
+
+/// Removes the timed out taker orders.
+/// The unmatched volume of GoodTillCancelled orders is moved to maker orders, the other ones expire.
+/// The expiry is recorded to my orders history, so the `order_status` can report it.
+fn handle_timed_out_taker_orders(
+    ctx: &MmArc,
+    my_taker_orders: &mut HashMap<Uuid, TakerOrder>,
+    my_maker_orders: &mut HashMap<Uuid, MakerOrder>,
+) {
+    *my_taker_orders = my_taker_orders.drain().filter_map(|(uuid, mut order)| if order.created_at + ORDERMATCH_TIMEOUT < now_ms() {
+        delete_my_taker_order(ctx, &order);
+        // the swaps are not started for the matches that are not connected yet so their volume is released
+        order.matches.retain(|_, order_match| order_match.connected.is_some());
+        if order.remaining_base_amount() > MmNumber::default() {
+            match order.order_type {
+                OrderType::GoodTillCancelled => {
+                    let maker_order = order.into();
+                    save_my_maker_order(ctx, &maker_order);
+                    add_maker_order_history_event(ctx, &maker_order, OrderHistoryEvent::ConvertedToMaker);
+                    my_maker_orders.insert(uuid, maker_order);
+                },
+                // the FillOrKill order is reserved for its whole volume only, so it's killed entirely
+                OrderType::FillOrKill => {
+                    log!("FillOrKill taker order " (uuid) " is not filled and expired");
+                    add_taker_order_history_event(ctx, &order, OrderHistoryEvent::Expired);
+                },
+                // the swaps of the connected matches go on, only the unmatched volume expires
+                OrderType::ImmediateOrCancel => {
+                    log!("ImmediateOrCancel taker order " (uuid) " is expired with " (order.matches.len()) " matches");
+                    add_taker_order_history_event(ctx, &order, OrderHistoryEvent::Expired);
+                },
+            }
+        } else {
+            add_taker_order_history_event(ctx, &order, OrderHistoryEvent::Filled);
+        }
+        None
+    } else {
+        Some((uuid, order))
+    }).collect();
+}
+
+/// Price of the reservation from the taker point of view, the lower the better
+fn reserved_price(reserved: &MakerReserved) -> MmNumber {
+    reserved.get_rel_amount() / reserved.get_base_amount()
+}
+
+/// Sends "connect" messages to the pending reservations starting from the best priced
+/// until the taker order volume is covered.
+fn connect_to_reserved(ctx: &MmArc, order: &mut TakerOrder) -> Result<(), String> {
+    if order.pending_reservations.is_empty() || now_ms() < order.created_at + order.reservations_window {
+        return Ok(());
+    }
+
+    let our_public_id = try_s!(ctx.public_id());
+    let mut pending: Vec<_> = order.pending_reservations.drain(..).collect();
+    let mut reputations = HashMap::new();
+    if order.tie_breaker == TieBreaker::Reputation && pending.len() > 1 {
+        for reserved in pending.iter() {
+            if let Entry::Vacant(entry) = reputations.entry(reserved.sender_pubkey.0) {
+                entry.insert(try_s!(successful_swaps_count(ctx, &reserved.sender_pubkey)));
+            }
+        }
+    }
+    let reputation = |reserved: &MakerReserved| reputations.get(&reserved.sender_pubkey.0).cloned().unwrap_or(0);
+    // the sort is stable so the equally priced reservations are kept in the order of arrival if the reputation is equal
+    pending.sort_by(|a, b| reserved_price(a).partial_cmp(&reserved_price(b)).unwrap_or(Ordering::Equal)
+        .then_with(|| reputation(b).cmp(&reputation(a))));
+    for reserved in pending {
+        if order.match_reserved(&reserved) != MatchReservedResult::Matched {
+            continue;
+        }
+        // the order without partial fills can't be reserved by someone else, the FillOrKill order is filled at once
+        if (order.order_type == OrderType::FillOrKill || !order.request.allow_partial_fill) && !order.matches.is_empty() {
+            continue;
+        }
+
+        let swap_uuid = if order.matches.is_empty() { order.request.uuid } else { new_uuid() };
+        let connect = TakerConnect {
+            sender_pubkey: H256Json::from(our_public_id.bytes),
+            dest_pub_key: reserved.sender_pubkey.clone(),
+            method: "connect".into(),
+            taker_order_uuid: reserved.taker_order_uuid,
+            maker_order_uuid: reserved.maker_order_uuid,
+            swap_uuid: Some(swap_uuid),
+        };
+        ctx.broadcast_p2p_msg(&try_s!(sign_ordermatch_message(ctx, &connect)));
+        let taker_match = TakerMatch {
+            reserved,
+            connect,
+            connected: None,
+            last_updated: now_ms(),
+        };
+        let other_order_uuid = taker_match.reserved.maker_order_uuid;
+        order.matches.insert(other_order_uuid, taker_match);
+        add_taker_order_history_event(ctx, order, OrderHistoryEvent::Matched { other_order_uuid });
+    }
+    save_my_taker_order(ctx, order);
+    Ok(())
+}
+
 pub async fn lp_ordermatch_loop(ctx: MmArc) {
-    const ORDERMATCH_TIMEOUT: u64 = 30000;
     let mut last_price_broadcast = 0;
+    // the reputation of makers is read from my swaps files here once instead of under the orders locks
+    if let Err(e) = init_successful_swaps(&ctx) {
+        ctx.log.log("", &[&"init_successful_swaps"], &format!("error {}", e));
+    }
 
     loop {
         if ctx.is_stopping() { break }
@@ -398,18 +1060,13 @@ pub async fn lp_ordermatch_loop(ctx: MmArc) {
             let mut my_taker_orders = unwrap!(ordermatch_ctx.my_taker_orders.lock());
             let mut my_maker_orders = unwrap!(ordermatch_ctx.my_maker_orders.lock());
             let mut my_cancelled_orders = unwrap!(ordermatch_ctx.my_cancelled_orders.lock());
-            // move the timed out and unmatched taker orders to maker
-            *my_taker_orders = my_taker_orders.drain().filter_map(|(uuid, order)| if order.created_at + ORDERMATCH_TIMEOUT < now_ms() {
-                delete_my_taker_order(&ctx, &order);
-                if order.matches.is_empty() {
-                    let maker_order = order.into();
-                    save_my_maker_order(&ctx, &maker_order);
-                    my_maker_orders.insert(uuid, maker_order);
+            // move the timed out and unmatched GoodTillCancelled taker orders to maker
+            handle_timed_out_taker_orders(&ctx, &mut my_taker_orders, &mut my_maker_orders);
+            for (_, order) in my_taker_orders.iter_mut() {
+                if let Err(e) = connect_to_reserved(&ctx, order) {
+                    ctx.log.log("", &[&"connect_to_reserved"], &format!("error {}", e));
                 }
-                None
-            } else {
-                Some((uuid, order))
-            }).collect();
+            }
             // remove timed out unfinished matches to unlock the reserved amount
             my_maker_orders.iter_mut().for_each(|(_, order)| {
                 order.matches = order.matches.drain().filter(
@@ -420,8 +1077,13 @@ pub async fn lp_ordermatch_loop(ctx: MmArc) {
             *my_maker_orders = my_maker_orders.drain().filter_map(|(uuid, order)| {
                 let min_amount: BigDecimal = "0.00777".parse().unwrap();
                 let min_amount: MmNumber = min_amount.into();
-                if order.available_amount() <= min_amount && !order.has_ongoing_matches() {
+                let min_vol: MmNumber = order.min_base_vol_rat.clone().into();
+                let available = order.available_amount();
+                // the order can't be matched anymore if its available amount is lower than min volume
+                if (available <= min_amount || available < min_vol) && !order.has_ongoing_matches() {
                     delete_my_maker_order(&ctx, &order);
+                    let event = if order.started_swaps.is_empty() { OrderHistoryEvent::Cancelled } else { OrderHistoryEvent::Filled };
+                    add_maker_order_history_event(&ctx, &order, event);
                     my_cancelled_orders.insert(uuid, order);
                     None
                 } else {
@@ -430,6 +1092,15 @@ pub async fn lp_ordermatch_loop(ctx: MmArc) {
             }).collect();
         }
 
+        match triggered_conditional_orders(&ctx) {
+            // the orders are submitted in background so the slow coin RPCs don't hold the matching
+            Ok(triggered) => for order in triggered {
+                let ctx = ctx.clone();
+                spawn(async move { submit_conditional_order(&ctx, order).await });
+            },
+            Err(e) => ctx.log.log("", &[&"conditional_orders"], &format!("error {}", e)),
+        }
+
         if now_ms() > last_price_broadcast + 10000 {
             if let Err(e) = broadcast_my_maker_orders(&ctx).await {
                 ctx.log.log("", &[&"broadcast_my_maker_orders"], &format!("error {}", e));
@@ -441,18 +1112,16 @@ pub async fn lp_ordermatch_loop(ctx: MmArc) {
             // remove "timed out" orders from orderbook
             // ones that didn't receive an update for 30 seconds or more
             let mut orderbook = unwrap!(ordermatch_ctx.orderbook.lock());
-            *orderbook = orderbook.drain().filter_map(|((base, rel), mut pair_orderbook)| {
-                pair_orderbook = pair_orderbook.drain().filter_map(|(pubkey, order)| if now_ms() / 1000 > order.timestamp + 30 {
-                    None
-                } else {
-                    Some((pubkey, order))
-                }).collect();
-                if pair_orderbook.is_empty() {
-                    None
+            let mut updates = unwrap!(ordermatch_ctx.orderbook_updates.lock());
+            orderbook.retain(|(base, rel), pair_orderbook| {
+                pair_orderbook.retain(|uuid, order| if now_ms() / 1000 > order.timestamp + ORDERBOOK_ENTRY_TIMEOUT {
+                    updates.push(OrderbookUpdateKind::Removed, base, rel, *uuid, None);
+                    false
                 } else {
-                    Some(((base, rel), pair_orderbook))
-                }
-            }).collect();
+                    true
+                });
+                !pair_orderbook.is_empty()
+            });
         }
 
         Timer::sleep(0.777).await;
@@ -479,6 +1148,10 @@ pub fn lp_trade_command(
             // ignore the messages that do not target our node
             return 1;
         }
+        if let Err(e) = verify_ordermatch_message(&ctx, &ordermatch_ctx, &json, &reserved_msg) {
+            log!("Reserved message from " [reserved_msg.sender_pubkey] " is rejected: " (e));
+            return 1;
+        }
 
         let mut my_taker_orders = unwrap!(ordermatch_ctx.my_taker_orders.lock());
         let my_order = match my_taker_orders.entry(reserved_msg.taker_order_uuid) {
@@ -494,25 +1167,18 @@ pub fn lp_trade_command(
             return 1;
         }
 
-        // send "connect" message if reserved message targets our pubkey AND
-        // reserved amounts match our order AND order is NOT reserved by someone else (empty matches)
-        if my_order.match_reserved(&reserved_msg) == MatchReservedResult::Matched && my_order.matches.is_empty() {
-            let connect = TakerConnect {
-                sender_pubkey: H256Json::from(our_public_id.bytes),
-                dest_pub_key: reserved_msg.sender_pubkey.clone(),
-                method: "connect".into(),
-                taker_order_uuid: reserved_msg.taker_order_uuid,
-                maker_order_uuid: reserved_msg.maker_order_uuid,
-            };
-            ctx.broadcast_p2p_msg(&unwrap!(json::to_string(&connect)));
-            let taker_match = TakerMatch {
-                reserved: reserved_msg,
-                connect,
-                connected: None,
-                last_updated: now_ms(),
-            };
-            my_order.matches.insert(taker_match.reserved.maker_order_uuid, taker_match);
-            save_my_taker_order(&ctx, &my_order);
+        if !is_maker_min_volume_met(&ordermatch_ctx, &reserved_msg) {
+            log!("Reserved amount " (reserved_msg.get_base_amount()) " is lower than min volume of maker order " (reserved_msg.maker_order_uuid));
+            return 1;
+        }
+
+        // collect the reservation if reserved message targets our pubkey AND reserved amounts match our order,
+        // the "connect" message is sent to the best priced reservations by the ordermatch loop
+        let is_known = my_order.matches.contains_key(&reserved_msg.maker_order_uuid)
+            || my_order.pending_reservations.iter().any(|r| r.maker_order_uuid == reserved_msg.maker_order_uuid);
+        let is_empty = reserved_msg.get_base_amount() <= MmNumber::default();
+        if my_order.match_reserved(&reserved_msg) == MatchReservedResult::Matched && !is_known && !is_empty {
+            my_order.pending_reservations.push(reserved_msg);
         }
         return 1;
     }
@@ -522,26 +1188,48 @@ pub fn lp_trade_command(
             Err(_) => return 1,
         };
         if H256Json::from(our_public_id.bytes) == connected.dest_pub_key && H256Json::from(our_public_id.bytes) != connected.sender_pubkey {
+            if let Err(e) = verify_ordermatch_message(&ctx, &ordermatch_ctx, &json, &connected) {
+                log!("Connected message from " [connected.sender_pubkey] " is rejected: " (e));
+                return 1;
+            }
             let mut my_taker_orders = unwrap!(ordermatch_ctx.my_taker_orders.lock());
-            let my_order_entry = match my_taker_orders.entry(connected.taker_order_uuid) {
+            let mut my_order_entry = match my_taker_orders.entry(connected.taker_order_uuid) {
                 Entry::Occupied(e) => e,
                 Entry::Vacant(_) => {
                     log!("Our node doesn't have the order with uuid "(connected.taker_order_uuid));
                     return 1;
                 },
             };
-            let order_match = match my_order_entry.get().matches.get(&connected.maker_order_uuid) {
+            let order_match = match my_order_entry.get_mut().matches.get_mut(&connected.maker_order_uuid) {
                 Some(o) => o,
                 None => {
                     log!("Our node doesn't have the match with uuid "(connected.maker_order_uuid));
                     return 1;
                 }
             };
+            if connected.sender_pubkey != order_match.reserved.sender_pubkey {
+                log!("Connected message for " (connected.maker_order_uuid) " is not sent by the maker that reserved it");
+                return 1;
+            }
+            if order_match.connected.is_some() {
+                log!("The match with uuid " (connected.maker_order_uuid) " is already connected");
+                return 1;
+            }
+            order_match.connected = Some(connected);
+            order_match.last_updated = now_ms();
+            let swap_uuid = order_match.connect.swap_uuid();
             // alice
             lp_connected_alice(ctx.clone(), order_match.clone());
-            // remove the matched order immediately
-            delete_my_taker_order(&ctx, &my_order_entry.get());
-            my_order_entry.remove();
+            // remove the order immediately when it's fully matched and all its swaps are started
+            let order = my_order_entry.get();
+            add_taker_order_history_event(&ctx, &order, OrderHistoryEvent::SwapStarted { swap_uuid });
+            if order.remaining_base_amount() <= MmNumber::default() && !order.has_ongoing_matches() {
+                delete_my_taker_order(&ctx, &order);
+                add_taker_order_history_event(&ctx, &order, OrderHistoryEvent::Filled);
+                my_order_entry.remove();
+            } else {
+                save_my_taker_order(&ctx, &order);
+            }
             // AG: Bob's p2p ID (`LP_mypub25519`) is in `json["srchash"]`.
             log!("CONNECTED.(" (json) ")");
         }
@@ -557,15 +1245,35 @@ pub fn lp_trade_command(
             log!("Sender pubkey " [taker_request.sender_pubkey] " is banned");
             return 1;
         }
-        if our_public_id.bytes == taker_request.dest_pub_key.0 {
+        if our_public_id.bytes == taker_request.sender_pubkey.0 {
             log!("Skip the request originating from our pubkey");
             return 1;
         }
+        // the targeted requests (e.g. of the market orders) are processed by the target maker only
+        if taker_request.dest_pub_key != H256Json::default() && taker_request.dest_pub_key.0 != our_public_id.bytes {
+            return 1;
+        }
+        // silently skip the pairs we don't trade to avoid the log flooding
+        if !ordermatch_ctx.pair_limits.is_pair_allowed(&taker_request.base, &taker_request.rel) {
+            return 1;
+        }
+        if let Err(e) = verify_ordermatch_message(&ctx, &ordermatch_ctx, &json, &taker_request) {
+            log!("Request message from " [taker_request.sender_pubkey] " is rejected: " (e));
+            return 1;
+        }
         let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
         let mut my_orders = unwrap!(ordermatch_ctx.my_maker_orders.lock());
+        if let Err(e) = check_taker_request_limits(&ctx, &ordermatch_ctx, &my_orders, &taker_request.sender_pubkey) {
+            log!("Skip the request " (taker_request.uuid) ": " (e));
+            return 1;
+        }
 
         for (uuid, order) in my_orders.iter_mut() {
             if let OrderMatchResult::Matched((base_amount, rel_amount)) = match_order_and_request(order, &taker_request) {
+                if let Err(e) = check_pair_swaps_limit(&ctx, &order.base, &order.rel) {
+                    log!("Skip the request " (taker_request.uuid) ": " (e));
+                    return 1;
+                }
                 let reserved = MakerReserved {
                     dest_pub_key: taker_request.sender_pubkey.clone(),
                     sender_pubkey: our_public_id.bytes.into(),
@@ -579,7 +1287,7 @@ pub fn lp_trade_command(
                     taker_order_uuid: taker_request.uuid,
                     maker_order_uuid: *uuid,
                 };
-                ctx.broadcast_p2p_msg(&unwrap!(json::to_string(&reserved)));
+                ctx.broadcast_p2p_msg(&unwrap!(sign_ordermatch_message(&ctx, &reserved)));
                 let maker_match = MakerMatch {
                     request: taker_request,
                     reserved,
@@ -587,8 +1295,10 @@ pub fn lp_trade_command(
                     connected: None,
                     last_updated: now_ms(),
                 };
-                order.matches.insert(maker_match.request.uuid, maker_match);
+                let other_order_uuid = maker_match.request.uuid;
+                order.matches.insert(other_order_uuid, maker_match);
                 save_my_maker_order(&ctx, &order);
+                add_maker_order_history_event(&ctx, &order, OrderHistoryEvent::Matched { other_order_uuid });
                 return 1;
             }
         }
@@ -601,6 +1311,10 @@ pub fn lp_trade_command(
             Err(_) => return 1,
         };
         if our_public_id.bytes == connect_msg.dest_pub_key.0 && our_public_id.bytes != connect_msg.sender_pubkey.0 {
+            if let Err(e) = verify_ordermatch_message(&ctx, &ordermatch_ctx, &json, &connect_msg) {
+                log!("Connect message from " [connect_msg.sender_pubkey] " is rejected: " (e));
+                return 1;
+            }
             let mut maker_orders = unwrap!(ordermatch_ctx.my_maker_orders.lock());
             let my_order = match maker_orders.get_mut(&connect_msg.maker_order_uuid) {
                 Some(o) => o,
@@ -616,6 +1330,15 @@ pub fn lp_trade_command(
                     return 1;
                 },
             };
+            if connect_msg.sender_pubkey != order_match.request.sender_pubkey {
+                log!("Connect message for " (connect_msg.taker_order_uuid) " is not sent by the taker that requested it");
+                return 1;
+            }
+            // the repeated connect message would start one more swap for the same match
+            if order_match.connect.is_some() || order_match.connected.is_some() {
+                log!("Match " (connect_msg.taker_order_uuid) " is connected already, skip the connect message");
+                return 1;
+            }
 
             let connected = MakerConnected {
                 sender_pubkey: our_public_id.bytes.into(),
@@ -624,21 +1347,57 @@ pub fn lp_trade_command(
                 maker_order_uuid: connect_msg.maker_order_uuid,
                 method: "connected".into(),
             };
-            ctx.broadcast_p2p_msg(&unwrap!(json::to_string(&connected)));
+            ctx.broadcast_p2p_msg(&unwrap!(sign_ordermatch_message(&ctx, &connected)));
+            let swap_uuid = connect_msg.swap_uuid();
+            my_order.started_swaps.push(swap_uuid);
             order_match.connect = Some(connect_msg);
             order_match.connected = Some(connected);
-            my_order.started_swaps.push(order_match.request.uuid);
             lp_connect_start_bob(ctx.clone(), order_match.clone());
             save_my_maker_order(&ctx, &my_order);
+            add_maker_order_history_event(&ctx, &my_order, OrderHistoryEvent::SwapStarted { swap_uuid });
         }
         return 1;
     }
     -1
 }
 
-async fn check_locked_coins(ctx: &MmArc, amount: &MmNumber, balance: &BigDecimal, ticker: &str) -> Result<(), String> {
-    let locked = get_locked_amount(ctx, ticker);
-    let available = balance - &locked;
+/// Checks the volume of the request targeting the specific maker against the min volume of its orders in the orderbook,
+/// there is no point in sending the request if the maker can't reserve any of its orders for it.
+fn check_dest_maker_min_volume(ordermatch_ctx: &OrdermatchContext, request: &TakerRequest) -> Result<(), String> {
+    if request.dest_pub_key == H256Json::default() { return Ok(()) }
+    let dest_pubkey = hex::encode(&request.dest_pub_key.0);
+    // the min volume of the maker order is in its base coin
+    let (pair, volume) = match request.action {
+        TakerAction::Buy => ((request.base.clone(), request.rel.clone()), request.get_base_amount()),
+        TakerAction::Sell => ((request.rel.clone(), request.base.clone()), request.get_rel_amount()),
+    };
+    let orderbook = try_s!(ordermatch_ctx.orderbook.lock());
+    let min_volumes: Vec<MmNumber> = orderbook.get(&pair).into_iter()
+        .flat_map(|pair_orders| pair_orders.values())
+        .filter(|order| order.pubkey == dest_pubkey)
+        .map(|order| order.get_min_volume())
+        .collect();
+    if !min_volumes.is_empty() && min_volumes.iter().all(|min_volume| *min_volume > volume) {
+        return ERR!("The volume {} is lower than the min volume of every {}/{} order of the maker {}", volume, pair.0, pair.1, dest_pubkey);
+    }
+    Ok(())
+}
+
+/// Checks the reserved amount against the min volume advertised by the maker order in the orderbook.
+/// The maker should never reserve less than its own min volume, but we can't rely on the remote node.
+fn is_maker_min_volume_met(ordermatch_ctx: &OrdermatchContext, reserved: &MakerReserved) -> bool {
+    let orderbook = unwrap!(ordermatch_ctx.orderbook.lock());
+    let maker_order = orderbook.get(&(reserved.base.clone(), reserved.rel.clone()))
+        .and_then(|pair_orders| pair_orders.get(&reserved.maker_order_uuid));
+    match maker_order {
+        Some(order) => reserved.get_base_amount() >= order.get_min_volume(),
+        None => true,
+    }
+}
+
+async fn check_locked_coins(ctx: &MmArc, amount: &MmNumber, balance: &BigDecimal, ticker: &str) -> Result<(), String> {
+    let locked = get_locked_amount(ctx, ticker);
+    let available = balance - &locked;
     if amount > &available {
         ERR!("The {} amount {} is larger than available {:.8}, balance: {}, locked by swaps: {:.8}", ticker, amount, available, balance, locked)
     } else {
@@ -660,7 +1419,17 @@ pub struct AutoBuyInput {
     gui: Option<String>,
     #[serde(rename="destpubkey")]
     #[serde(default)]
-    dest_pub_key: H256Json
+    dest_pub_key: H256Json,
+    #[serde(default)]
+    order_type: OrderType,
+    /// Whether the order volume can be split across several maker orders, the partial fills are opt-in
+    #[serde(default)]
+    allow_partial_fill: bool,
+    /// Time in milliseconds to collect the maker reservations before connecting to the best priced ones
+    #[serde(default = "default_reservations_window")]
+    reservations_window: u64,
+    #[serde(default)]
+    tie_breaker: TieBreaker,
 }
 
 pub async fn buy(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
@@ -671,9 +1440,11 @@ pub async fn buy(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
     let base_coin = try_s!(lp_coinfindᵃ(&ctx, &input.base).await);
     let base_coin: MmCoinEnum = try_s!(base_coin.ok_or("Base coin is not found or inactive"));
     let my_amount = &input.volume * &input.price;
+    try_s!(check_pair_limits(&ctx, &input.base, &input.rel, &input.rel, &my_amount, |_| false));
+    try_s!(check_pair_swaps_limit(&ctx, &input.base, &input.rel));
     let my_balance = try_s!(rel_coin.my_balance().compat().await);
     try_s!(check_locked_coins(&ctx, &my_amount, &my_balance, rel_coin.ticker()).await);
-    let dex_fee = dex_fee_amount(base_coin.ticker(), rel_coin.ticker(), &my_amount.clone().into());
+    let dex_fee = try_s!(dex_fee_amount(&ctx, base_coin.ticker(), rel_coin.ticker(), &my_amount.clone().into()));
     let trade_info = TradeInfo::Taker(dex_fee);
     try_s!(rel_coin.check_i_have_enough_to_trade(&my_amount.clone().into(), &my_balance.clone().into(), trade_info).compat().await);
     try_s!(base_coin.can_i_spend_other_payment().compat().await);
@@ -688,9 +1459,11 @@ pub async fn sell(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
     let base_coin = try_s!(base_coin.ok_or("Base coin is not found or inactive"));
     let rel_coin = try_s!(lp_coinfindᵃ(&ctx, &input.rel).await);
     let rel_coin = try_s!(rel_coin.ok_or("Rel coin is not found or inactive"));
+    try_s!(check_pair_limits(&ctx, &input.base, &input.rel, &input.base, &input.volume, |_| false));
+    try_s!(check_pair_swaps_limit(&ctx, &input.base, &input.rel));
     let my_balance = try_s!(base_coin.my_balance().compat().await);
     try_s!(check_locked_coins(&ctx, &input.volume, &my_balance, base_coin.ticker()).await);
-    let dex_fee = dex_fee_amount(base_coin.ticker(), rel_coin.ticker(), &input.volume.clone().into());
+    let dex_fee = try_s!(dex_fee_amount(&ctx, rel_coin.ticker(), base_coin.ticker(), &input.volume.clone().into()));
     let trade_info = TradeInfo::Taker(dex_fee);
     try_s!(base_coin.check_i_have_enough_to_trade(&input.volume.clone().into(), &my_balance.clone().into(), trade_info).compat().await);
     try_s!(rel_coin.can_i_spend_other_payment().compat().await);
@@ -698,6 +1471,206 @@ pub async fn sell(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
     Ok(try_s!(Response::builder().body(res)))
 }
 
+#[derive(Deserialize)]
+struct MarketOrderReq {
+    base: String,
+    rel: String,
+    /// The base coin volume to buy or sell
+    volume: MmNumber,
+    /// Max difference between the volume-weighted price and the best price, in percent
+    max_slippage: MmNumber,
+}
+
+/// The maker order from the orderbook offering to trade with us, the price is in rel per base
+/// and the volumes are in base coin regardless of the maker order direction
+#[derive(Clone, Debug)]
+struct MarketOffer {
+    pubkey: H256Json,
+    price: MmNumber,
+    max_volume: MmNumber,
+    min_volume: MmNumber,
+}
+
+/// The part of the market order that is sent to the specific maker
+#[derive(Debug, PartialEq)]
+struct MarketOrderFill {
+    dest_pub_key: H256Json,
+    price: MmNumber,
+    volume: MmNumber,
+}
+
+/// Fills the volume by the offers which must be sorted from the best price to the worst.
+/// The offers that would be filled by less than their min volume are skipped.
+fn sweep_market_offers(offers: &[MarketOffer], volume: &MmNumber) -> Result<Vec<MarketOrderFill>, String> {
+    let mut fills = vec![];
+    let mut remaining = volume.clone();
+    for offer in offers {
+        if remaining <= MmNumber::default() {
+            break;
+        }
+        let fill = if offer.max_volume < remaining { offer.max_volume.clone() } else { remaining.clone() };
+        if fill <= MmNumber::default() || fill < offer.min_volume {
+            continue;
+        }
+        remaining = remaining - fill.clone();
+        fills.push(MarketOrderFill {
+            dest_pub_key: offer.pubkey.clone(),
+            price: offer.price.clone(),
+            volume: fill,
+        });
+    }
+    if remaining > MmNumber::default() {
+        return ERR!("There is not enough liquidity in the orderbook, {} is not filled", BigDecimal::from(remaining));
+    }
+    Ok(fills)
+}
+
+/// Returns the volume-weighted price of the fills
+fn market_fills_price(fills: &[MarketOrderFill]) -> MmNumber {
+    let (total, cost) = fills.iter().fold((MmNumber::default(), MmNumber::default()), |(total, cost), fill|
+        (total + fill.volume.clone(), cost + &fill.volume * &fill.price)
+    );
+    if total > MmNumber::default() { cost / total } else { MmNumber::default() }
+}
+
+/// Collects the offers of the other nodes for our market order sorted from the best price.
+/// Buying is offered by the asks of the base/rel pair and selling by the asks of the rel/base pair.
+fn market_offers(ctx: &MmArc, base: &str, rel: &str, action: &TakerAction) -> Result<Vec<MarketOffer>, String> {
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    let our_pubkey = hex::encode(&try_s!(ctx.public_id()).bytes);
+    let orderbook = try_s!(ordermatch_ctx.orderbook.lock());
+    let pair = match action {
+        TakerAction::Buy => (base.to_owned(), rel.to_owned()),
+        TakerAction::Sell => (rel.to_owned(), base.to_owned()),
+    };
+    let mut offers = vec![];
+    for order in orderbook.get(&pair).into_iter().flat_map(|orders| orders.values()) {
+        if order.pubkey == our_pubkey {
+            continue;
+        }
+        let pubkey = match hex::decode(&order.pubkey) {
+            Ok(ref pubkey) if pubkey.len() == 32 => H256Json::from(pubkey.as_slice()),
+            _ => continue,
+        };
+        let maker_price = order.get_price();
+        if maker_price <= MmNumber::default() {
+            continue;
+        }
+        let maker_volume = order.get_max_volume();
+        let offer = match action {
+            TakerAction::Buy => MarketOffer {
+                pubkey,
+                price: maker_price,
+                max_volume: maker_volume,
+                min_volume: order.get_min_volume(),
+            },
+            // the maker sells the rel coin for base, so its volumes are converted to base
+            TakerAction::Sell => MarketOffer {
+                pubkey,
+                price: MmNumber::from(1) / maker_price.clone(),
+                max_volume: &maker_volume * &maker_price,
+                min_volume: order.get_min_volume() * maker_price,
+            },
+        };
+        offers.push(offer);
+    }
+    // the best ask has the lowest price and the best bid has the highest
+    offers.sort_by(|a, b| match action {
+        TakerAction::Buy => a.price.partial_cmp(&b.price),
+        TakerAction::Sell => b.price.partial_cmp(&a.price),
+    }.unwrap_or(Ordering::Equal));
+    Ok(offers)
+}
+
+async fn market_order(ctx: MmArc, req: Json, action: TakerAction) -> Result<Response<Vec<u8>>, String> {
+    let req: MarketOrderReq = try_s!(json::from_value(req));
+    if req.base == req.rel {return ERR!("Base and rel must be different coins")}
+    if req.volume <= MmNumber::default() {
+        return ERR!("Volume must be greater than zero");
+    }
+    if req.max_slippage < MmNumber::default() {
+        return ERR!("Max slippage must not be negative");
+    }
+    let base_coin = try_s!(lp_coinfindᵃ(&ctx, &req.base).await);
+    let base_coin: MmCoinEnum = try_s!(base_coin.ok_or("Base coin is not found or inactive"));
+    let rel_coin = try_s!(lp_coinfindᵃ(&ctx, &req.rel).await);
+    let rel_coin: MmCoinEnum = try_s!(rel_coin.ok_or("Rel coin is not found or inactive"));
+
+    let offers = try_s!(market_offers(&ctx, &req.base, &req.rel, &action));
+    let fills = try_s!(sweep_market_offers(&offers, &req.volume));
+    let price = market_fills_price(&fills);
+    let best_price = fills[0].price.clone();
+    let slippage = match action {
+        TakerAction::Buy => (price.clone() - best_price.clone()) * MmNumber::from(100) / best_price,
+        TakerAction::Sell => (best_price.clone() - price.clone()) * MmNumber::from(100) / best_price,
+    };
+    if slippage > req.max_slippage {
+        return ERR!("The price {} slippage {}% exceeds the max slippage {}%",
+            BigDecimal::from(price), BigDecimal::from(slippage), BigDecimal::from(req.max_slippage));
+    }
+
+    // the balance is checked for the whole volume as the separate orders are not locked until matched
+    let (my_coin, other_coin, my_amount) = match action {
+        TakerAction::Buy => (&rel_coin, &base_coin, &req.volume * &price),
+        TakerAction::Sell => (&base_coin, &rel_coin, req.volume.clone()),
+    };
+    try_s!(check_pair_limits(&ctx, &req.base, &req.rel, my_coin.ticker(), &my_amount, |_| false));
+    try_s!(check_pair_swaps_limit(&ctx, &req.base, &req.rel));
+    let my_balance = try_s!(my_coin.my_balance().compat().await);
+    try_s!(check_locked_coins(&ctx, &my_amount, &my_balance, my_coin.ticker()).await);
+    let dex_fee = try_s!(dex_fee_amount(&ctx, other_coin.ticker(), my_coin.ticker(), &my_amount.clone().into()));
+    let trade_info = TradeInfo::Taker(dex_fee);
+    try_s!(my_coin.check_i_have_enough_to_trade(&my_amount.clone().into(), &my_balance.clone().into(), trade_info).compat().await);
+    try_s!(other_coin.can_i_spend_other_payment().compat().await);
+
+    let method = match action {
+        TakerAction::Buy => "buy",
+        TakerAction::Sell => "sell",
+    };
+    let mut requests = vec![];
+    for fill in fills {
+        let input = AutoBuyInput {
+            base: req.base.clone(),
+            rel: req.rel.clone(),
+            price: fill.price,
+            volume: fill.volume,
+            timeout: None,
+            duration: None,
+            method: method.into(),
+            gui: None,
+            dest_pub_key: fill.dest_pub_key,
+            // the unmatched volume must not stay on the orderbook
+            order_type: OrderType::ImmediateOrCancel,
+            // the maker may have less volume than the orderbook shows
+            allow_partial_fill: true,
+            reservations_window: 0,
+            tie_breaker: TieBreaker::default(),
+        };
+        let res: Json = try_s!(json::from_str(&try_s!(lp_auto_buy(&ctx, input))));
+        requests.push(res["result"].clone());
+    }
+
+    let res = try_s!(json::to_vec(&json!({
+        "result": {
+            "price": BigDecimal::from(price.clone()),
+            "price_rat": BigRational::from(price),
+            "slippage": BigDecimal::from(slippage),
+            "requests": requests,
+        }
+    })));
+    Ok(try_s!(Response::builder().body(res)))
+}
+
+/// Buys the volume of base at the best prices of the orderbook asks
+pub async fn market_buy(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    market_order(ctx, req, TakerAction::Buy).await
+}
+
+/// Sells the volume of base at the best prices of the orderbook bids
+pub async fn market_sell(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    market_order(ctx, req, TakerAction::Sell).await
+}
+
 /// Created when maker order is matched with taker request
 #[derive(Clone, Debug, Deserialize, Serialize)]
 struct MakerMatch {
@@ -722,6 +1695,14 @@ pub fn lp_auto_buy(ctx: &MmArc, input: AutoBuyInput) -> Result<String, String> {
         return ERR!("Price is too low, minimum is 0.00000001");
     }
 
+    if input.reservations_window >= ORDERMATCH_TIMEOUT {
+        return ERR!("Reservations window must be lower than {} ms", ORDERMATCH_TIMEOUT);
+    }
+
+    if input.allow_partial_fill && input.order_type == OrderType::FillOrKill {
+        return ERR!("FillOrKill order can't be filled partially");
+    }
+
     let action = match Some(input.method.as_ref()) {
         Some("buy") => {
             TakerAction::Buy
@@ -733,7 +1714,6 @@ pub fn lp_auto_buy(ctx: &MmArc, input: AutoBuyInput) -> Result<String, String> {
     };
 
     let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(&ctx));
-    let mut my_taker_orders = try_s!(ordermatch_ctx.my_taker_orders.lock());
     let uuid = new_uuid();
     let our_public_id = try_s!(ctx.public_id());
     let rel_volume = &input.volume * &input.price;
@@ -750,8 +1730,11 @@ pub fn lp_auto_buy(ctx: &MmArc, input: AutoBuyInput) -> Result<String, String> {
         dest_pub_key: input.dest_pub_key,
         sender_pubkey: H256Json::from(our_public_id.bytes),
         action,
+        allow_partial_fill: input.allow_partial_fill,
     };
-    ctx.broadcast_p2p_msg(&unwrap!(json::to_string(&request)));
+    try_s!(check_dest_maker_min_volume(&ordermatch_ctx, &request));
+    let mut my_taker_orders = try_s!(ordermatch_ctx.my_taker_orders.lock());
+    ctx.broadcast_p2p_msg(&try_s!(sign_ordermatch_message(ctx, &request)));
     let result = json!({
         "result": request
     }).to_string();
@@ -759,14 +1742,29 @@ pub fn lp_auto_buy(ctx: &MmArc, input: AutoBuyInput) -> Result<String, String> {
         created_at: now_ms(),
         matches: HashMap::new(),
         request,
+        order_type: input.order_type,
+        pending_reservations: Vec::new(),
+        reservations_window: input.reservations_window,
+        tie_breaker: input.tie_breaker,
     };
     save_my_taker_order(ctx, &order);
+    add_taker_order_history_event(ctx, &order, OrderHistoryEvent::Created);
     my_taker_orders.insert(uuid, order);
     drop(my_taker_orders);
     Ok(result)
 }
 
-fn price_ping_sig_hash(timestamp: u32, pubsecp: &[u8], pubkey: &[u8], base: &[u8], rel: &[u8], price64: u64) -> H256 {
+/// The `min_volume64` is signed only when it's set to keep the pings of orders without min volume
+/// compatible with the nodes that don't know about it.
+fn price_ping_sig_hash(
+    timestamp: u32,
+    pubsecp: &[u8],
+    pubkey: &[u8],
+    base: &[u8],
+    rel: &[u8],
+    price64: u64,
+    min_volume64: Option<u64>,
+) -> H256 {
     let mut input = vec![];
     input.extend_from_slice(&timestamp.to_le_bytes());
     input.extend_from_slice(pubsecp);
@@ -774,10 +1772,231 @@ fn price_ping_sig_hash(timestamp: u32, pubsecp: &[u8], pubkey: &[u8], base: &[u8
     input.extend_from_slice(base);
     input.extend_from_slice(rel);
     input.extend_from_slice(&price64.to_le_bytes());
+    if let Some(min_volume64) = min_volume64 {
+        input.extend_from_slice(&min_volume64.to_le_bytes());
+    }
     sha256(&input)
 }
 
+/// The max difference between the ordermatch message timestamp and the local time, in seconds
+const MESSAGE_TIMESTAMP_TOLERANCE: u64 = 60;
+/// The version of the ordermatch messages signature scheme, the messages without version are the legacy unsigned ones
+const ORDERMATCH_SIG_VERSION: u64 = 1;
+
+/// The unsigned messages can be forged on behalf of any node, so they are rejected unless the legacy nodes
+/// are explicitly allowed with `"accept_unsigned_ordermatch_messages": true` in the MM2 config.
+fn accept_unsigned_ordermatch_messages(ctx: &MmArc) -> bool {
+    ctx.conf["accept_unsigned_ordermatch_messages"].as_bool().unwrap_or(false)
+}
+
+/// The ordermatch message signed by its sender
+trait SignedOrdermatchMessage: serde::Serialize {
+    /// The node that must sign the message
+    fn sender_pubkey(&self) -> &H256Json;
+
+    /// The message fields covered by the signature
+    fn sig_fields(&self) -> Vec<Vec<u8>>;
+}
+
+fn rat_sig_field(rat: &Option<BigRational>) -> Vec<u8> {
+    rat.as_ref().map(|r| r.to_string()).unwrap_or_default().into_bytes()
+}
+
+impl SignedOrdermatchMessage for TakerRequest {
+    fn sender_pubkey(&self) -> &H256Json { &self.sender_pubkey }
+
+    fn sig_fields(&self) -> Vec<Vec<u8>> {
+        let action: &[u8] = match self.action {
+            TakerAction::Buy => b"Buy",
+            TakerAction::Sell => b"Sell",
+        };
+        vec![
+            self.method.as_bytes().to_vec(),
+            self.uuid.as_bytes().to_vec(),
+            self.sender_pubkey.0.to_vec(),
+            self.dest_pub_key.0.to_vec(),
+            self.base.as_bytes().to_vec(),
+            self.rel.as_bytes().to_vec(),
+            self.base_amount.to_string().into_bytes(),
+            rat_sig_field(&self.base_amount_rat),
+            self.rel_amount.to_string().into_bytes(),
+            rat_sig_field(&self.rel_amount_rat),
+            action.to_vec(),
+            vec![self.allow_partial_fill as u8],
+        ]
+    }
+}
+
+impl SignedOrdermatchMessage for MakerReserved {
+    fn sender_pubkey(&self) -> &H256Json { &self.sender_pubkey }
+
+    fn sig_fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            self.method.as_bytes().to_vec(),
+            self.taker_order_uuid.as_bytes().to_vec(),
+            self.maker_order_uuid.as_bytes().to_vec(),
+            self.sender_pubkey.0.to_vec(),
+            self.dest_pub_key.0.to_vec(),
+            self.base.as_bytes().to_vec(),
+            self.rel.as_bytes().to_vec(),
+            self.base_amount.to_string().into_bytes(),
+            rat_sig_field(&self.base_amount_rat),
+            self.rel_amount.to_string().into_bytes(),
+            rat_sig_field(&self.rel_amount_rat),
+        ]
+    }
+}
+
+impl SignedOrdermatchMessage for TakerConnect {
+    fn sender_pubkey(&self) -> &H256Json { &self.sender_pubkey }
+
+    fn sig_fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            self.method.as_bytes().to_vec(),
+            self.taker_order_uuid.as_bytes().to_vec(),
+            self.maker_order_uuid.as_bytes().to_vec(),
+            self.sender_pubkey.0.to_vec(),
+            self.dest_pub_key.0.to_vec(),
+            self.swap_uuid.map(|uuid| uuid.as_bytes().to_vec()).unwrap_or_default(),
+        ]
+    }
+}
+
+impl SignedOrdermatchMessage for OrderbookSnapshotRequest {
+    fn sender_pubkey(&self) -> &H256Json { &self.sender_pubkey }
+
+    fn sig_fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            self.method.as_bytes().to_vec(),
+            self.base.as_bytes().to_vec(),
+            self.rel.as_bytes().to_vec(),
+            self.sender_pubkey.0.to_vec(),
+        ]
+    }
+}
+
+impl SignedOrdermatchMessage for MakerConnected {
+    fn sender_pubkey(&self) -> &H256Json { &self.sender_pubkey }
+
+    fn sig_fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            self.method.as_bytes().to_vec(),
+            self.taker_order_uuid.as_bytes().to_vec(),
+            self.maker_order_uuid.as_bytes().to_vec(),
+            self.sender_pubkey.0.to_vec(),
+            self.dest_pub_key.0.to_vec(),
+        ]
+    }
+}
+
+/// The signature envelope fields added to the ordermatch message JSON
 #[derive(Debug, Deserialize, Serialize)]
+struct OrdermatchMessageSig {
+    sig_version: u64,
+    timestamp: u64,
+    nonce: Uuid,
+    /// The compressed secp256k1 public key of the sender
+    pubsecp: String,
+    sig: String,
+}
+
+/// The hash of the canonical byte encoding of the message fields and the signature envelope,
+/// every field is prefixed with its length so the encoding doesn't depend on the JSON serialization.
+fn ordermatch_message_sig_hash(msg: &impl SignedOrdermatchMessage, sig_version: u64, timestamp: u64, nonce: &Uuid, pubsecp: &[u8]) -> H256 {
+    let mut input = vec![];
+    input.extend_from_slice(&sig_version.to_le_bytes());
+    input.extend_from_slice(&timestamp.to_le_bytes());
+    input.extend_from_slice(nonce.as_bytes());
+    input.extend_from_slice(pubsecp);
+    for field in msg.sig_fields() {
+        input.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        input.extend_from_slice(&field);
+    }
+    sha256(&input)
+}
+
+/// Serializes the ordermatch message adding the signature version, the timestamp, the random nonce
+/// and the signature made by our secp256k1 key.
+fn sign_ordermatch_message(ctx: &MmArc, msg: &impl SignedOrdermatchMessage) -> Result<String, String> {
+    let key_pair = ctx.secp256k1_key_pair();
+    let timestamp = now_ms() / 1000;
+    let nonce = new_uuid();
+    let sig_hash = ordermatch_message_sig_hash(msg, ORDERMATCH_SIG_VERSION, timestamp, &nonce, &**key_pair.public());
+    let sig = try_s!(key_pair.private().sign(&sig_hash));
+    let envelope = OrdermatchMessageSig {
+        sig_version: ORDERMATCH_SIG_VERSION,
+        timestamp,
+        nonce,
+        pubsecp: hex::encode(&**key_pair.public()),
+        sig: hex::encode(&*sig),
+    };
+    let mut json = try_s!(json::to_value(msg));
+    let fields = try_s!(json.as_object_mut().ok_or("Message must be a JSON object"));
+    if let Json::Object(envelope) = try_s!(json::to_value(&envelope)) {
+        fields.extend(envelope);
+    }
+    Ok(try_s!(json::to_string(&json)))
+}
+
+/// Checks that the ordermatch message is signed by its sender node,
+/// rejects the stale messages and the messages with already used nonce.
+/// The legacy unsigned messages are accepted only if `accept_unsigned_ordermatch_messages`
+/// and only from the nodes that have never sent the signed ones.
+fn verify_ordermatch_message(ctx: &MmArc, ordermatch_ctx: &OrdermatchContext, json: &Json, msg: &impl SignedOrdermatchMessage)
+    -> Result<(), String> {
+    if json["sig_version"].is_null() && json["sig"].is_null() {
+        if !accept_unsigned_ordermatch_messages(ctx) {
+            return ERR!("The unsigned messages are not accepted");
+        }
+        if try_s!(ordermatch_ctx.signed_senders.lock()).contains(msg.sender_pubkey()) {
+            return ERR!("The node {} signs its messages, the unsigned one is not accepted", hex::encode(&msg.sender_pubkey().0));
+        }
+        return Ok(());
+    }
+    try_s!(verify_ordermatch_message_sig(ordermatch_ctx, json, msg));
+    try_s!(ordermatch_ctx.signed_senders.lock()).insert(msg.sender_pubkey().clone());
+    Ok(())
+}
+
+/// Checks the signature of the ordermatch message, the message must be signed regardless of the transition period
+fn verify_ordermatch_message_sig(ordermatch_ctx: &OrdermatchContext, json: &Json, msg: &impl SignedOrdermatchMessage)
+    -> Result<(), String> {
+    let envelope: OrdermatchMessageSig = try_s!(json::from_value(json.clone()));
+    if envelope.sig_version != ORDERMATCH_SIG_VERSION {
+        return ERR!("Unsupported message signature version {}", envelope.sig_version);
+    }
+    let now = now_ms() / 1000;
+    if envelope.timestamp + MESSAGE_TIMESTAMP_TOLERANCE < now || envelope.timestamp > now + MESSAGE_TIMESTAMP_TOLERANCE {
+        return ERR!("The message timestamp {} is too far from the current time {}", envelope.timestamp, now);
+    }
+
+    let sender_pubkey = msg.sender_pubkey();
+    let pubsecp = try_s!(hex::decode(&envelope.pubsecp));
+    // the sender pubkey is the compressed secp256k1 public key without the prefix byte
+    if pubsecp.len() != 33 || pubsecp[1..] != sender_pubkey.0[..] {
+        return ERR!("The message pubsecp doesn't match the sender pubkey {}", hex::encode(&sender_pubkey.0));
+    }
+    let sig_hash = ordermatch_message_sig_hash(msg, envelope.sig_version, envelope.timestamp, &envelope.nonce, &pubsecp);
+    let pubsecp = try_s!(Public::from_slice(&pubsecp));
+    let sig: Signature = try_s!(envelope.sig.parse());
+    if !try_s!(pubsecp.verify(&sig_hash, &sig)) {
+        return ERR!("The message signature is invalid");
+    }
+
+    let mut nonces = try_s!(ordermatch_ctx.message_nonces.lock());
+    // the nonces of the messages that are stale already are not needed anymore
+    nonces.retain(|_, nonce_timestamp| *nonce_timestamp + MESSAGE_TIMESTAMP_TOLERANCE >= now);
+    if nonces.insert(envelope.nonce.to_string(), envelope.timestamp).is_some() {
+        return ERR!("The message nonce {} is already used", envelope.nonce);
+    }
+    Ok(())
+}
+
+fn to_satoshis(amount: &BigDecimal) -> Option<u64> {
+    (amount * BigDecimal::from(100000000)).to_u64()
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 struct PricePingRequest {
     method: String,
     pubkey: String,
@@ -794,14 +2013,53 @@ struct PricePingRequest {
     balance: BigDecimal,
     balance_rat: Option<BigRational>,
     uuid: Option<Uuid>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_volume: Option<BigDecimal>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_volume_rat: Option<BigRational>,
 }
 
 impl PricePingRequest {
+    fn get_price(&self) -> MmNumber {
+        match &self.price_rat {
+            Some(r) => r.clone().into(),
+            None => self.price.clone().into(),
+        }
+    }
+
+    fn get_max_volume(&self) -> MmNumber {
+        match &self.balance_rat {
+            Some(r) => r.clone().into(),
+            None => self.balance.clone().into(),
+        }
+    }
+
+    /// Only the `min_volume` is covered by the signature, so the unsigned `min_volume_rat` isn't trusted
+    fn get_min_volume(&self) -> MmNumber {
+        match &self.min_volume {
+            Some(d) => d.clone().into(),
+            None => MmNumber::default(),
+        }
+    }
+
     fn new(ctx: &MmArc, order: &MakerOrder, balance: BigDecimal) -> Result<PricePingRequest, String> {
         let public_id = try_s!(ctx.public_id());
 
-        let price64 = (&order.price * BigDecimal::from(100000000)).to_u64().unwrap();
+        let price = order.advertised_price();
+        let price_dec: BigDecimal = price.clone().into();
+        let price64 = (&price_dec * BigDecimal::from(100000000)).to_u64().unwrap();
         let timestamp = now_ms() / 1000;
+        let (min_volume, min_volume_rat) = if order.min_base_vol_rat > BigRational::zero() {
+            (Some(order.min_base_vol.clone()), Some(order.min_base_vol_rat.clone()))
+        } else {
+            (None, None)
+        };
+        let min_volume64 = match &min_volume {
+            Some(v) => Some(try_s!(to_satoshis(v).ok_or(ERRL!("Invalid min volume {}", v)))),
+            None => None,
+        };
         let sig_hash = price_ping_sig_hash(
             timestamp as u32,
             &**ctx.secp256k1_key_pair().public(),
@@ -809,6 +2067,7 @@ impl PricePingRequest {
             order.base.as_bytes(),
             order.rel.as_bytes(),
             price64,
+            min_volume64,
         );
 
         let sig = try_s!(ctx.secp256k1_key_pair().private().sign(&sig_hash));
@@ -832,67 +2091,213 @@ impl PricePingRequest {
             base: order.base.clone(),
             rel: order.rel.clone(),
             price64: price64.to_string(),
-            price: order.price.clone(),
-            price_rat: Some(order.price_rat.clone()),
+            price: price_dec,
+            price_rat: Some(price.into()),
             timestamp,
             pubsecp: hex::encode(&**ctx.secp256k1_key_pair().public()),
             sig: hex::encode(&*sig),
             balance: from_ratio_to_dec(&max_volume),
             balance_rat: Some(max_volume),
             uuid: Some(order.uuid),
+            min_volume,
+            min_volume_rat,
         })
     }
 }
 
-pub fn lp_post_price_recv(ctx: &MmArc, req: Json) -> HyRes {
-    let req: PricePingRequest = try_h!(json::from_value(req));
-    let signature: Signature = try_h!(req.sig.parse());
-    let pubkey_bytes = try_h!(hex::decode(&req.pubsecp));
+/// Verifies the price ping signature, returns the uuid of the order
+fn verify_price_ping(ctx: &MmArc, req: &PricePingRequest) -> Result<Uuid, String> {
+    let signature: Signature = try_s!(req.sig.parse());
+    let pubkey_bytes = try_s!(hex::decode(&req.pubsecp));
     if is_pubkey_banned(ctx, &H256::from(&pubkey_bytes[1..])) {
-        return rpc_err_response(400, &ERRL!("sender pubkey {} is banned", req.pubsecp));
+        return ERR!("sender pubkey {} is banned", req.pubsecp);
     }
-    let pub_secp = try_h!(Public::from_slice(&pubkey_bytes));
-    let pubkey = try_h!(hex::decode(&req.pubkey));
+    let pub_secp = try_s!(Public::from_slice(&pubkey_bytes));
+    let pubkey = try_s!(hex::decode(&req.pubkey));
+    let min_volume64 = match &req.min_volume {
+        Some(v) => Some(try_s!(to_satoshis(v).ok_or(ERRL!("Invalid min volume {}", v)))),
+        None => None,
+    };
     let sig_hash = price_ping_sig_hash(
         req.timestamp as u32,
         &*pub_secp,
         &pubkey,
         req.base.as_bytes(),
         req.rel.as_bytes(),
-        try_h!(req.price64.parse()),
+        try_s!(req.price64.parse()),
+        min_volume64,
     );
-    let sig_check = try_h!(pub_secp.verify(&sig_hash, &signature));
-    if sig_check {
-        // identify the order by first 16 bytes of node pubkey to keep backwards-compatibility
-        // TODO remove this when all nodes are updated
-        let mut bytes = [0; 16];
-        bytes.copy_from_slice(&pubkey[..16]);
-        let uuid = req.uuid.unwrap_or(Uuid::from_bytes(bytes));
-        let ordermatch_ctx: Arc<OrdermatchContext> = try_h!(OrdermatchContext::from_ctx(ctx));
-        let mut orderbook = try_h!(ordermatch_ctx.orderbook.lock());
-        match orderbook.entry((req.base.clone(), req.rel.clone())) {
-            Entry::Vacant(pair_orders) => if req.balance > 0.into() && req.price > 0.into() {
-                let mut orders = HashMap::new();
-                orders.insert(uuid, req);
-                pair_orders.insert(orders);
-            },
-            Entry::Occupied(mut pair_orders) => {
-                match pair_orders.get_mut().entry(uuid) {
-                    Entry::Vacant(order) => if req.balance > 0.into() && req.price > 0.into() {
-                        order.insert(req);
-                    },
-                    Entry::Occupied(mut order) => if req.balance > 0.into() {
-                        order.insert(req);
-                    } else {
-                        order.remove();
-                    },
-                }
+    if !try_s!(pub_secp.verify(&sig_hash, &signature)) {
+        return ERR!("price ping invalid signature");
+    }
+    // identify the order by first 16 bytes of node pubkey to keep backwards-compatibility
+    // TODO remove this when all nodes are updated
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&pubkey[..16]);
+    Ok(req.uuid.unwrap_or(Uuid::from_bytes(bytes)))
+}
+
+/// Adds or updates the orderbook entry, the entry is removed if the price ping has zero balance
+fn apply_price_ping(ordermatch_ctx: &OrdermatchContext, uuid: Uuid, req: PricePingRequest) -> Result<(), String> {
+    let mut orderbook = try_s!(ordermatch_ctx.orderbook.lock());
+    let mut updates = try_s!(ordermatch_ctx.orderbook_updates.lock());
+    let (base, rel) = (req.base.clone(), req.rel.clone());
+    match orderbook.entry((req.base.clone(), req.rel.clone())) {
+        Entry::Vacant(pair_orders) => if req.balance > 0.into() && req.price > 0.into() {
+            updates.push(OrderbookUpdateKind::Added, &base, &rel, uuid, Some(req.clone()));
+            let mut orders = HashMap::new();
+            orders.insert(uuid, req);
+            pair_orders.insert(orders);
+        },
+        Entry::Occupied(mut pair_orders) => {
+            match pair_orders.get_mut().entry(uuid) {
+                Entry::Vacant(order) => if req.balance > 0.into() && req.price > 0.into() {
+                    updates.push(OrderbookUpdateKind::Added, &base, &rel, uuid, Some(req.clone()));
+                    order.insert(req);
+                },
+                Entry::Occupied(mut order) => if req.balance > 0.into() {
+                    updates.push(OrderbookUpdateKind::Updated, &base, &rel, uuid, Some(req.clone()));
+                    order.insert(req);
+                } else {
+                    updates.push(OrderbookUpdateKind::Removed, &base, &rel, uuid, None);
+                    order.remove();
+                },
             }
         }
-        rpc_response(200, r#"{"result":"success"}"#)
-    } else {
-        rpc_err_response(400, "price ping invalid signature")
     }
+    Ok(())
+}
+
+pub fn lp_post_price_recv(ctx: &MmArc, req: Json) -> HyRes {
+    let req: PricePingRequest = try_h!(json::from_value(req));
+    let uuid = match verify_price_ping(ctx, &req) {
+        Ok(uuid) => uuid,
+        Err(e) => return rpc_err_response(400, &e),
+    };
+    let ordermatch_ctx: Arc<OrdermatchContext> = try_h!(OrdermatchContext::from_ctx(ctx));
+    try_h!(apply_price_ping(&ordermatch_ctx, uuid, req));
+    rpc_response(200, r#"{"result":"success"}"#)
+}
+
+/// The seed nodes don't respond to the same node and pair snapshot requests more often than this, in milliseconds
+const ORDERBOOK_SNAPSHOT_RESPONSE_INTERVAL: u64 = 10000;
+/// The node doesn't request the snapshot of the same pair more often than this, in milliseconds
+const ORDERBOOK_SNAPSHOT_REQUEST_INTERVAL: u64 = 60000;
+/// The orderbook entries that didn't receive an update for this time are removed, in seconds
+const ORDERBOOK_ENTRY_TIMEOUT: u64 = 30;
+
+/// Asks the seed nodes for the current orders of the pair (in both directions)
+#[derive(Debug, Deserialize, Serialize)]
+struct OrderbookSnapshotRequest {
+    method: String,
+    base: String,
+    rel: String,
+    sender_pubkey: H256Json,
+}
+
+/// The response of the seed node, contains the original signed price pings
+#[derive(Debug, Deserialize, Serialize)]
+struct OrderbookSnapshot {
+    method: String,
+    base: String,
+    rel: String,
+    dest_pub_key: H256Json,
+    orders: Vec<PricePingRequest>,
+}
+
+/// Broadcasts the orderbook snapshot request unless the pair snapshot was requested recently
+fn request_orderbook_snapshot(ctx: &MmArc, base: &str, rel: &str) -> Result<(), String> {
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    {
+        let mut requested = try_s!(ordermatch_ctx.snapshot_requests.lock());
+        let last_request = requested.entry((base.to_owned(), rel.to_owned())).or_insert(0);
+        if *last_request + ORDERBOOK_SNAPSHOT_REQUEST_INTERVAL > now_ms() {
+            return Ok(());
+        }
+        *last_request = now_ms();
+    }
+    let request = OrderbookSnapshotRequest {
+        method: "orderbook_snapshot_request".into(),
+        base: base.into(),
+        rel: rel.into(),
+        sender_pubkey: H256Json::from(try_s!(ctx.public_id()).bytes),
+    };
+    ctx.broadcast_p2p_msg(&try_s!(sign_ordermatch_message(ctx, &request)));
+    Ok(())
+}
+
+/// Only the seed nodes respond to the snapshot requests
+pub fn lp_orderbook_snapshot_request_recv(ctx: &MmArc, json: Json) -> HyRes {
+    let req: OrderbookSnapshotRequest = try_h!(json::from_value(json.clone()));
+    if !ctx.conf["i_am_seed"].as_bool().unwrap_or(false) {
+        return rpc_response(200, r#"{"result":"success"}"#);
+    }
+    if req.base == req.rel {
+        return rpc_err_response(400, "Base and rel must be different coins");
+    }
+
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(ctx));
+    // the request is signed so the rate limit can't be exhausted on behalf of other node
+    if let Err(e) = verify_ordermatch_message_sig(&ordermatch_ctx, &json, &req) {
+        return rpc_err_response(400, &format!("Snapshot request from {} is rejected: {}", hex::encode(&req.sender_pubkey.0), e));
+    }
+    {
+        let mut responses = try_h!(ordermatch_ctx.snapshot_responses.lock());
+        responses.retain(|_, responded_at| *responded_at + ORDERBOOK_SNAPSHOT_RESPONSE_INTERVAL > now_ms());
+        let key = (req.sender_pubkey.clone(), req.base.clone(), req.rel.clone());
+        if responses.contains_key(&key) {
+            return rpc_err_response(429, &format!("The snapshot of {}/{} is already sent to {}", req.base, req.rel, hex::encode(&req.sender_pubkey.0)));
+        }
+        responses.insert(key, now_ms());
+    }
+
+    let orders = {
+        let orderbook = try_h!(ordermatch_ctx.orderbook.lock());
+        let pairs = [(req.base.clone(), req.rel.clone()), (req.rel.clone(), req.base.clone())];
+        pairs.iter().filter_map(|pair| orderbook.get(pair)).flat_map(|orders| orders.values().cloned()).collect()
+    };
+    let snapshot = OrderbookSnapshot {
+        method: "orderbook_snapshot".into(),
+        base: req.base,
+        rel: req.rel,
+        dest_pub_key: req.sender_pubkey,
+        orders,
+    };
+    ctx.broadcast_p2p_msg(&try_h!(json::to_string(&snapshot)));
+    rpc_response(200, r#"{"result":"success"}"#)
+}
+
+/// Inserts the verified snapshot entries that are newer than the ones we already have
+pub fn lp_orderbook_snapshot_recv(ctx: &MmArc, req: Json) -> HyRes {
+    let snapshot: OrderbookSnapshot = try_h!(json::from_value(req));
+    if snapshot.dest_pub_key.0 != try_h!(ctx.public_id()).bytes {
+        return rpc_response(200, r#"{"result":"success"}"#);
+    }
+
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(ctx));
+    for order in snapshot.orders {
+        let is_snapshot_pair = (order.base == snapshot.base && order.rel == snapshot.rel)
+            || (order.base == snapshot.rel && order.rel == snapshot.base);
+        if !is_snapshot_pair || now_ms() / 1000 > order.timestamp + ORDERBOOK_ENTRY_TIMEOUT {
+            continue;
+        }
+        let uuid = match verify_price_ping(ctx, &order) {
+            Ok(uuid) => uuid,
+            Err(e) => {
+                log!("Snapshot entry of " (order.pubkey) " is rejected: " (e));
+                continue;
+            },
+        };
+        let is_known = {
+            let orderbook = try_h!(ordermatch_ctx.orderbook.lock());
+            orderbook.get(&(order.base.clone(), order.rel.clone())).and_then(|orders| orders.get(&uuid))
+                .map(|known| known.timestamp >= order.timestamp).unwrap_or(false)
+        };
+        if !is_known {
+            try_h!(apply_price_ping(&ordermatch_ctx, uuid, order));
+        }
+    }
+    rpc_response(200, r#"{"result":"success"}"#)
 }
 
 fn lp_send_price_ping(req: &PricePingRequest, ctx: &MmArc) -> Result<(), String> {
@@ -929,6 +2334,11 @@ struct SetPriceReq {
     volume: MmNumber,
     #[serde(default = "get_true")]
     cancel_previous: bool,
+    #[serde(default)]
+    min_volume: MmNumber,
+    /// The price of the band orders is set by the band, the fixed price orders use the `price`
+    #[serde(default)]
+    strategy: MakerStrategy,
 }
 
 pub async fn set_price(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
@@ -940,6 +2350,8 @@ pub async fn set_price(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, Strin
     if req.base == req.rel {
         return ERR!("Base and rel must be different coins");
     }
+    try_s!(req.strategy.validate());
+    let price = req.strategy.price(&req.price, &MmNumber::default());
 
     let base_coin: MmCoinEnum = match try_s!(lp_coinfindᵃ(&ctx, &req.base).await) {
         Some(coin) => coin,
@@ -966,7 +2378,15 @@ pub async fn set_price(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, Strin
         try_s!(base_coin.check_i_have_enough_to_trade(&req.volume, &my_balance.clone().into(), TradeInfo::Maker).compat().await);
         req.volume.clone()
     };
+    if req.min_volume < MmNumber::default() {
+        return ERR!("Min volume {} must not be negative", req.min_volume);
+    }
+    if req.min_volume > volume {
+        return ERR!("Min volume {} is larger than the order volume {}", req.min_volume, volume);
+    }
     try_s!(rel_coin.can_i_spend_other_payment().compat().await);
+    try_s!(check_pair_limits(&ctx, &req.base, &req.rel, &req.base, &volume,
+        |order| req.cancel_previous && order.base == req.base && order.rel == req.rel));
 
     let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(&ctx));
     let mut my_orders = try_s!(ordermatch_ctx.my_maker_orders.lock());
@@ -979,6 +2399,7 @@ pub async fn set_price(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, Strin
             let to_delete = order.base == req.base && order.rel == req.rel;
             if to_delete {
                 delete_my_maker_order(&ctx, &order);
+                add_maker_order_history_event(&ctx, &order, OrderHistoryEvent::Cancelled);
             }
             !to_delete
         }).collect();
@@ -988,20 +2409,228 @@ pub async fn set_price(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, Strin
     let order = MakerOrder {
         max_base_vol: volume.clone().into(),
         max_base_vol_rat: volume.into(),
-        min_base_vol: 0.into(),
-        min_base_vol_rat: BigRational::from_integer(0.into()),
-        price: req.price.clone().into(),
-        price_rat: req.price.clone().into(),
+        min_base_vol: req.min_volume.clone().into(),
+        min_base_vol_rat: req.min_volume.into(),
+        price: price.clone().into(),
+        price_rat: price.into(),
         created_at: now_ms(),
         base: req.base,
         rel: req.rel,
         matches: HashMap::new(),
         started_swaps: Vec::new(),
         uuid,
+        strategy: req.strategy,
+    };
+    save_my_maker_order(&ctx, &order);
+    add_maker_order_history_event(&ctx, &order, OrderHistoryEvent::Created);
+    let res = try_s!(json::to_vec(&json!({"result":order})));
+    my_orders.insert(uuid, order);
+    Ok(try_s!(Response::builder().body(res)))
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum TradePreimageMethod {
+    Buy,
+    Sell,
+    SetPrice,
+}
+
+#[derive(Deserialize)]
+struct TradePreimageReq {
+    base: String,
+    rel: String,
+    swap_method: TradePreimageMethod,
+    price: MmNumber,
+    #[serde(default)]
+    volume: MmNumber,
+    #[serde(default)]
+    max: bool,
+}
+
+/// Max amount of my coin that can be offered by the new order.
+/// The taker order pays the dex fee of the (maker coin, my coin) `dex_fee_pair`.
+fn max_trade_amount(ctx: &MmArc, available: BigDecimal, my_fee: Option<&BigDecimal>, dex_fee_pair: Option<(&str, &str)>) -> Result<BigDecimal, String> {
+    let mut amount = available;
+    if let Some(fee) = my_fee {
+        // the taker sends the dex fee and the payment transactions
+        amount -= match dex_fee_pair {
+            Some(_) => fee * BigDecimal::from(2),
+            None => fee.clone(),
+        };
+    }
+    match dex_fee_pair {
+        Some((maker_coin, taker_coin)) => Ok(try_s!(max_taker_vol_from_available(ctx, &amount, Some(maker_coin), taker_coin)).unwrap_or_else(|| 0.into())),
+        None => Ok(if amount < BigDecimal::from(0) { BigDecimal::from(0) } else { amount }),
+    }
+}
+
+/// Runs the same checks as the `buy`, `sell` and `setprice` RPCs do before placing the order
+async fn check_trade(
+    ctx: &MmArc,
+    req: &TradePreimageReq,
+    my_coin: &MmCoinEnum,
+    other_coin: &MmCoinEnum,
+    my_amount: &MmNumber,
+    my_balance: &BigDecimal,
+    dex_fee: Option<&BigDecimal>,
+) -> Result<(), String> {
+    if req.swap_method == TradePreimageMethod::SetPrice {
+        try_s!(check_pair_limits(ctx, &req.base, &req.rel, my_coin.ticker(), my_amount,
+            |order| order.base == req.base && order.rel == req.rel));
+    } else {
+        try_s!(check_pair_limits(ctx, &req.base, &req.rel, my_coin.ticker(), my_amount, |_| false));
+        try_s!(check_pair_swaps_limit(ctx, &req.base, &req.rel));
+    }
+    // `setprice` skips the balance checks when the max volume is requested
+    if !(req.max && req.swap_method == TradePreimageMethod::SetPrice) {
+        try_s!(check_locked_coins(ctx, my_amount, my_balance, my_coin.ticker()).await);
+        let trade_info = match dex_fee {
+            Some(fee) => TradeInfo::Taker(fee.clone()),
+            None => TradeInfo::Maker,
+        };
+        try_s!(my_coin.check_i_have_enough_to_trade(my_amount, &my_balance.clone().into(), trade_info).compat().await);
+    }
+    try_s!(other_coin.can_i_spend_other_payment().compat().await);
+    Ok(())
+}
+
+/// Shows the fees, the locked and max tradable amounts of the order that would be placed by `buy`, `sell` or `setprice`
+/// and the reason why the order would be rejected, nothing is broadcasted
+pub async fn trade_preimage(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    let req: TradePreimageReq = try_s!(json::from_value(req));
+    if req.price < MmNumber::from(BigRational::new(1.into(), 100000000.into())) {
+        return ERR!("Price is too low, minimum is 0.00000001");
+    }
+    if req.base == req.rel {return ERR!("Base and rel must be different coins")}
+    let base_coin = try_s!(lp_coinfindᵃ(&ctx, &req.base).await);
+    let base_coin: MmCoinEnum = try_s!(base_coin.ok_or("Base coin is not found or inactive"));
+    let rel_coin = try_s!(lp_coinfindᵃ(&ctx, &req.rel).await);
+    let rel_coin: MmCoinEnum = try_s!(rel_coin.ok_or("Rel coin is not found or inactive"));
+    let base_coin_fee = try_s!(base_coin.get_trade_fee().compat().await);
+    let rel_coin_fee = try_s!(rel_coin.get_trade_fee().compat().await);
+
+    let (my_coin, other_coin, my_fee) = match req.swap_method {
+        TradePreimageMethod::Buy => (&rel_coin, &base_coin, &rel_coin_fee),
+        TradePreimageMethod::Sell | TradePreimageMethod::SetPrice => (&base_coin, &rel_coin, &base_coin_fee),
+    };
+    let is_taker = req.swap_method != TradePreimageMethod::SetPrice;
+    let my_balance = try_s!(my_coin.my_balance().compat().await);
+    let locked_by_swaps = get_locked_amount(&ctx, my_coin.ticker());
+    let my_fee_amount = if my_fee.coin == my_coin.ticker() { Some(&my_fee.amount) } else { None };
+    let dex_fee_pair = if is_taker { Some((other_coin.ticker(), my_coin.ticker())) } else { None };
+    let max_amount: MmNumber = try_s!(max_trade_amount(&ctx, &my_balance - &locked_by_swaps, my_fee_amount, dex_fee_pair)).into();
+    let max_volume = match req.swap_method {
+        TradePreimageMethod::Buy => &max_amount / &req.price,
+        TradePreimageMethod::Sell | TradePreimageMethod::SetPrice => max_amount,
+    };
+
+    let volume = if req.max { max_volume.clone() } else { req.volume.clone() };
+    let my_amount = match req.swap_method {
+        TradePreimageMethod::Buy => &volume * &req.price,
+        TradePreimageMethod::Sell | TradePreimageMethod::SetPrice => volume.clone(),
+    };
+    let dex_fee = if is_taker { Some(try_s!(dex_fee_amount(&ctx, other_coin.ticker(), my_coin.ticker(), &my_amount.clone().into()))) } else { None };
+    let error = check_trade(&ctx, &req, my_coin, other_coin, &my_amount, &my_balance, dex_fee.as_ref()).await.err();
+
+    let res = try_s!(json::to_vec(&json!({
+        "result": {
+            "base": req.base,
+            "rel": req.rel,
+            "swap_method": req.swap_method,
+            "volume": BigDecimal::from(volume.clone()),
+            "volume_rat": BigRational::from(volume),
+            "max_volume": BigDecimal::from(max_volume.clone()),
+            "max_volume_rat": BigRational::from(max_volume),
+            "my_coin": my_coin.ticker(),
+            "my_amount": BigDecimal::from(my_amount),
+            "my_balance": my_balance,
+            "locked_by_swaps": locked_by_swaps,
+            "dex_fee": dex_fee,
+            "base_coin_fee": base_coin_fee,
+            "rel_coin_fee": rel_coin_fee,
+            "error": error,
+        }
+    })));
+    Ok(try_s!(Response::builder().body(res)))
+}
+
+/// Updates the price of my maker orders of the pair in place, the new prices are broadcasted with the next price pings.
+/// The price band orders get the new reference price and keep their own spread, the others get the `price`.
+/// Returns the number of repriced orders.
+pub fn reprice_my_maker_orders(ctx: &MmArc, base: &str, rel: &str, reference_price: &MmNumber, price: &MmNumber) -> Result<usize, String> {
+    let min_price = MmNumber::from(BigRational::new(1.into(), 100000000.into()));
+    if *price < min_price {
+        return ERR!("Price is too low, minimum is 0.00000001");
+    }
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    let mut my_orders = try_s!(ordermatch_ctx.my_maker_orders.lock());
+    let mut repriced = 0;
+    for order in my_orders.values_mut().filter(|order| order.base == base && order.rel == rel) {
+        let new_price = match &mut order.strategy {
+            MakerStrategy::PriceBand { reference_price: band_reference, .. } => {
+                *band_reference = reference_price.clone();
+                order.strategy.price(price, &MmNumber::default())
+            },
+            MakerStrategy::FixedPrice | MakerStrategy::VolumeTiers(_) => price.clone(),
+        };
+        order.price = new_price.clone().into();
+        order.price_rat = new_price.into();
+        save_my_maker_order(ctx, order);
+        repriced += 1;
+    }
+    Ok(repriced)
+}
+
+#[derive(Deserialize)]
+struct MakerOrderUpdate {
+    uuid: Uuid,
+    price: Option<MmNumber>,
+    volume: Option<MmNumber>,
+    min_volume: Option<MmNumber>,
+}
+
+pub async fn update_maker_order(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    let req: MakerOrderUpdate = try_s!(json::from_value(req));
+    if req.price.is_none() && req.volume.is_none() && req.min_volume.is_none() {
+        return ERR!("At least one of price, volume or min_volume must be specified");
+    }
+
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(&ctx));
+    let order = match try_s!(ordermatch_ctx.my_maker_orders.lock()).get(&req.uuid) {
+        Some(order) => order.clone(),
+        None => return ERR!("Maker order with uuid {} is not found", req.uuid),
+    };
+
+    let base_coin: MmCoinEnum = match try_s!(lp_coinfindᵃ(&ctx, &order.base).await) {
+        Some(coin) => coin,
+        None => return ERR!("Base coin {} is not found", order.base),
+    };
+
+    // validate the update on a copy first to avoid the balance checks for the invalid requests
+    let mut updated = order;
+    try_s!(updated.apply_update(&req));
+    let my_balance = try_s!(base_coin.my_balance().compat().await);
+    if req.volume.is_some() {
+        let available = updated.available_amount();
+        try_s!(check_pair_limits(&ctx, &updated.base, &updated.rel, &updated.base, &available, |order| order.uuid == req.uuid));
+        try_s!(check_locked_coins(&ctx, &available, &my_balance, base_coin.ticker()).await);
+        try_s!(base_coin.check_i_have_enough_to_trade(&available, &my_balance.clone().into(), TradeInfo::Maker).compat().await);
+    }
+
+    // the order might be matched while we were checking the balance, so the update is validated again
+    let (res, ping) = {
+        let mut my_orders = try_s!(ordermatch_ctx.my_maker_orders.lock());
+        let order = match my_orders.get_mut(&req.uuid) {
+            Some(order) => order,
+            None => return ERR!("Maker order with uuid {} is not found", req.uuid),
+        };
+        try_s!(order.apply_update(&req));
+        save_my_maker_order(&ctx, &order);
+        let res = try_s!(json::to_vec(&json!({"result": MakerOrderForRpc::from(&*order)})));
+        (res, try_s!(PricePingRequest::new(&ctx, &order, my_balance)))
     };
-    save_my_maker_order(&ctx, &order);
-    let res = try_s!(json::to_vec(&json!({"result":order})));
-    my_orders.insert(uuid, order);
+    try_s!(lp_send_price_ping(&ping, &ctx));
     Ok(try_s!(Response::builder().body(res)))
 }
 
@@ -1050,6 +2679,7 @@ pub async fn broadcast_my_maker_orders(ctx: &MmArc) -> Result<(), String> {
             // cancel the order if available balance is lower than "0.00777"
             try_s!(ordermatch_ctx.my_maker_orders.lock()).remove(&order.uuid);
             delete_my_maker_order(ctx, &order);
+            add_maker_order_history_event(ctx, &order, OrderHistoryEvent::Cancelled);
             try_s!(ordermatch_ctx.my_cancelled_orders.lock()).insert(order.uuid, order);
         }
     }
@@ -1087,33 +2717,54 @@ enum OrderMatchResult {
 }
 
 /// Attempts to match the Maker's order and Taker's request
+/// If the request allows partial fill and its volume is larger than order available amount
+/// the available amount is reserved.
 fn match_order_and_request(maker: &MakerOrder, taker: &TakerRequest) -> OrderMatchResult {
     let taker_base_amount: MmNumber = taker.get_base_amount();
     let taker_rel_amount: MmNumber = taker.get_rel_amount();
-    let maker_price: MmNumber = maker.price_rat.clone().into();
     let maker_min_vol: MmNumber = maker.min_base_vol_rat.clone().into();
+    let available = maker.available_amount();
+    let can_fill_partially = taker.allow_partial_fill && available > MmNumber::default() && available >= maker_min_vol;
 
     match taker.action {
         TakerAction::Buy => {
-            if maker.base == taker.base && maker.rel == taker.rel && taker_base_amount <= maker.available_amount() && taker_base_amount >= maker_min_vol {
+            if maker.base == taker.base && maker.rel == taker.rel && taker_base_amount <= available && taker_base_amount >= maker_min_vol {
+                let maker_price = maker.price_for_volume(&taker_base_amount);
                 let taker_price = &taker_rel_amount / &taker_base_amount;
                 if taker_price >= maker_price {
                     OrderMatchResult::Matched((taker_base_amount.clone(), taker_base_amount * maker_price))
                 } else {
                     OrderMatchResult::NotMatched
                 }
+            } else if maker.base == taker.base && maker.rel == taker.rel && taker_base_amount > available && can_fill_partially {
+                let maker_price = maker.price_for_volume(&available);
+                let taker_price = &taker_rel_amount / &taker_base_amount;
+                if taker_price >= maker_price {
+                    OrderMatchResult::Matched((available.clone(), available * maker_price))
+                } else {
+                    OrderMatchResult::NotMatched
+                }
             } else {
                 OrderMatchResult::NotMatched
             }
         },
         TakerAction::Sell => {
-            if maker.base == taker.rel && maker.rel == taker.base && taker_rel_amount <= maker.available_amount() && taker_rel_amount >= maker_min_vol {
+            if maker.base == taker.rel && maker.rel == taker.base && taker_rel_amount <= available && taker_rel_amount >= maker_min_vol {
+                let maker_price = maker.price_for_volume(&taker_rel_amount);
                 let taker_price = &taker_base_amount / &taker_rel_amount;
                 if taker_price >= maker_price {
                     OrderMatchResult::Matched((&taker_base_amount / &maker_price, taker_base_amount))
                 } else {
                     OrderMatchResult::NotMatched
                 }
+            } else if maker.base == taker.rel && maker.rel == taker.base && taker_rel_amount > available && can_fill_partially {
+                let maker_price = maker.price_for_volume(&available);
+                let taker_price = &taker_base_amount / &taker_rel_amount;
+                if taker_price >= maker_price {
+                    OrderMatchResult::Matched((available.clone(), available * maker_price))
+                } else {
+                    OrderMatchResult::NotMatched
+                }
             } else {
                 OrderMatchResult::NotMatched
             }
@@ -1145,6 +2796,30 @@ pub fn order_status(ctx: MmArc, req: Json) -> HyRes {
             "order": TakerOrderForRpc::from(order),
         }).to_string());
     }
+    drop(taker_orders);
+    drop(maker_orders);
+
+    // the conditional order is reported with the error of its last failed submission
+    let conditional_orders = try_h!(ordermatch_ctx.my_conditional_orders.lock());
+    if let Some(order) = conditional_orders.get(&req.uuid) {
+        return rpc_response(200, json!({
+            "type": "Conditional",
+            "order": order,
+        }).to_string());
+    }
+    drop(conditional_orders);
+
+    // the expired taker orders are removed from my orders, their last state is kept in the history
+    if let Some(history) = try_h!(load_my_order_history(&ctx, &req.uuid)) {
+        let expired_at = history.events.iter().rev().find(|entry| entry.event == OrderHistoryEvent::Expired).map(|entry| entry.timestamp);
+        if let (Some(order), Some(expired_at)) = (&history.taker_order, expired_at) {
+            return rpc_response(200, json!({
+                "type": "Taker",
+                "order": order,
+                "expired_at": expired_at,
+            }).to_string());
+        }
+    }
 
     rpc_err_response(404, &format!("Order with uuid {} is not found", req.uuid))
 }
@@ -1167,6 +2842,7 @@ pub fn cancel_order(ctx: MmArc, req: Json) -> HyRes {
             let mut cancelled_orders = try_h!(ordermatch_ctx.my_cancelled_orders.lock());
             let order = order.remove();
             delete_my_maker_order(&ctx, &order);
+            add_maker_order_history_event(&ctx, &order, OrderHistoryEvent::Cancelled);
             cancelled_orders.insert(req.uuid, order);
             return rpc_response(200, json!({
                 "result": "success"
@@ -1184,6 +2860,7 @@ pub fn cancel_order(ctx: MmArc, req: Json) -> HyRes {
             }
             let order = order.remove();
             delete_my_taker_order(&ctx, &order);
+            add_taker_order_history_event(&ctx, &order, OrderHistoryEvent::Cancelled);
             return rpc_response(200, json!({
                 "result": "success"
             }).to_string())
@@ -1259,12 +2936,14 @@ fn my_taker_order_file_path(ctx: &MmArc, uuid: &Uuid) -> PathBuf {
     my_taker_orders_dir(ctx).join(format!("{}.json", uuid))
 }
 
+#[cfg_attr(test, mockable)]
 fn save_my_maker_order(ctx: &MmArc, order: &MakerOrder) {
     let path = my_maker_order_file_path(ctx, &order.uuid);
     let content = unwrap!(json::to_vec(order));
     unwrap!(write(&path, &content));
 }
 
+#[cfg_attr(test, mockable)]
 fn save_my_taker_order(ctx: &MmArc, order: &TakerOrder) {
     let path = my_taker_order_file_path(ctx, &order.request.uuid);
     let content = unwrap!(json::to_vec(order));
@@ -1281,6 +2960,184 @@ fn delete_my_taker_order(ctx: &MmArc, order: &TakerOrder) {
     unwrap!(remove_file(&my_taker_order_file_path(ctx, &order.request.uuid)));
 }
 
+fn my_conditional_orders_dir(ctx: &MmArc) -> PathBuf {
+    ctx.dbdir().join("ORDERS").join("MY").join("CONDITIONAL")
+}
+
+fn my_conditional_order_file_path(ctx: &MmArc, uuid: &Uuid) -> PathBuf {
+    my_conditional_orders_dir(ctx).join(format!("{}.json", uuid))
+}
+
+#[cfg_attr(test, mockable)]
+fn save_my_conditional_order(ctx: &MmArc, order: &ConditionalOrder) {
+    let path = my_conditional_order_file_path(ctx, &order.uuid);
+    let content = unwrap!(json::to_vec(order));
+    unwrap!(write(&path, &content));
+}
+
+#[cfg_attr(test, mockable)]
+fn delete_my_conditional_order(ctx: &MmArc, order: &ConditionalOrder) {
+    unwrap!(remove_file(&my_conditional_order_file_path(ctx, &order.uuid)));
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", content = "data")]
+enum OrderHistoryEvent {
+    Created,
+    /// The order is matched with the order of other node
+    Matched { other_order_uuid: Uuid },
+    SwapStarted { swap_uuid: Uuid },
+    /// The unmatched volume of the timed out taker order is moved to the maker order with the same uuid
+    ConvertedToMaker,
+    /// The order can't be matched anymore as all its volume is swapped
+    Filled,
+    Cancelled,
+    Expired,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct OrderHistoryEntry {
+    event: OrderHistoryEvent,
+    timestamp: u64,
+}
+
+/// The history of my order, it's kept after the order is removed from `my_orders`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct MyOrderHistory {
+    uuid: Uuid,
+    /// The latest state of the taker order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    taker_order: Option<TakerOrder>,
+    /// The latest state of the maker order, it's also set when the taker order is converted to maker
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    maker_order: Option<MakerOrder>,
+    events: Vec<OrderHistoryEntry>,
+}
+
+impl MyOrderHistory {
+    fn new(uuid: Uuid) -> MyOrderHistory {
+        MyOrderHistory {
+            uuid,
+            taker_order: None,
+            maker_order: None,
+            events: vec![],
+        }
+    }
+
+    /// The uuids of the swaps started by the order
+    fn swaps(&self) -> Vec<Uuid> {
+        self.events.iter().filter_map(|entry| match entry.event {
+            OrderHistoryEvent::SwapStarted { swap_uuid } => Some(swap_uuid),
+            _ => None,
+        }).collect()
+    }
+}
+
+fn my_orders_history_dir(ctx: &MmArc) -> PathBuf {
+    ctx.dbdir().join("ORDERS").join("MY").join("HISTORY")
+}
+
+fn my_order_history_file_path(ctx: &MmArc, uuid: &Uuid) -> PathBuf {
+    my_orders_history_dir(ctx).join(format!("{}.json", uuid))
+}
+
+/// Returns None if my order has no history
+fn load_my_order_history(ctx: &MmArc, uuid: &Uuid) -> Result<Option<MyOrderHistory>, String> {
+    let content = slurp(&my_order_history_file_path(ctx, uuid));
+    if content.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(try_s!(json::from_slice(&content))))
+}
+
+fn add_my_order_history_event(
+    ctx: &MmArc,
+    uuid: Uuid,
+    event: OrderHistoryEvent,
+    update: impl FnOnce(&mut MyOrderHistory),
+) -> Result<(), String> {
+    let path = my_order_history_file_path(ctx, &uuid);
+    let mut history = try_s!(load_my_order_history(ctx, &uuid)).unwrap_or_else(|| MyOrderHistory::new(uuid));
+    update(&mut history);
+    history.events.push(OrderHistoryEntry {
+        event,
+        timestamp: now_ms(),
+    });
+    let content = try_s!(json::to_vec(&history));
+    try_s!(write(&path, &content));
+    Ok(())
+}
+
+/// Stores the event with the current state of the maker order, the errors are logged only
+/// as the history must not affect the trading
+#[cfg_attr(test, mockable)]
+fn add_maker_order_history_event(ctx: &MmArc, order: &MakerOrder, event: OrderHistoryEvent) {
+    if let Err(e) = add_my_order_history_event(ctx, order.uuid, event, |history| history.maker_order = Some(order.clone())) {
+        ctx.log.log("", &[&"order_history", &order.uuid.to_string()], &format!("error {}", e));
+    }
+}
+
+/// Stores the event with the current state of the taker order, the errors are logged only
+/// as the history must not affect the trading
+#[cfg_attr(test, mockable)]
+fn add_taker_order_history_event(ctx: &MmArc, order: &TakerOrder, event: OrderHistoryEvent) {
+    if let Err(e) = add_my_order_history_event(ctx, order.request.uuid, event, |history| history.taker_order = Some(order.clone())) {
+        ctx.log.log("", &[&"order_history", &order.request.uuid.to_string()], &format!("error {}", e));
+    }
+}
+
+#[derive(Serialize)]
+struct MyOrderHistoryForRpc<'a> {
+    #[serde(flatten)]
+    history: &'a MyOrderHistory,
+    swaps: Vec<Uuid>,
+}
+
+impl<'a> From<&'a MyOrderHistory> for MyOrderHistoryForRpc<'a> {
+    fn from(history: &'a MyOrderHistory) -> MyOrderHistoryForRpc {
+        MyOrderHistoryForRpc {
+            history,
+            swaps: history.swaps(),
+        }
+    }
+}
+
+pub fn my_orders_history(ctx: MmArc, req: Json) -> HyRes {
+    let limit = req["limit"].as_u64().unwrap_or(10);
+    let from_uuid: Option<Uuid> = try_h!(json::from_value(req["from_uuid"].clone()));
+    let mut entries: Vec<(u64, PathBuf)> = try_h!(read_dir(&my_orders_history_dir(&ctx)));
+    // sort by m_time in descending order, so the recently updated orders go first
+    entries.sort_by(|(a, _), (b, _)| b.cmp(&a));
+
+    let skip = match &from_uuid {
+        Some(uuid) => {
+            let history_path = my_order_history_file_path(&ctx, uuid);
+            try_h!(entries.iter().position(|(_, path)| *path == history_path).ok_or(format!("from_uuid {} order is not found", uuid))) + 1
+        },
+        None => 0,
+    };
+
+    let orders: Vec<Json> = entries.iter().skip(skip).take(limit as usize).map(|(_, path)|
+        match json::from_slice::<MyOrderHistory>(&slurp(&path)) {
+            Ok(history) => unwrap!(json::to_value(MyOrderHistoryForRpc::from(&history))),
+            Err(e) => {
+                log!("Error " (e) " parsing JSON from " (path.display()));
+                Json::Null
+            },
+        },
+    ).collect();
+
+    rpc_response(200, json!({
+        "result": {
+            "orders": orders,
+            "from_uuid": from_uuid,
+            "skipped": skip,
+            "limit": limit,
+            "total": entries.len(),
+        },
+    }).to_string())
+}
+
 pub fn orders_kick_start(ctx: &MmArc) -> Result<HashSet<String>, String> {
     let mut coins = HashSet::new();
     let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
@@ -1311,6 +3168,20 @@ pub fn orders_kick_start(ctx: &MmArc) -> Result<HashSet<String>, String> {
             Err(_) => (),
         }
     });
+
+    let mut conditional_orders = try_s!(ordermatch_ctx.my_conditional_orders.lock());
+    let conditional_entries: Vec<DirEntry> = try_s!(json_dir_entries(&my_conditional_orders_dir(&ctx)));
+
+    conditional_entries.iter().for_each(|entry| {
+        match json::from_slice::<ConditionalOrder>(&slurp(&entry.path())) {
+            Ok(order) => {
+                coins.insert(order.base.clone());
+                coins.insert(order.rel.clone());
+                conditional_orders.insert(order.uuid, order);
+            }
+            Err(_) => (),
+        }
+    });
     Ok(coins)
 }
 
@@ -1339,6 +3210,7 @@ pub fn cancel_orders_by(ctx: &MmArc, cancel_by: CancelBy) -> Result<(Vec<Uuid>,
             if $e {
                 if $order.is_cancellable() {
                     delete_my_maker_order(&ctx, &$order);
+                    add_maker_order_history_event(&ctx, &$order, OrderHistoryEvent::Cancelled);
                     my_cancelled_orders.insert($uuid, $order);
                     cancelled.push($uuid);
                     None
@@ -1357,6 +3229,7 @@ pub fn cancel_orders_by(ctx: &MmArc, cancel_by: CancelBy) -> Result<(Vec<Uuid>,
             if $e {
                 if $order.is_cancellable() {
                     delete_my_taker_order(&ctx, &$order);
+                    add_taker_order_history_event(&ctx, &$order, OrderHistoryEvent::Cancelled);
                     cancelled.push($uuid);
                     None
                 } else {
@@ -1412,29 +3285,358 @@ pub fn cancel_all_orders(ctx: MmArc, req: Json) -> HyRes {
     }).to_string())
 }
 
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+enum ConditionalTrigger {
+    /// Limits the loss when the price moves against us: the sell is triggered when the best bid falls
+    /// to the trigger price, the buy is triggered when the best ask rises to it
+    StopLoss,
+    /// Takes the profit when the price moves in our favor: the sell is triggered when the best bid rises
+    /// to the trigger price, the buy is triggered when the best ask falls to it
+    TakeProfit,
+}
+
+/// The way the conditional order is submitted when triggered
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ConditionalOrderMethod {
+    /// Taker order buying the base
+    Buy,
+    /// Taker order selling the base
+    Sell,
+    /// Maker order selling the base
+    SetPrice,
+}
+
+/// The order that stays dormant until the best price of the pair in the orderbook crosses the trigger price
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct ConditionalOrder {
+    uuid: Uuid,
+    base: String,
+    rel: String,
+    method: ConditionalOrderMethod,
+    trigger: ConditionalTrigger,
+    trigger_price: MmNumber,
+    /// The price of the order submitted when triggered
+    price: MmNumber,
+    volume: MmNumber,
+    created_at: u64,
+    /// The time of the last submission attempt, the failed submission is retried after `CONDITIONAL_ORDER_RETRY_INTERVAL`
+    #[serde(default)]
+    submitted_at: u64,
+    /// The error of the last failed submission
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_error: Option<String>,
+    /// The order is being submitted now
+    #[serde(default, skip_deserializing)]
+    submitting: bool,
+}
+
+/// The interval between the submission attempts of the triggered conditional order, in milliseconds
+const CONDITIONAL_ORDER_RETRY_INTERVAL: u64 = 30000;
+
+impl ConditionalOrder {
+    /// Checks the trigger price against the best bid (for sells) or the best ask (for buys) of the base/rel pair
+    fn is_triggered(&self, best_bid: Option<&MmNumber>, best_ask: Option<&MmNumber>) -> bool {
+        let trigger_price = &self.trigger_price;
+        match self.method {
+            ConditionalOrderMethod::Buy => match (best_ask, &self.trigger) {
+                (Some(ask), ConditionalTrigger::StopLoss) => ask >= trigger_price,
+                (Some(ask), ConditionalTrigger::TakeProfit) => ask <= trigger_price,
+                (None, _) => false,
+            },
+            ConditionalOrderMethod::Sell | ConditionalOrderMethod::SetPrice => match (best_bid, &self.trigger) {
+                (Some(bid), ConditionalTrigger::StopLoss) => bid <= trigger_price,
+                (Some(bid), ConditionalTrigger::TakeProfit) => bid >= trigger_price,
+                (None, _) => false,
+            },
+        }
+    }
+
+    /// The request of the RPC method submitting the order
+    fn rpc_request(&self) -> Json {
+        match self.method {
+            ConditionalOrderMethod::Buy | ConditionalOrderMethod::Sell => json!({
+                "method": if self.method == ConditionalOrderMethod::Buy { "buy" } else { "sell" },
+                "base": self.base,
+                "rel": self.rel,
+                "price": self.price,
+                "volume": self.volume,
+            }),
+            // keep the other maker orders of the pair
+            ConditionalOrderMethod::SetPrice => json!({
+                "base": self.base,
+                "rel": self.rel,
+                "price": self.price,
+                "volume": self.volume,
+                "cancel_previous": false,
+            }),
+        }
+    }
+}
+
+/// Returns the best bid and the best ask of the base/rel pair in the orderbook, the prices are in rel per base.
+/// The orders of the `exclude_pubsecp` node are skipped.
+fn best_bid_ask(
+    orderbook: &HashMap<(String, String), HashMap<Uuid, PricePingRequest>>,
+    base: &str,
+    rel: &str,
+    exclude_pubsecp: Option<&str>,
+) -> (Option<MmNumber>, Option<MmNumber>) {
+    let min_price = |base: &str, rel: &str| orderbook.get(&(base.to_owned(), rel.to_owned())).and_then(|orders| {
+        orders.values()
+            .filter(|order| Some(order.pubsecp.as_str()) != exclude_pubsecp)
+            .map(|order| order.get_price())
+            .filter(|price| *price > MmNumber::default())
+            .min_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+    });
+    // the bids are the asks of the rel/base pair
+    let best_bid = min_price(rel, base).map(|price| MmNumber::from(1) / price);
+    let best_ask = min_price(base, rel);
+    (best_bid, best_ask)
+}
+
+#[derive(Debug, Serialize)]
+struct Ticker {
+    base: String,
+    rel: String,
+    best_bid: Option<BigDecimal>,
+    best_ask: Option<BigDecimal>,
+    spread: Option<BigDecimal>,
+    /// The total base volume of the asks
+    ask_volume: BigDecimal,
+    /// The total base volume of the bids
+    bid_volume: BigDecimal,
+    num_makers: usize,
+}
+
+/// Returns the tickers of all pairs of the orderbook.
+/// Every pair is listed once with its coins sorted alphabetically.
+fn orderbook_tickers(orderbook: &HashMap<(String, String), HashMap<Uuid, PricePingRequest>>) -> Vec<Ticker> {
+    let mut pairs: Vec<(String, String)> = orderbook.keys().map(|(base, rel)| {
+        if base < rel { (base.clone(), rel.clone()) } else { (rel.clone(), base.clone()) }
+    }).collect();
+    pairs.sort();
+    pairs.dedup();
+
+    let empty = HashMap::new();
+    pairs.into_iter().map(|(base, rel)| {
+        let asks = orderbook.get(&(base.clone(), rel.clone())).unwrap_or(&empty);
+        let bids = orderbook.get(&(rel.clone(), base.clone())).unwrap_or(&empty);
+        let ask_volume = asks.values().fold(MmNumber::default(), |total, ask| total + ask.get_max_volume());
+        // the bids are the asks of the rel/base pair, so their volume is converted to base
+        let bid_volume = bids.values().fold(MmNumber::default(), |total, bid| total + &bid.get_max_volume() * &bid.get_price());
+        let num_makers = asks.values().chain(bids.values()).map(|order| &order.pubkey).collect::<HashSet<_>>().len();
+        let (best_bid, best_ask) = best_bid_ask(orderbook, &base, &rel, None);
+        let spread = match (&best_bid, &best_ask) {
+            (Some(bid), Some(ask)) => Some(ask.clone() - bid.clone()),
+            _ => None,
+        };
+        Ticker {
+            base,
+            rel,
+            best_bid: best_bid.map(BigDecimal::from),
+            best_ask: best_ask.map(BigDecimal::from),
+            spread: spread.map(BigDecimal::from),
+            ask_volume: ask_volume.into(),
+            bid_volume: bid_volume.into(),
+            num_makers,
+        }
+    }).collect()
+}
+
+/// Returns the best prices and volumes of all orderbook pairs, the coins don't have to be enabled
+pub fn tickers(ctx: MmArc) -> HyRes {
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
+    let orderbook = try_h!(ordermatch_ctx.orderbook.lock());
+    rpc_response(200, json!({
+        "result": orderbook_tickers(&orderbook)
+    }).to_string())
+}
+
+/// Marks the conditional orders triggered by the current orderbook as being submitted and returns them.
+/// The order is kept until its submission succeeds, the triggers are checked against the orders of other nodes only.
+fn triggered_conditional_orders(ctx: &MmArc) -> Result<Vec<ConditionalOrder>, String> {
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    let my_pubsecp = hex::encode(&**ctx.secp256k1_key_pair().public());
+    let orderbook = try_s!(ordermatch_ctx.orderbook.lock());
+    let mut conditional_orders = try_s!(ordermatch_ctx.my_conditional_orders.lock());
+    let mut triggered = vec![];
+    for order in conditional_orders.values_mut() {
+        if order.submitting || order.submitted_at + CONDITIONAL_ORDER_RETRY_INTERVAL > now_ms() {
+            continue;
+        }
+        let (best_bid, best_ask) = best_bid_ask(&orderbook, &order.base, &order.rel, Some(&my_pubsecp));
+        if order.is_triggered(best_bid.as_ref(), best_ask.as_ref()) {
+            order.submitting = true;
+            order.submitted_at = now_ms();
+            triggered.push(order.clone());
+        }
+    }
+    Ok(triggered)
+}
+
+async fn submit_conditional_order(ctx: &MmArc, order: ConditionalOrder) {
+    let req = order.rpc_request();
+    let res = match order.method {
+        ConditionalOrderMethod::Buy => buy(ctx.clone(), req).await,
+        ConditionalOrderMethod::Sell => sell(ctx.clone(), req).await,
+        ConditionalOrderMethod::SetPrice => set_price(ctx.clone(), req).await,
+    };
+    match &res {
+        Ok(_) => log!("Conditional order " (order.uuid) " " [order.trigger] " is triggered, " [order.method] " is submitted"),
+        Err(e) => log!("Conditional order " (order.uuid) " " [order.method] " submission failed " (e)),
+    }
+    if let Err(e) = finish_conditional_order_submission(ctx, &order.uuid, res.map(|_| ())) {
+        log!("Error " (e) " finishing the conditional order " (order.uuid) " submission");
+    }
+}
+
+/// Removes the successfully submitted conditional order,
+/// the failed one is kept with the error to be reported by the order status and submitted again.
+fn finish_conditional_order_submission(ctx: &MmArc, uuid: &Uuid, res: Result<(), String>) -> Result<(), String> {
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    let mut conditional_orders = try_s!(ordermatch_ctx.my_conditional_orders.lock());
+    match res {
+        Ok(_) => if let Some(order) = conditional_orders.remove(uuid) {
+            delete_my_conditional_order(ctx, &order);
+        },
+        Err(e) => if let Some(order) = conditional_orders.get_mut(uuid) {
+            order.submitting = false;
+            order.last_error = Some(e);
+            save_my_conditional_order(ctx, order);
+        },
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ConditionalOrderReq {
+    base: String,
+    rel: String,
+    method: ConditionalOrderMethod,
+    trigger: ConditionalTrigger,
+    trigger_price: MmNumber,
+    price: MmNumber,
+    volume: MmNumber,
+}
+
+pub async fn create_conditional_order(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    let req: ConditionalOrderReq = try_s!(json::from_value(req));
+    if req.base == req.rel {
+        return ERR!("Base and rel must be different coins");
+    }
+    let min_price = MmNumber::from(BigRational::new(1.into(), 100000000.into()));
+    if req.price < min_price || req.trigger_price < min_price {
+        return ERR!("Price is too low, minimum is 0.00000001");
+    }
+    if req.volume <= MmNumber::default() {
+        return ERR!("Volume must be greater than zero");
+    }
+    // the balances are checked when the order is triggered as they might change until then
+    if try_s!(lp_coinfindᵃ(&ctx, &req.base).await).is_none() {
+        return ERR!("Base coin {} is not found", req.base);
+    }
+    if try_s!(lp_coinfindᵃ(&ctx, &req.rel).await).is_none() {
+        return ERR!("Rel coin {} is not found", req.rel);
+    }
+
+    let order = ConditionalOrder {
+        uuid: new_uuid(),
+        base: req.base,
+        rel: req.rel,
+        method: req.method,
+        trigger: req.trigger,
+        trigger_price: req.trigger_price,
+        price: req.price,
+        volume: req.volume,
+        created_at: now_ms(),
+        submitted_at: 0,
+        last_error: None,
+        submitting: false,
+    };
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(&ctx));
+    save_my_conditional_order(&ctx, &order);
+    let res = try_s!(json::to_vec(&json!({"result": order})));
+    try_s!(ordermatch_ctx.my_conditional_orders.lock()).insert(order.uuid, order);
+    Ok(try_s!(Response::builder().body(res)))
+}
+
+pub fn my_conditional_orders(ctx: MmArc) -> HyRes {
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
+    let conditional_orders = try_h!(ordermatch_ctx.my_conditional_orders.lock());
+    rpc_response(200, json!({
+        "result": *conditional_orders,
+    }).to_string())
+}
+
+pub fn cancel_conditional_order(ctx: MmArc, req: Json) -> HyRes {
+    let req: CancelOrderReq = try_h!(json::from_value(req));
+
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
+    let mut conditional_orders = try_h!(ordermatch_ctx.my_conditional_orders.lock());
+    if conditional_orders.get(&req.uuid).map(|order| order.submitting) == Some(true) {
+        return rpc_err_response(500, &format!("Conditional order {} is being submitted now, can't cancel", req.uuid));
+    }
+    match conditional_orders.remove(&req.uuid) {
+        Some(order) => {
+            delete_my_conditional_order(&ctx, &order);
+            rpc_response(200, json!({
+                "result": "success"
+            }).to_string())
+        },
+        None => rpc_err_response(404, &format!("Conditional order with uuid {} is not found", req.uuid)),
+    }
+}
+
 #[derive(Serialize)]
 pub struct OrderbookEntry {
     coin: String,
-    address: String,
+    /// Not set if the address can't be derived neither by the enabled coin nor by the coin config
+    address: Option<String>,
     price: BigDecimal,
     price_rat: BigRational,
     #[serde(rename="maxvolume")]
     max_volume: BigDecimal,
     max_volume_rat: BigRational,
+    min_volume: BigDecimal,
+    min_volume_rat: BigRational,
     pubkey: String,
     age: i64,
     zcredits: u64,
 }
 
+/// Aggregated orderbook price level, the volumes are expressed in the coin of the orderbook side
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OrderbookLevel {
+    price: BigDecimal,
+    price_rat: BigRational,
+    volume: BigDecimal,
+    volume_rat: BigRational,
+    /// Cumulative volume of this level and all the better priced levels
+    depth: BigDecimal,
+    depth_rat: BigRational,
+    num_orders: usize,
+}
+
 #[derive(Serialize)]
 pub struct OrderbookResponse {
+    /// Number of the returned ask price levels
     #[serde(rename="askdepth")]
     ask_depth: u32,
+    /// Total volume of the returned asks
+    ask_volume_depth: BigDecimal,
+    ask_volume_depth_rat: BigRational,
     asks: Vec<OrderbookEntry>,
+    ask_levels: Vec<OrderbookLevel>,
     base: String,
+    /// Number of the returned bid price levels
     #[serde(rename="biddepth")]
     bid_depth: u32,
+    /// Total volume of the returned bids
+    bid_volume_depth: BigDecimal,
+    bid_volume_depth_rat: BigRational,
     bids: Vec<OrderbookEntry>,
+    bid_levels: Vec<OrderbookLevel>,
     netid: u16,
     #[serde(rename="numasks")]
     num_asks: usize,
@@ -1448,28 +3650,98 @@ pub struct OrderbookResponse {
 struct OrderbookReq {
     base: String,
     rel: String,
+    /// Max number of price levels returned for each side
+    #[serde(default)]
+    depth: Option<usize>,
+    /// Group the price levels by multiples of this value
+    #[serde(default)]
+    price_tick: Option<MmNumber>,
+}
+
+/// Groups the sorted orderbook side entries into price levels.
+/// If the `tick` is set the price is rounded to its multiple away from the best price (up for asks, down for bids)
+/// so the level price is never better than the prices of the orders it contains.
+fn aggregate_price_levels(
+    entries: &[OrderbookEntry],
+    tick: Option<&BigRational>,
+    round_up: bool,
+    depth: Option<usize>,
+) -> Vec<OrderbookLevel> {
+    let mut levels: Vec<OrderbookLevel> = vec![];
+    let mut cumulative = BigRational::zero();
+    for entry in entries {
+        let price = match tick {
+            Some(tick) => {
+                let ticks = &entry.price_rat / tick;
+                let ticks = if round_up { ticks.ceil() } else { ticks.floor() };
+                ticks * tick
+            },
+            None => entry.price_rat.clone(),
+        };
+        let is_same_level = levels.last().map(|level| level.price_rat == price).unwrap_or(false);
+        if !is_same_level && Some(levels.len()) == depth {
+            break;
+        }
+
+        cumulative = cumulative + &entry.max_volume_rat;
+        if is_same_level {
+            let level = levels.last_mut().unwrap();
+            level.volume_rat = &level.volume_rat + &entry.max_volume_rat;
+            level.volume = from_ratio_to_dec(&level.volume_rat);
+            level.depth_rat = cumulative.clone();
+            level.depth = from_ratio_to_dec(&cumulative);
+            level.num_orders += 1;
+        } else {
+            levels.push(OrderbookLevel {
+                price: from_ratio_to_dec(&price),
+                price_rat: price,
+                volume: entry.max_volume.clone(),
+                volume_rat: entry.max_volume_rat.clone(),
+                depth: from_ratio_to_dec(&cumulative),
+                depth_rat: cumulative.clone(),
+                num_orders: 1,
+            });
+        }
+    }
+    levels
+}
+
+/// Derives the address of the orderbook entry by the enabled coin or by the coin config if the coin is not enabled
+fn orderbook_address(coin: Option<&MmCoinEnum>, ticker: &str, conf: &Json, pubsecp: &str) -> Option<String> {
+    let address = match coin {
+        Some(coin) => coin.address_from_pubkey_str(pubsecp),
+        None => address_by_coin_conf_and_pubkey_str(ticker, conf, pubsecp),
+    };
+    address.ok()
 }
 
 pub async fn orderbook(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
     let req: OrderbookReq = try_s!(json::from_value(req));
     if req.base == req.rel {return ERR!("Base and rel must be different coins")}
+    // the coins don't have to be enabled to read the orderbook
     let rel_coin = try_s!(lp_coinfindᵃ(&ctx, &req.rel).await);
-    let rel_coin = try_s!(rel_coin.ok_or("Rel coin is not found or inactive"));
     let base_coin = try_s!(lp_coinfindᵃ(&ctx, &req.base).await);
-    let base_coin: MmCoinEnum = try_s!(base_coin.ok_or("Base coin is not found or inactive"));
+    let rel_coin_conf = coin_conf(&ctx, &req.rel);
+    let base_coin_conf = coin_conf(&ctx, &req.base);
+    // the pair might be not known yet if the node has been started recently
+    if let Err(e) = request_orderbook_snapshot(&ctx, &req.base, &req.rel) {
+        log!("Error " (e) " requesting the orderbook snapshot");
+    }
     let ordermatch_ctx: Arc<OrdermatchContext> = try_s!(OrdermatchContext::from_ctx(&ctx));
     let orderbook = try_s!(ordermatch_ctx.orderbook.lock());
-    let asks = match orderbook.get(&(req.base.clone(), req.rel.clone())) {
+    let mut asks = match orderbook.get(&(req.base.clone(), req.rel.clone())) {
         Some(asks) => {
             let mut orderbook_entries = vec![];
             for (_, ask) in asks.iter() {
                 orderbook_entries.push(OrderbookEntry {
                     coin: req.base.clone(),
-                    address: try_s!(base_coin.address_from_pubkey_str(&ask.pubsecp)),
+                    address: orderbook_address(base_coin.as_ref(), &req.base, &base_coin_conf, &ask.pubsecp),
                     price: ask.price.clone(),
                     price_rat: ask.price_rat.as_ref().map(|p| p.clone()).unwrap_or(from_dec_to_ratio(ask.price.clone())),
                     max_volume: ask.balance.clone(),
                     max_volume_rat: ask.balance_rat.as_ref().map(|p| p.clone()).unwrap_or(from_dec_to_ratio(ask.balance.clone())),
+                    min_volume: ask.min_volume.clone().unwrap_or(0.into()),
+                    min_volume_rat: ask.get_min_volume().into(),
                     pubkey: ask.pubkey.clone(),
                     age: (now_ms() as i64 / 1000) - ask.timestamp as i64,
                     zcredits: 0,
@@ -1479,19 +3751,21 @@ pub async fn orderbook(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, Strin
         },
         None => vec![],
     };
-    let bids = match orderbook.get(&(req.rel.clone(), req.base.clone())) {
+    let mut bids = match orderbook.get(&(req.rel.clone(), req.base.clone())) {
         Some(asks) => {
             let mut orderbook_entries = vec![];
             for (_, ask) in asks.iter() {
                 orderbook_entries.push(OrderbookEntry {
                     coin: req.rel.clone(),
-                    address: try_s!(rel_coin.address_from_pubkey_str(&ask.pubsecp)),
+                    address: orderbook_address(rel_coin.as_ref(), &req.rel, &rel_coin_conf, &ask.pubsecp),
                     // NB: 1/x can not be represented as a decimal and introduces a rounding error
                     // cf. https://github.com/KomodoPlatform/atomicDEX-API/issues/495#issuecomment-516365682
                     price: BigDecimal::from (1) / &ask.price,
                     price_rat: BigRational::from_integer(1.into()) / ask.price_rat.as_ref().map(|p| p.clone()).unwrap_or(from_dec_to_ratio(ask.price.clone())),
                     max_volume: ask.balance.clone(),
                     max_volume_rat: ask.balance_rat.as_ref().map(|p| p.clone()).unwrap_or(from_dec_to_ratio(ask.balance.clone())),
+                    min_volume: ask.min_volume.clone().unwrap_or(0.into()),
+                    min_volume_rat: ask.get_min_volume().into(),
                     pubkey: ask.pubkey.clone(),
                     age: (now_ms() as i64 / 1000) - ask.timestamp as i64,
                     zcredits: 0,
@@ -1501,14 +3775,39 @@ pub async fn orderbook(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, Strin
         },
         None => vec![],
     };
+    drop(orderbook);
+
+    let tick: Option<BigRational> = req.price_tick.map(|tick| tick.into());
+    if let Some(tick) = &tick {
+        if *tick <= BigRational::zero() {
+            return ERR!("Price tick must be greater than zero");
+        }
+    }
+    // the best asks have the lowest price and the best bids have the highest
+    asks.sort_by(|a, b| a.price_rat.cmp(&b.price_rat));
+    bids.sort_by(|a, b| b.price_rat.cmp(&a.price_rat));
+    let ask_levels = aggregate_price_levels(&asks, tick.as_ref(), true, req.depth);
+    let bid_levels = aggregate_price_levels(&bids, tick.as_ref(), false, req.depth);
+    // keep only the entries that are included in the returned levels
+    asks.truncate(ask_levels.iter().map(|level| level.num_orders).sum());
+    bids.truncate(bid_levels.iter().map(|level| level.num_orders).sum());
+
+    let ask_volume_depth = ask_levels.last().map(|level| level.depth_rat.clone()).unwrap_or(BigRational::zero());
+    let bid_volume_depth = bid_levels.last().map(|level| level.depth_rat.clone()).unwrap_or(BigRational::zero());
     let response = OrderbookResponse {
         num_asks: asks.len(),
         num_bids: bids.len(),
-        ask_depth: 0,
+        ask_depth: ask_levels.len() as u32,
+        ask_volume_depth: from_ratio_to_dec(&ask_volume_depth),
+        ask_volume_depth_rat: ask_volume_depth,
         asks,
+        ask_levels,
         base: req.base,
-        bid_depth: 0,
+        bid_depth: bid_levels.len() as u32,
+        bid_volume_depth: from_ratio_to_dec(&bid_volume_depth),
+        bid_volume_depth_rat: bid_volume_depth,
         bids,
+        bid_levels,
         netid: ctx.netid(),
         rel: req.rel,
         timestamp: now_ms() / 1000,
@@ -1517,6 +3816,84 @@ pub async fn orderbook(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, Strin
     Ok(try_s!(Response::builder().body(responseʲ)))
 }
 
+#[derive(Deserialize)]
+struct OrderbookUpdatesReq {
+    base: String,
+    rel: String,
+    /// The `seq` returned by the previous call, the snapshot is returned if not set
+    #[serde(default)]
+    from_seq: Option<u64>,
+    /// How long to wait for new updates, in seconds
+    #[serde(default)]
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct OrderbookUpdatesResponse {
+    /// The sequence number to pass as `from_seq` to the next call
+    seq: u64,
+    /// The current orders of the pair (in both directions), set when the subscriber has to start over
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapshot: Option<Vec<OrderbookUpdate>>,
+    updates: Vec<OrderbookUpdate>,
+}
+
+/// Long polls the incremental orderbook changes of the base/rel pair.
+/// Returns immediately if there are updates after `from_seq` or the snapshot is required,
+/// otherwise waits until the updates appear or the timeout expires.
+pub async fn orderbook_updates(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    let req: OrderbookUpdatesReq = try_s!(json::from_value(req));
+    if req.base == req.rel {return ERR!("Base and rel must be different coins")}
+    let timeout = req.timeout.unwrap_or(0);
+    if timeout > MAX_ORDERBOOK_UPDATES_TIMEOUT {
+        return ERR!("Timeout {} is larger than max {}", timeout, MAX_ORDERBOOK_UPDATES_TIMEOUT);
+    }
+    if let Err(e) = request_orderbook_snapshot(&ctx, &req.base, &req.rel) {
+        log!("Error " (e) " requesting the orderbook snapshot");
+    }
+    let ordermatch_ctx: Arc<OrdermatchContext> = try_s!(OrdermatchContext::from_ctx(&ctx));
+    let wait_until = now_ms() + timeout * 1000;
+
+    let response = loop {
+        {
+            let orderbook = try_s!(ordermatch_ctx.orderbook.lock());
+            let updates = try_s!(ordermatch_ctx.orderbook_updates.lock());
+            let since = req.from_seq.and_then(|from_seq| updates.updates_since(&req.base, &req.rel, from_seq));
+            match since {
+                Some(pair_updates) => if !pair_updates.is_empty() || now_ms() >= wait_until {
+                    break OrderbookUpdatesResponse {
+                        seq: updates.seq,
+                        snapshot: None,
+                        updates: pair_updates,
+                    };
+                },
+                None => {
+                    let seq = updates.seq;
+                    let pairs = [(req.base.clone(), req.rel.clone()), (req.rel.clone(), req.base.clone())];
+                    let snapshot = pairs.iter().filter_map(|pair| orderbook.get(pair).map(|orders| (pair, orders)))
+                        .flat_map(|((base, rel), orders)| orders.iter().map(move |(uuid, order)| OrderbookUpdate {
+                            seq,
+                            kind: OrderbookUpdateKind::Added,
+                            base: base.clone(),
+                            rel: rel.clone(),
+                            uuid: *uuid,
+                            order: Some(order.clone()),
+                        })).collect();
+                    break OrderbookUpdatesResponse {
+                        seq,
+                        snapshot: Some(snapshot),
+                        updates: vec![],
+                    };
+                },
+            }
+        }
+        Timer::sleep(0.2).await;
+    };
+
+    let res = try_s!(json::to_vec(&json!({"result": response})));
+    Ok(try_s!(Response::builder().body(res)))
+}
+
 pub fn migrate_saved_orders(ctx: &MmArc) -> Result<(), String> {
     let maker_entries = try_s!(json_dir_entries(&my_maker_orders_dir(&ctx)));
     maker_entries.iter().for_each(|entry| {
@@ -58,17 +58,22 @@
 #![cfg_attr(not(feature = "native"), allow(dead_code))]
 
 use bigdecimal::BigDecimal;
-use coins::{lp_coinfind, TransactionEnum};
-use common::{block_on, read_dir, rpc_response, slurp, write, HyRes};
+use coins::{coin_conf, lp_coinfind, lp_coinfindᵃ, TransactionDetails, TransactionEnum};
+use common::{block_on, now_ms, read_dir, rpc_response, slurp, write, HyRes};
 use common::mm_ctx::{from_ctx, MmArc};
+use futures::compat::Future01CompatExt;
 use http::Response;
+#[cfg(test)]
+use mocktopus::macros::*;
 use primitives::hash::{H160, H256, H264};
-use rpc::v1::types::{Bytes as BytesJson};
+use rpc::v1::types::{Bytes as BytesJson, H256 as H256Json};
 use serde_json::{self as json, Value as Json};
+use std::cmp::max;
 use std::collections::{HashSet, HashMap};
 use std::ffi::OsStr;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex, Weak};
+use std::sync::mpsc::Sender;
 use std::thread;
 use std::time::Duration;
 use uuid::Uuid;
@@ -129,23 +134,43 @@ mod maker_swap;
 #[path = "lp_swap/taker_swap.rs"]
 mod taker_swap;
 
+#[path = "lp_swap/swap_watcher.rs"]
+mod swap_watcher;
+
+#[path = "lp_swap/swap_events.rs"]
+mod swap_events;
+
 use maker_swap::{MakerSavedSwap, stats_maker_swap_file_path};
 use taker_swap::{TakerSavedSwap, stats_taker_swap_file_path};
 pub use maker_swap::{MakerSwap, run_maker_swap};
 pub use taker_swap::{TakerSwap, run_taker_swap};
+use swap_events::{notify_swap_event, SwapEventNotification};
+pub use swap_watcher::{taker_swap_watcher_data, watch_taker_swap, watched_swaps_kick_start};
+pub use swap_events::{check_swap_events_sink, set_swap_events_callback};
 
 /// Includes the grace time we add to the "normal" timeouts
 /// in order to give different and/or heavy communication channels a chance.
 const BASIC_COMM_TIMEOUT: u64 = 90;
 
-/// Default atomic swap payment locktime, in seconds.
+/// The atomic swap payment locktime is this number of the blocks of the slowest coin of the pair.
 /// Maker sends payment with LOCKTIME * 2
 /// Taker sends payment with LOCKTIME
-const PAYMENT_LOCKTIME: u64 = 3600 * 2 + 300 * 2;
+const PAYMENT_LOCKTIME_BLOCKS: u64 = 120;
+/// The coin average block time if "avg_blocktime" is not set in the coins config, in seconds
+const DEFAULT_AVG_BLOCKTIME: u64 = 60;
+/// The safety margin added to the locktime if "locktime_margin" is not set in the coins config, in seconds
+const DEFAULT_LOCKTIME_MARGIN: u64 = 600;
+/// The max excess of the counterparty payment locktime over the expected one, in percent.
+/// The shorter locktime is never accepted as it reduces our safety margin.
+const LOCKTIME_TOLERANCE_PERCENT: u64 = 10;
 const _SWAP_DEFAULT_NUM_CONFIRMS: u32 = 1;
 const _SWAP_DEFAULT_MAX_CONFIRMS: u32 = 6;
 /// MM2 checks that swap payment is confirmed every WAIT_CONFIRM_INTERVAL seconds
 const WAIT_CONFIRM_INTERVAL: u64 = 15;
+/// How often the finished swaps are checked for the funds to be recovered automatically, in seconds
+const SWAPS_RECOVERY_INTERVAL: u64 = 600;
+/// How many times the details of the recovery transaction are requested before the swap is left for the next check
+const RECOVERED_TX_DETAILS_ATTEMPTS: u32 = 10;
 
 #[derive(Debug, PartialEq, Serialize)]
 pub enum RecoveredSwapAction {
@@ -179,6 +204,18 @@ pub trait AtomicSwap: Send + Sync {
 struct SwapsContext {
     running_swaps: Mutex<Vec<Weak<dyn AtomicSwap>>>,
     banned_pubkeys: Mutex<HashSet<H256>>,
+    dex_fee_schedule: DexFeeSchedule,
+    /// The sender to the sink of my swaps events notifications, the sink is started on the first event
+    events_sender: Mutex<Option<Sender<SwapEventNotification>>>,
+    /// The uuids of my successful swaps per the other side pubkey, loaded from my swaps files on the first use
+    /// and updated when my swap is finished, so the reputation doesn't require reading all the files every time
+    successful_swaps: Mutex<Option<HashMap<[u8; 32], HashSet<String>>>>,
+}
+
+/// Creates the swaps context at startup, so the invalid "dex_fee_schedule" fails the start instead of the running swaps
+pub fn init_swaps_context(ctx: &MmArc) -> Result<(), String> {
+    try_s!(SwapsContext::from_ctx(ctx));
+    Ok(())
 }
 
 impl SwapsContext {
@@ -188,6 +225,9 @@ impl SwapsContext {
             Ok (SwapsContext {
                 running_swaps: Mutex::new(vec![]),
                 banned_pubkeys: Mutex::new(HashSet::new()),
+                dex_fee_schedule: try_s!(DexFeeSchedule::from_conf(&ctx.conf)),
+                events_sender: Mutex::new(None),
+                successful_swaps: Mutex::new(None),
             })
         })))
     }
@@ -274,39 +314,191 @@ pub fn active_swaps_using_coin(ctx: &MmArc, coin: &str) -> Result<Vec<Uuid>, Str
     Ok(uuids)
 }
 
+/// Returns the number of ongoing swaps trading the coin_a and coin_b in any direction
+pub fn active_swaps_count_for_pair(ctx: &MmArc, coin_a: &str, coin_b: &str) -> Result<usize, String> {
+    let swap_ctx = try_s!(SwapsContext::from_ctx(&ctx));
+    let swaps = try_s!(swap_ctx.running_swaps.lock());
+    let count = swaps.iter().filter_map(|swap| swap.upgrade()).filter(|swap|
+        (swap.maker_coin() == coin_a && swap.taker_coin() == coin_b) || (swap.maker_coin() == coin_b && swap.taker_coin() == coin_a)
+    ).count();
+    Ok(count)
+}
+
+/// The built-in payment locktime of the coin, in seconds, used by the nodes not having the coin locktime config.
 /// Some coins are "slow" (block time is high - e.g. BTC average block time is ~10 minutes).
 /// https://bitinfocharts.com/comparison/bitcoin-confirmationtime.html
+fn default_coin_payment_locktime(ticker: &str) -> u64 {
+    let default_locktime = DEFAULT_AVG_BLOCKTIME * PAYMENT_LOCKTIME_BLOCKS + DEFAULT_LOCKTIME_MARGIN;
+    match ticker {
+        "BTC" => default_locktime * 10,
+        "BCH" | "BTG" | "SBTC" => default_locktime * 4,
+        _ => default_locktime,
+    }
+}
+
+/// The swap payment locktime of the coin, in seconds.
+/// The locktime is derived from the "avg_blocktime" and "locktime_margin" fields of the coin config,
+/// e.g. `"avg_blocktime": 600, "locktime_margin": 5400` for BTC.
+/// The config can only increase the built-in locktime of the coin to keep the swaps safe
+/// and compatible with the nodes using the built-in locktimes.
+fn coin_payment_locktime(ticker: &str, conf: &Json) -> u64 {
+    let avg_blocktime = conf["avg_blocktime"].as_u64().unwrap_or(DEFAULT_AVG_BLOCKTIME);
+    let margin = conf["locktime_margin"].as_u64().unwrap_or(DEFAULT_LOCKTIME_MARGIN);
+    max(avg_blocktime * PAYMENT_LOCKTIME_BLOCKS + margin, default_coin_payment_locktime(ticker))
+}
+
 /// We need to increase payment locktime accordingly when at least 1 side of swap uses "slow" coin.
-fn lp_atomic_locktime(base: &str, rel: &str) -> u64 {
-    if base == "BTC" || rel == "BTC" {
-        PAYMENT_LOCKTIME * 10
-    } else if base == "BCH" || rel == "BCH" || base == "BTG" || rel == "BTG" || base == "SBTC" || rel == "SBTC" {
-        PAYMENT_LOCKTIME * 4
-    } else {
-        PAYMENT_LOCKTIME
+fn lp_atomic_locktime(ctx: &MmArc, base: &str, rel: &str) -> u64 {
+    max(coin_payment_locktime(base, &coin_conf(ctx, base)), coin_payment_locktime(rel, &coin_conf(ctx, rel)))
+}
+
+/// Checks that the payment locktime proposed by the counterparty during the negotiation
+/// is not shorter than the expected `started_at + lock_duration`
+/// and doesn't exceed it by more than `LOCKTIME_TOLERANCE_PERCENT`.
+/// The counterparty may derive the longer lock duration from its coins config.
+fn validate_payment_locktime(started_at: u64, payment_locktime: u64, lock_duration: u64) -> Result<(), String> {
+    let min_locktime = started_at + lock_duration;
+    let max_locktime = min_locktime + lock_duration * LOCKTIME_TOLERANCE_PERCENT / 100;
+    if payment_locktime < min_locktime || payment_locktime > max_locktime {
+        return ERR!("Payment locktime {} is out of the allowed range {}..={}", payment_locktime, min_locktime, max_locktime);
     }
+    Ok(())
 }
 
-fn dex_fee_rate(base: &str, rel: &str) -> BigDecimal {
-    if base == "KMD" || rel == "KMD" {
-        // 1/777 - 10%
-        BigDecimal::from(9) / BigDecimal::from(7770)
-    } else {
-        BigDecimal::from(1) / BigDecimal::from(777)
+/// 1/777
+fn default_dex_fee_rate() -> BigDecimal { BigDecimal::from(1) / BigDecimal::from(777) }
+
+/// The KMD pairs are traded with the 10% discount
+fn default_dex_fee_coin_rates() -> HashMap<String, BigDecimal> {
+    let mut rates = HashMap::new();
+    // 1/777 - 10%
+    rates.insert("KMD".to_owned(), BigDecimal::from(9) / BigDecimal::from(7770));
+    rates
+}
+
+fn default_min_dex_fee() -> BigDecimal { unwrap!("0.0001".parse()) }
+
+/// The dex fee rates and min amounts, loaded from the "dex_fee_schedule" field of the MM2 config.
+/// The taker pays the fee in the coin it sends and the maker validates it by its own schedule,
+/// so the nodes should use the same schedule for the swaps to pass the taker fee validation.
+#[derive(Debug, Deserialize)]
+struct DexFeeSchedule {
+    /// The rate of the pairs having no specific rate
+    #[serde(default = "default_dex_fee_rate")]
+    rate: BigDecimal,
+    /// The rates of all pairs involving the coin, the lower one is used if both coins of the pair have the rate
+    #[serde(default = "default_dex_fee_coin_rates")]
+    coin_rates: HashMap<String, BigDecimal>,
+    /// The rates of the "BASE/REL" pairs, the pairs are matched in both directions
+    #[serde(default)]
+    pair_rates: HashMap<String, BigDecimal>,
+    /// The min fee of the coins having no specific min fee, in the coin units
+    #[serde(default = "default_min_dex_fee")]
+    min_fee: BigDecimal,
+    /// The min fees by the coin ticker, in the coin units.
+    /// Should be set for the coins having a different number of decimals or a different value than the most of the coins.
+    #[serde(default)]
+    min_fees: HashMap<String, BigDecimal>,
+}
+
+impl Default for DexFeeSchedule {
+    fn default() -> DexFeeSchedule {
+        DexFeeSchedule {
+            rate: default_dex_fee_rate(),
+            coin_rates: default_dex_fee_coin_rates(),
+            pair_rates: HashMap::new(),
+            min_fee: default_min_dex_fee(),
+            min_fees: HashMap::new(),
+        }
     }
 }
 
-pub fn dex_fee_amount(base: &str, rel: &str, trade_amount: &BigDecimal) -> BigDecimal {
-    let rate = dex_fee_rate(base, rel);
-    let min_fee = unwrap!("0.0001".parse());
-    let fee_amount = trade_amount * rate;
+impl DexFeeSchedule {
+    fn from_conf(conf: &Json) -> Result<DexFeeSchedule, String> {
+        if conf["dex_fee_schedule"].is_null() {
+            return Ok(DexFeeSchedule::default());
+        }
+        let schedule: DexFeeSchedule = try_s!(json::from_value(conf["dex_fee_schedule"].clone()));
+        let zero = BigDecimal::from(0);
+        let one = BigDecimal::from(1);
+        let rates = Some(&schedule.rate).into_iter().chain(schedule.coin_rates.values()).chain(schedule.pair_rates.values());
+        for rate in rates {
+            if *rate < zero || *rate >= one {
+                return ERR!("The dex fee rate {} must be in the [0, 1) range", rate);
+            }
+        }
+        for min_fee in Some(&schedule.min_fee).into_iter().chain(schedule.min_fees.values()) {
+            if *min_fee < zero {
+                return ERR!("The min dex fee {} must not be negative", min_fee);
+            }
+        }
+        Ok(schedule)
+    }
+
+    fn rate(&self, maker_coin: &str, taker_coin: &str) -> BigDecimal {
+        let pair_rate = self.pair_rates.get(&format!("{}/{}", maker_coin, taker_coin))
+            .or_else(|| self.pair_rates.get(&format!("{}/{}", taker_coin, maker_coin)));
+        if let Some(rate) = pair_rate {
+            return rate.clone();
+        }
+        match (self.coin_rates.get(maker_coin), self.coin_rates.get(taker_coin)) {
+            (Some(maker_rate), Some(taker_rate)) => if maker_rate < taker_rate { maker_rate.clone() } else { taker_rate.clone() },
+            (Some(rate), None) | (None, Some(rate)) => rate.clone(),
+            (None, None) => self.rate.clone(),
+        }
+    }
+
+    /// The max rate of the pairs involving the coin, used when the other coin of the pair is not known yet
+    fn max_rate(&self, coin: &str) -> BigDecimal {
+        let coin_rate = match self.coin_rates.get(coin) {
+            // the lower rate is used if the other coin has the rate too
+            Some(rate) => rate.clone(),
+            // the pair is traded at the rate of the other coin or at the default rate
+            None => unwrap!(self.coin_rates.values().chain(Some(&self.rate)).max()).clone(),
+        };
+        self.pair_rates.iter()
+            .filter(|(pair, _)| pair.split('/').any(|pair_coin| pair_coin == coin))
+            .map(|(_, rate)| rate.clone())
+            .fold(coin_rate, std::cmp::max)
+    }
+
+    fn min_fee(&self, coin: &str) -> BigDecimal {
+        self.min_fees.get(coin).unwrap_or(&self.min_fee).clone()
+    }
+}
+
+/// The dex fee of the `trade_amount` of the `taker_coin` the taker sends to the maker, the fee is paid in the `taker_coin`
+pub fn dex_fee_amount(ctx: &MmArc, maker_coin: &str, taker_coin: &str, trade_amount: &BigDecimal) -> Result<BigDecimal, String> {
+    let swaps_ctx = try_s!(SwapsContext::from_ctx(ctx));
+    let schedule = &swaps_ctx.dex_fee_schedule;
+    let min_fee = schedule.min_fee(taker_coin);
+    let fee_amount = trade_amount * schedule.rate(maker_coin, taker_coin);
     if fee_amount < min_fee {
-        min_fee
+        Ok(min_fee)
     } else {
-        fee_amount
+        Ok(fee_amount)
     }
 }
 
+/// Returns the max taker volume such that the volume plus the dex fee fits the `available` amount of the `taker_coin`.
+/// The max dex fee rate of the `taker_coin` pairs is used if the `maker_coin` is not known.
+/// The volume is rounded down to 8 decimal places, None is returned if the min dex fee doesn't fit.
+pub fn max_taker_vol_from_available(ctx: &MmArc, available: &BigDecimal, maker_coin: Option<&str>, taker_coin: &str) -> Result<Option<BigDecimal>, String> {
+    let swaps_ctx = try_s!(SwapsContext::from_ctx(ctx));
+    let rate = match maker_coin {
+        Some(maker_coin) => swaps_ctx.dex_fee_schedule.rate(maker_coin, taker_coin),
+        None => swaps_ctx.dex_fee_schedule.max_rate(taker_coin),
+    };
+    let min_fee = swaps_ctx.dex_fee_schedule.min_fee(taker_coin);
+    // the dex fee is proportional to the volume unless it's less than the min fee
+    let vol = (available / (BigDecimal::from(1) + &rate)).with_scale(8);
+    if &vol * &rate >= min_fee {
+        return Ok(Some(vol));
+    }
+    let vol = (available - &min_fee).with_scale(8);
+    if vol > BigDecimal::from(0) { Ok(Some(vol)) } else { Ok(None) }
+}
+
 /// Data to be exchanged and validated on swap start, the replacement of LP_pubkeys_data, LP_choosei_data, etc.
 #[derive(Debug, Default, Deserializable, Eq, PartialEq, Serializable)]
 struct SwapNegotiationData {
@@ -387,6 +579,21 @@ impl SavedSwap {
         }
     }
 
+    fn is_success(&self) -> bool {
+        match self {
+            SavedSwap::Maker(swap) => swap.is_success(),
+            SavedSwap::Taker(swap) => swap.is_success(),
+        }
+    }
+
+    /// The persistent p2p pubkey of the other side of the swap
+    fn other_pubkey(&self) -> Option<H256Json> {
+        match self {
+            SavedSwap::Maker(swap) => swap.taker_pubkey(),
+            SavedSwap::Taker(swap) => swap.maker_pubkey(),
+        }
+    }
+
     fn recover_funds(self, ctx: MmArc) -> Result<RecoveredSwap, String> {
         let maker_ticker = try_s!(self.maker_coin_ticker());
         let maker_coin = match lp_coinfind(&ctx, &maker_ticker) {
@@ -424,6 +631,21 @@ impl SavedSwap {
         }
     }
 
+    /// The time since which the funds recovery of the finished swap can be attempted, in seconds
+    fn recoverable_since(&self) -> Result<u64, String> {
+        match self {
+            SavedSwap::Maker(saved) => saved.recoverable_since(),
+            SavedSwap::Taker(saved) => saved.recoverable_since(),
+        }
+    }
+
+    fn add_recovered_event(&mut self, action: &RecoveredSwapAction, tx: TransactionDetails) -> Result<(), String> {
+        match self {
+            SavedSwap::Maker(saved) => saved.add_recovered_event(action, tx),
+            SavedSwap::Taker(saved) => saved.add_recovered_event(action, tx),
+        }
+    }
+
     fn save_to_db(&self, ctx: &MmArc) -> Result<(), String> {
         let path = my_swap_file_path(ctx, self.uuid());
         if path.exists() {
@@ -582,6 +804,58 @@ pub fn my_recent_swaps(ctx: MmArc, req: Json) -> HyRes {
     }).to_string())
 }
 
+fn load_successful_swaps(ctx: &MmArc) -> Result<HashMap<[u8; 32], HashSet<String>>, String> {
+    let entries = try_s!(read_dir(&my_swaps_dir(ctx)));
+    let mut successful_swaps: HashMap<[u8; 32], HashSet<String>> = HashMap::new();
+    let swaps = entries.iter().filter_map(|(_, path)| {
+        if path.extension() != Some(OsStr::new("json")) { return None }
+        let content = slurp(&path).ok()?;
+        json::from_slice::<SavedSwap>(&content).ok()
+    });
+    for swap in swaps {
+        if let (true, Some(pubkey)) = (swap.is_success(), swap.other_pubkey()) {
+            successful_swaps.entry(pubkey.0).or_default().insert(swap.uuid().to_owned());
+        }
+    }
+    Ok(successful_swaps)
+}
+
+/// Loads the successful swaps from my swaps files unless they're loaded already
+pub fn init_successful_swaps(ctx: &MmArc) -> Result<(), String> {
+    let swap_ctx = try_s!(SwapsContext::from_ctx(ctx));
+    let mut successful_swaps = try_s!(swap_ctx.successful_swaps.lock());
+    if successful_swaps.is_none() {
+        *successful_swaps = Some(try_s!(load_successful_swaps(ctx)));
+    }
+    Ok(())
+}
+
+/// Returns the number of successful swaps with the node having the `pubkey`, it's used as the node reputation
+#[cfg_attr(test, mockable)]
+pub fn successful_swaps_count(ctx: &MmArc, pubkey: &H256Json) -> Result<usize, String> {
+    try_s!(init_successful_swaps(ctx));
+    let swap_ctx = try_s!(SwapsContext::from_ctx(ctx));
+    let successful_swaps = try_s!(swap_ctx.successful_swaps.lock());
+    Ok(successful_swaps.as_ref().and_then(|swaps| swaps.get(&pubkey.0)).map_or(0, |uuids| uuids.len()))
+}
+
+/// Adds my finished swap to the successful swaps if it's succeeded
+fn register_finished_swap(ctx: &MmArc, uuid: &str) -> Result<(), String> {
+    let content = try_s!(slurp(&my_swap_file_path(ctx, uuid)));
+    let swap: SavedSwap = try_s!(json::from_slice(&content));
+    let pubkey = match (swap.is_success(), swap.other_pubkey()) {
+        (true, Some(pubkey)) => pubkey,
+        _ => return Ok(()),
+    };
+    let swap_ctx = try_s!(SwapsContext::from_ctx(ctx));
+    let mut successful_swaps = try_s!(swap_ctx.successful_swaps.lock());
+    // the swaps are counted once they're loaded
+    if let Some(swaps) = successful_swaps.as_mut() {
+        swaps.entry(pubkey.0).or_default().insert(uuid.to_owned());
+    }
+    Ok(())
+}
+
 /// Find out the swaps that need to be kick-started, continue from the point where swap was interrupted
 /// Return the tickers of coins that must be enabled for swaps to continue
 pub fn swap_kick_starts(ctx: MmArc) -> HashSet<String> {
@@ -706,6 +980,99 @@ pub async fn recover_funds_of_swap(ctx: MmArc, req: Json) -> Result<Response<Vec
     Ok(try_s!(Response::builder().body(res)))
 }
 
+fn is_auto_recovery_enabled(ctx: &MmArc) -> bool { ctx.conf["auto_recover_swaps"].as_bool().unwrap_or(false) }
+
+/// Returns the recoverable swaps which funds can be recovered at the given time, skipping the running ones
+fn swaps_to_recover(ctx: &MmArc, now: u64) -> Result<Vec<SavedSwap>, String> {
+    let swap_ctx = try_s!(SwapsContext::from_ctx(ctx));
+    let running: HashSet<String> = try_s!(swap_ctx.running_swaps.lock()).iter()
+        .filter_map(|swap| swap.upgrade().map(|swap| swap.uuid().to_owned()))
+        .collect();
+    let entries = try_s!(read_dir(&my_swaps_dir(ctx)));
+    Ok(entries.iter().filter_map(|(_, path)| {
+        if path.extension() != Some(OsStr::new("json")) { return None }
+        let content = slurp(&path).ok()?;
+        json::from_slice::<SavedSwap>(&content).ok()
+    }).filter(|swap| {
+        if !swap.is_recoverable() || running.contains(swap.uuid()) { return false }
+        match swap.recoverable_since() {
+            Ok(since) => since <= now,
+            Err(e) => {
+                log!("Error " (e) " getting the recovery time of swap " (swap.uuid()));
+                false
+            },
+        }
+    }).collect())
+}
+
+/// Recovers the funds of the finished swap and records the recovery transaction as the swap event
+fn recover_funds_and_save(ctx: &MmArc, swap: SavedSwap) -> Result<RecoveredSwap, String> {
+    let uuid = swap.uuid().to_owned();
+    let recovered = try_s!(swap.recover_funds(ctx.clone()));
+    let hash = recovered.transaction.tx_hash();
+    let coin = match lp_coinfind(ctx, &recovered.coin) {
+        Ok(Some(c)) => c,
+        Ok(None) => return ERR!("Coin {} is not activated", recovered.coin),
+        Err(e) => return ERR!("Error {} on {} coin find attempt", e, recovered.coin),
+    };
+    // the transaction is already sent so only transport errors are expected to happen,
+    // the retries are limited so the unavailable coin doesn't hold the recovery of the other swaps
+    let mut attempts = 0;
+    let tx_details = loop {
+        match block_on(coin.tx_details_by_hash(&hash).compat()) {
+            Ok(details) => break details,
+            Err(e) => {
+                attempts += 1;
+                if attempts >= RECOVERED_TX_DETAILS_ATTEMPTS {
+                    return ERR!("Error {} getting tx details of {:02x}, the swap event is not saved", e, hash);
+                }
+                if ctx.is_stopping() { return ERR!("MM is stopping") }
+                log!({"Error {} getting tx details of {:02x}", e, hash});
+                thread::sleep(Duration::from_secs(30));
+            }
+        }
+    };
+
+    let path = my_swap_file_path(ctx, &uuid);
+    let mut swap: SavedSwap = try_s!(json::from_slice(&try_s!(slurp(&path))));
+    try_s!(swap.add_recovered_event(&recovered.action, tx_details));
+    try_s!(write(&path, &try_s!(json::to_vec(&swap))));
+    Ok(recovered)
+}
+
+/// Periodically recovers the funds of the finished swaps which `is_recoverable`,
+/// so the user doesn't have to call the `recover_funds_of_swap` for each of them.
+/// It's opt-in, enabled with the `"auto_recover_swaps": true` in the MM2 config.
+pub fn lp_swaps_recovery_loop(ctx: MmArc) {
+    if !is_auto_recovery_enabled(&ctx) {
+        log!("Automatic swaps funds recovery is disabled");
+        return;
+    }
+    let mut last_check = 0;
+    loop {
+        if ctx.is_stopping() { break }
+        let now = now_ms() / 1000;
+        if last_check + SWAPS_RECOVERY_INTERVAL <= now {
+            last_check = now;
+            let swaps = match swaps_to_recover(&ctx, now) {
+                Ok(swaps) => swaps,
+                Err(e) => {
+                    log!("Error " (e) " looking for the swaps to recover");
+                    vec![]
+                },
+            };
+            for swap in swaps {
+                let uuid = swap.uuid().to_owned();
+                match recover_funds_and_save(&ctx, swap) {
+                    Ok(recovered) => log!("Recovered the funds of swap " (uuid) ": " [recovered.action] " " (recovered.coin) " tx " [recovered.transaction.tx_hash()]),
+                    Err(e) => log!("Error " (e) " recovering the funds of swap " (uuid)),
+                }
+            }
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
 pub async fn import_swaps(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
     let swaps: Vec<SavedSwap> = try_s!(json::from_value(req["swaps"].clone()));
     let mut imported = vec![];
@@ -725,42 +1092,214 @@ pub async fn import_swaps(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, St
     Ok(try_s!(Response::builder().body(res)))
 }
 
+/// Returns the max volume of the coin that can be offered by the taker order.
+/// The trade fee is deducted twice as the taker sends the dex fee and the payment transactions.
+pub async fn max_taker_vol(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    let ticker = try_s!(req["coin"].as_str().ok_or("No 'coin' field")).to_owned();
+    // the dex fee rate depends on the pair, the max rate of the coin pairs is used if the other coin is not set
+    let other_ticker = req["trade_with"].as_str();
+    let coin = match lp_coinfindᵃ(&ctx, &ticker).await {
+        Ok(Some(t)) => t,
+        Ok(None) => return ERR!("No such coin: {}", ticker),
+        Err(err) => return ERR!("!lp_coinfind({}): {}", ticker, err),
+    };
+    let balance = try_s!(coin.my_balance().compat().await);
+    let locked = get_locked_amount(&ctx, &ticker);
+    let trade_fee = try_s!(coin.get_trade_fee().compat().await);
+    let mut available = &balance - &locked;
+    if trade_fee.coin == ticker {
+        available -= &trade_fee.amount * BigDecimal::from(2);
+    }
+    let max_vol = try_s!(max_taker_vol_from_available(&ctx, &available, other_ticker, &ticker)).unwrap_or_else(|| 0.into());
+    let res = try_s!(json::to_vec(&json!({
+        "result": {
+            "coin": ticker,
+            "balance": balance,
+            "locked_by_swaps": locked,
+            "trade_fee": trade_fee,
+            "max_vol": max_vol,
+        }
+    })));
+    Ok(try_s!(Response::builder().body(res)))
+}
+
 #[cfg(test)]
 mod lp_swap_tests {
+    use common::mm_ctx::MmCtxBuilder;
+    use common::privkey::key_pair_from_seed;
     use serialization::{deserialize, serialize};
     use super::*;
 
     #[test]
     fn test_dex_fee_amount() {
+        let ctx = MmCtxBuilder::default().into_mm_arc();
         let base = "BTC";
         let rel = "ETH";
         let amount = 1.into();
-        let actual_fee = dex_fee_amount(base, rel, &amount);
+        let actual_fee = unwrap!(dex_fee_amount(&ctx, base, rel, &amount));
         let expected_fee = amount / 777;
         assert_eq!(expected_fee, actual_fee);
 
         let base = "KMD";
         let rel = "ETH";
         let amount = 1.into();
-        let actual_fee = dex_fee_amount(base, rel, &amount);
+        let actual_fee = unwrap!(dex_fee_amount(&ctx, base, rel, &amount));
         let expected_fee = amount * BigDecimal::from(9) / 7770;
         assert_eq!(expected_fee, actual_fee);
 
         let base = "BTC";
         let rel = "KMD";
         let amount = 1.into();
-        let actual_fee = dex_fee_amount(base, rel, &amount);
+        let actual_fee = unwrap!(dex_fee_amount(&ctx, base, rel, &amount));
         let expected_fee = amount * BigDecimal::from(9) / 7770;
         assert_eq!(expected_fee, actual_fee);
 
         let base = "BTC";
         let rel = "KMD";
         let amount = unwrap!("0.001".parse());
-        let actual_fee = dex_fee_amount(base, rel, &amount);
+        let actual_fee = unwrap!(dex_fee_amount(&ctx, base, rel, &amount));
         let expected_fee: BigDecimal = unwrap!("0.0001".parse());
         assert_eq!(expected_fee, actual_fee);
     }
 
+    #[test]
+    fn test_lp_atomic_locktime() {
+        let conf = json!({
+            "coins": [
+                {"coin": "KMD"},
+                {"coin": "BTC", "avg_blocktime": 600, "locktime_margin": 5400},
+                {"coin": "BCH", "avg_blocktime": 240},
+                {"coin": "DOGE", "avg_blocktime": 120},
+            ]
+        });
+        let ctx = MmCtxBuilder::new().with_conf(conf).into_mm_arc();
+        assert_eq!(7800, lp_atomic_locktime(&ctx, "KMD", "ETH"));
+        assert_eq!(15000, lp_atomic_locktime(&ctx, "DOGE", "KMD"));
+        // the config can't reduce the built-in locktimes of the slow coins
+        assert_eq!(78000, lp_atomic_locktime(&ctx, "KMD", "BTC"));
+        assert_eq!(78000, lp_atomic_locktime(&ctx, "BTC", "BCH"));
+        assert_eq!(31200, lp_atomic_locktime(&ctx, "BCH", "KMD"));
+        assert_eq!(31200, lp_atomic_locktime(&ctx, "BTG", "KMD"));
+
+        let ctx = MmCtxBuilder::default().into_mm_arc();
+        assert_eq!(78000, lp_atomic_locktime(&ctx, "BTC", "KMD"));
+        assert_eq!(31200, lp_atomic_locktime(&ctx, "KMD", "SBTC"));
+    }
+
+    #[test]
+    fn test_validate_payment_locktime() {
+        let started_at = 1000;
+        let lock_duration = 7800;
+        unwrap!(validate_payment_locktime(started_at, started_at + lock_duration, lock_duration));
+        unwrap!(validate_payment_locktime(started_at, started_at + lock_duration + 780, lock_duration));
+        // the counterparty must not shorten its payment locktime
+        assert!(validate_payment_locktime(started_at, started_at + lock_duration - 1, lock_duration).is_err());
+        assert!(validate_payment_locktime(started_at, started_at + lock_duration + 781, lock_duration).is_err());
+        assert!(validate_payment_locktime(started_at, 0, lock_duration).is_err());
+    }
+
+    #[test]
+    fn test_max_taker_vol_from_available() {
+        let ctx = MmCtxBuilder::default().into_mm_arc();
+        let available = BigDecimal::from(1);
+        let max_vol = unwrap!(unwrap!(max_taker_vol_from_available(&ctx, &available, Some("ETH"), "BTC")));
+        let required = &max_vol + unwrap!(dex_fee_amount(&ctx, "ETH", "BTC", &max_vol));
+        assert!(required <= available);
+        assert!(required > &available - unwrap!("0.00000001".parse::<BigDecimal>()));
+
+        // the volume is too low to pay the proportional fee, so the min fee is deducted
+        let available: BigDecimal = unwrap!("0.01".parse());
+        let max_vol = unwrap!(unwrap!(max_taker_vol_from_available(&ctx, &available, Some("KMD"), "BTC")));
+        assert_eq!(unwrap!("0.0099".parse::<BigDecimal>()), max_vol);
+
+        let available: BigDecimal = unwrap!("0.0001".parse());
+        assert_eq!(None, unwrap!(max_taker_vol_from_available(&ctx, &available, Some("KMD"), "BTC")));
+    }
+
+    #[test]
+    fn test_dex_fee_schedule() {
+        let conf = json!({
+            "dex_fee_schedule": {
+                "rate": "0.002",
+                "coin_rates": {"KMD": "0.001", "RICK": "0.0005"},
+                "pair_rates": {"BTC/ETH": "0.003"},
+                "min_fees": {"BTC": "0.00001", "DOGE": "1"},
+            }
+        });
+        let ctx = MmCtxBuilder::new().with_conf(conf).into_mm_arc();
+        let amount = BigDecimal::from(10);
+        let fee = |maker_coin, taker_coin, amount: &BigDecimal| unwrap!(dex_fee_amount(&ctx, maker_coin, taker_coin, amount));
+        assert_eq!(unwrap!("0.02".parse::<BigDecimal>()), fee("LTC", "ETH", &amount));
+        assert_eq!(unwrap!("0.03".parse::<BigDecimal>()), fee("BTC", "ETH", &amount));
+        assert_eq!(unwrap!("0.03".parse::<BigDecimal>()), fee("ETH", "BTC", &amount));
+        assert_eq!(unwrap!("0.01".parse::<BigDecimal>()), fee("BTC", "KMD", &amount));
+        // the lower rate is used if both coins have the rate
+        assert_eq!(unwrap!("0.005".parse::<BigDecimal>()), fee("KMD", "RICK", &amount));
+        // the min fee is in the units of the taker coin
+        assert_eq!(BigDecimal::from(1), fee("LTC", "DOGE", &amount));
+        assert_eq!(unwrap!("0.00001".parse::<BigDecimal>()), fee("ETH", "BTC", &unwrap!("0.001".parse())));
+        assert_eq!(unwrap!("0.0001".parse::<BigDecimal>()), fee("BTC", "ETH", &unwrap!("0.001".parse())));
+
+        // the max rate of the coin pairs is used if the other coin is not known
+        let max_vol = |available: &BigDecimal, taker_coin| unwrap!(unwrap!(max_taker_vol_from_available(&ctx, available, None, taker_coin)));
+        assert_eq!(unwrap!("9.97008973".parse::<BigDecimal>()), max_vol(&amount, "ETH"));
+        assert_eq!(unwrap!("9.99000999".parse::<BigDecimal>()), max_vol(&amount, "KMD"));
+        assert_eq!(unwrap!("9.98003992".parse::<BigDecimal>()), max_vol(&amount, "LTC"));
+        for &(maker_coin, taker_coin) in &[("LTC", "ETH"), ("BTC", "ETH"), ("RICK", "KMD"), ("LTC", "KMD"), ("KMD", "LTC")] {
+            assert!(max_vol(&amount, taker_coin) + fee(maker_coin, taker_coin, &max_vol(&amount, taker_coin)) <= amount);
+        }
+
+        assert!(DexFeeSchedule::from_conf(&json!({"dex_fee_schedule": {"rate": 1}})).is_err());
+        assert!(DexFeeSchedule::from_conf(&json!({"dex_fee_schedule": {"pair_rates": {"BTC/ETH": -0.1}}})).is_err());
+        assert!(DexFeeSchedule::from_conf(&json!({"dex_fee_schedule": {"min_fee": -1}})).is_err());
+
+        // the invalid schedule is reported by the startup init and by the fee computation instead of panicking
+        let ctx = MmCtxBuilder::new().with_conf(json!({"dex_fee_schedule": {"rate": 1}})).into_mm_arc();
+        assert!(init_swaps_context(&ctx).is_err());
+        assert!(dex_fee_amount(&ctx, "ETH", "BTC", &BigDecimal::from(1)).is_err());
+    }
+
+    #[test]
+    fn test_successful_swaps_count_uses_loaded_swaps() {
+        let ctx = MmCtxBuilder::default().into_mm_arc();
+        let swap_ctx = unwrap!(SwapsContext::from_ctx(&ctx));
+        let mut swaps = HashMap::new();
+        swaps.insert([1; 32], vec!["swap1".to_owned(), "swap2".to_owned()].into_iter().collect());
+        *unwrap!(swap_ctx.successful_swaps.lock()) = Some(swaps);
+
+        // my swaps files are not read again, there are no files in the test context anyway
+        assert_eq!(2, unwrap!(successful_swaps_count(&ctx, &H256Json::from([1; 32]))));
+        assert_eq!(0, unwrap!(successful_swaps_count(&ctx, &H256Json::from([2; 32]))));
+    }
+
+    #[test]
+    fn test_swaps_to_recover() {
+        let key_pair = unwrap!(key_pair_from_seed("spice describe gravity federal blast come thank unfair canal monkey style afraid"));
+        let dbdir = std::env::temp_dir().join("test_swaps_to_recover");
+        let ctx = MmCtxBuilder::new()
+            .with_conf(json!({"dbdir": unwrap!(dbdir.to_str())}))
+            .with_secp256k1_key_pair(key_pair)
+            .into_mm_arc();
+        // the taker payment refund failed, so it can be refunded once the locktime passes
+        let taker_saved_json = r#"{"error_events":["StartFailed","NegotiateFailed","TakerFeeSendFailed","MakerPaymentValidateFailed","TakerPaymentTransactionFailed","TakerPaymentDataSendFailed","TakerPaymentWaitForSpendFailed","MakerPaymentSpendFailed","TakerPaymentRefunded","TakerPaymentRefundFailed"],"events":[{"event":{"data":{"lock_duration":7800,"maker":"1bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8","maker_amount":"0.58610590","maker_coin":"KMD","maker_coin_start_block":1450923,"maker_payment_confirmations":1,"maker_payment_wait":1563623475,"my_persistent_pub":"02713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91","started_at":1563620875,"taker_amount":"0.0077700000552410000000000","taker_coin":"LTC","taker_coin_start_block":1670837,"taker_payment_confirmations":1,"taker_payment_lock":1563628675,"uuid":"9db641f5-4300-4527-9fa6-f1c391d42c35"},"type":"Started"},"timestamp":1563620875766},{"event":{"data":{"maker_payment_locktime":1563636475,"maker_pubkey":"031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8","secret_hash":"7ed38daab6085c1a1e4426e61dc87a3c2c081a95"},"type":"Negotiated"},"timestamp":1563620955014},{"event":{"data":{"block_height":0,"coin":"LTC","fee_details":{"amount":0.001},"from":["LKquWDGkJHEcFn85Dzw4FV5XwYp8GT3WvD"],"internal_id":"6740136eaaa615d9d231969e3a9599d0fc59e53989237a8d31cd6fc86c160013","my_balance_change":-0.0011,"received_by_me":0.0168204,"spent_by_me":0.0179204,"timestamp":0,"to":["LKquWDGkJHEcFn85Dzw4FV5XwYp8GT3WvD","LdeeicEe3dYpjy36TPWrufiGToyaaEP2Zs"],"total_amount":0.0179204,"tx_hash":"6740136eaaa615d9d231969e3a9599d0fc59e53989237a8d31cd6fc86c160013","tx_hex":"0100000001a2586ea8294cedc55741bef625ba72c646399903391a7f6c604a58c6263135f2000000006b4830450221009c78c8ba4a7accab6b09f9a95da5bc59c81f4fc1e60b288ec3c5462b4d02ef01022056b63be1629cf17751d3cc5ffec51bcb1d7f9396e9ce9ca254d0f34104f7263a012102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ffffffff0210270000000000001976a914ca1e04745e8ca0c60d8c5881531d51bec470743f88ac78aa1900000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac5bf6325d"},"type":"TakerFeeSent"},"timestamp":1563620958220},{"event":{"data":{"block_height":0,"coin":"KMD","fee_details":{"amount":1e-05},"from":["RT9MpMyucqXiX8bZLimXBnrrn2ofmdGNKd"],"internal_id":"d0f6e664cea9d89fe7b5cf8005fdca070d1ab1d05a482aaef95c08cdaecddf0a","my_balance_change":0,"received_by_me":0,"spent_by_me":0,"timestamp":0,"to":["RT9MpMyucqXiX8bZLimXBnrrn2ofmdGNKd","bLVo4svJDxUF6C2fVivmV91HJqVjrkkAf4"],"total_amount":0.99998999,"tx_hash":"d0f6e664cea9d89fe7b5cf8005fdca070d1ab1d05a482aaef95c08cdaecddf0a","tx_hex":"0400008085202f89019f1cbda354342cdf982046b331bbd3791f53b692efc6e4becc36be495b2977d9000000006b483045022100fa9d4557394141f6a8b9bfb8cd594a521fd8bcd1965dbf8bc4e04abc849ac66e0220589f521814c10a7561abfd5e432f7a2ee60d4875fe4604618af3207dae531ac00121031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8ffffffff029e537e030000000017a9145534898009f1467191065f6890b96914b39a1c018791857702000000001976a914c3f710deb7320b0efa6edb14e3ebeeb9155fa90d88ac72ee325d000000000000000000000000000000"},"type":"MakerPaymentReceived"},"timestamp":1563620999307},{"event":{"type":"MakerPaymentWaitConfirmStarted"},"timestamp":1563620999310},{"event":{"type":"MakerPaymentValidatedAndConfirmed"},"timestamp":1563621244153},{"event":{"data":{"block_height":0,"coin":"LTC","fee_details":{"amount":0.001},"from":["LKquWDGkJHEcFn85Dzw4FV5XwYp8GT3WvD"],"internal_id":"1e883eb2f3991e84ba27f53651f89b7dda708678a5b9813d043577f222b9ca30","my_balance_change":-0.00877,"received_by_me":0.0080504,"spent_by_me":0.0168204,"timestamp":0,"to":["3DgMcEEjxwXfnEVapgQSCBVy2tz9X41RmR","LKquWDGkJHEcFn85Dzw4FV5XwYp8GT3WvD"],"total_amount":0.0168204,"tx_hash":"1e883eb2f3991e84ba27f53651f89b7dda708678a5b9813d043577f222b9ca30","tx_hex":"01000000011300166cc86fcd318d7a238939e559fcd099953a9e9631d2d915a6aa6e134067010000006a47304402206781d5f2db2ff13d2ec7e266f774ea5630cc2dba4019e18e9716131b8b026051022006ebb33857b6d180f13aa6be2fc532f9734abde9d00ae14757e7d7ba3741c08c012102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ffffffff0228db0b000000000017a91483818667161bf94adda3964a81a231cbf6f5338187b0480c00000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac7cf7325d"},"type":"TakerPaymentSent"},"timestamp":1563621246370},{"event":{"data":{"error":"utxo:1145] rpc_clients:782] Waited too long until 1563628675 for output TransactionOutput { value: 777000, script_pubkey: a91483818667161bf94adda3964a81a231cbf6f5338187 } to be spent "},"type":"TakerPaymentWaitForSpendFailed"},"timestamp":1563638060370},{"event":{"data":{"error":"lp_swap:2025] utxo:938] rpc_clients:719] JsonRpcError { request: JsonRpcRequest { jsonrpc: \"2.0\", id: \"9\", method: \"blockchain.transaction.broadcast\", params: [String(\"010000000130cab922f27735043d81b9a5788670da7d9bf85136f527ba841e99f3b23e881e00000000b6473044022058a0c1da6bcf8c1418899ff8475f3ab6dddbff918528451c1fe71c2f7dad176302204c2e0bcf8f9b5f09e02ccfeb9256e9b34fb355ea655a5704a8a3fa920079b91501514c6b63048314335db1752102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ac6782012088a9147ed38daab6085c1a1e4426e61dc87a3c2c081a958821031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8ac68feffffff0188540a00000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac1c2b335d\")] }, error: Response(Object({\"code\": Number(1), \"message\": String(\"the transaction was rejected by network rules.\\n\\nMissing inputs\\n[010000000130cab922f27735043d81b9a5788670da7d9bf85136f527ba841e99f3b23e881e00000000b6473044022058a0c1da6bcf8c1418899ff8475f3ab6dddbff918528451c1fe71c2f7dad176302204c2e0bcf8f9b5f09e02ccfeb9256e9b34fb355ea655a5704a8a3fa920079b91501514c6b63048314335db1752102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ac6782012088a9147ed38daab6085c1a1e4426e61dc87a3c2c081a958821031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8ac68feffffff0188540a00000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac1c2b335d]\")})) }"},"type":"TakerPaymentRefundFailed"},"timestamp":1563638060583},{"event":{"type":"Finished"},"timestamp":1563638060585}],"success_events":["Started","Negotiated","TakerFeeSent","MakerPaymentReceived","MakerPaymentWaitConfirmStarted","MakerPaymentValidatedAndConfirmed","TakerPaymentSent","TakerPaymentSpent","MakerPaymentSpent","Finished"],"uuid":"9db641f5-4300-4527-9fa6-f1c391d42c35"}"#;
+        let _ = std::fs::remove_dir_all(&dbdir);
+        unwrap!(std::fs::create_dir_all(my_swaps_dir(&ctx)));
+        unwrap!(write(&my_swap_file_path(&ctx, "9db641f5-4300-4527-9fa6-f1c391d42c35"), &taker_saved_json));
+
+        let recoverable_since = 1563628675 + 3700;
+        assert!(unwrap!(swaps_to_recover(&ctx, recoverable_since - 1)).is_empty());
+        let swaps = unwrap!(swaps_to_recover(&ctx, recoverable_since));
+        unwrap!(std::fs::remove_dir_all(&dbdir));
+        assert_eq!(1, swaps.len());
+        assert_eq!("9db641f5-4300-4527-9fa6-f1c391d42c35", swaps[0].uuid());
+    }
+
+    #[test]
+    fn test_auto_recovery_is_opt_in() {
+        assert!(!is_auto_recovery_enabled(&MmCtxBuilder::default().into_mm_arc()));
+        assert!(is_auto_recovery_enabled(&MmCtxBuilder::new().with_conf(json!({"auto_recover_swaps": true})).into_mm_arc()));
+    }
+
     #[test]
     fn test_serde_swap_negotiation_data() {
         let data = SwapNegotiationData::default();
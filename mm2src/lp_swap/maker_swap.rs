@@ -22,7 +22,7 @@ use std::path::PathBuf;
 use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::sync::atomic::Ordering;
 use super::{ban_pubkey, broadcast_my_swap_status, dex_fee_amount, get_locked_amount_by_other_swaps,
-  lp_atomic_locktime, my_swap_file_path,
+  lp_atomic_locktime, my_swap_file_path, notify_swap_event, register_finished_swap, validate_payment_locktime,
   AtomicSwap, LockedAmount, MySwapInfo, RecoveredSwap, RecoveredSwapAction,
   SavedSwap, SwapsContext, SwapError, SwapNegotiationData,
   BASIC_COMM_TIMEOUT, WAIT_CONFIRM_INTERVAL};
@@ -236,7 +236,7 @@ impl MakerSwap {
             ));
         };
 
-        let lock_duration = lp_atomic_locktime(self.maker_coin.ticker(), self.taker_coin.ticker());
+        let lock_duration = lp_atomic_locktime(&self.ctx, self.maker_coin.ticker(), self.taker_coin.ticker());
 
         let secret: [u8; 32] = {
             #[cfg(feature = "native")]
@@ -327,11 +327,10 @@ impl MakerSwap {
             ))
         }
 
-        let expected_lock_time = taker_data.started_at + self.r().data.lock_duration;
-        if taker_data.payment_locktime != expected_lock_time {
+        if let Err(e) = validate_payment_locktime(taker_data.started_at, taker_data.payment_locktime, self.r().data.lock_duration) {
             return Ok((
                 Some(MakerSwapCommand::Finish),
-                vec![MakerSwapEvent::NegotiateFailed(ERRL!("taker_data.payment_locktime {} not equal to expected {}", taker_data.payment_locktime, expected_lock_time).into())]
+                vec![MakerSwapEvent::NegotiateFailed(ERRL!("!validate_payment_locktime {}", e).into())]
             ))
         }
 
@@ -375,7 +374,13 @@ impl MakerSwap {
         log!({ "Taker fee tx {:02x}", hash });
 
         let fee_addr_pub_key = unwrap!(hex::decode("03bc2c7ba671bae4a6fc835244c9762b41647b9827d4780a89a949b984a8ddcc06"));
-        let fee_amount = dex_fee_amount(&self.r().data.maker_coin, &self.r().data.taker_coin, &self.taker_amount);
+        let fee_amount = match dex_fee_amount(&self.ctx, &self.r().data.maker_coin, &self.r().data.taker_coin, &self.taker_amount) {
+            Ok(amount) => amount,
+            Err(e) => return Ok((
+                Some(MakerSwapCommand::Finish),
+                vec![MakerSwapEvent::TakerFeeValidateFailed(ERRL!("{}", e).into())]
+            )),
+        };
 
         let mut attempts = 0;
         loop {
@@ -903,6 +908,24 @@ impl MakerSavedSwap {
         }
     }
 
+    /// The swap is successful when maker has spent the taker payment
+    pub fn is_success(&self) -> bool {
+        self.events.iter().any(|event| match event.event {
+            MakerSwapEvent::TakerPaymentSpent(_) => true,
+            _ => false,
+        })
+    }
+
+    pub fn taker_pubkey(&self) -> Option<H256Json> {
+        match self.events.first() {
+            Some(event) => match &event.event {
+                MakerSwapEvent::Started(data) => Some(data.taker.clone()),
+                _ => None,
+            },
+            None => None,
+        }
+    }
+
     pub fn get_my_info(&self) -> Option<MySwapInfo> {
         match self.events.first() {
             Some(event) => match &event.event {
@@ -944,6 +967,32 @@ impl MakerSavedSwap {
         }
         true
     }
+
+    /// The time since which the maker payment can be refunded, in seconds
+    pub fn recoverable_since(&self) -> Result<u64, String> {
+        match self.events.first() {
+            Some(event) => match &event.event {
+                MakerSwapEvent::Started(data) => Ok(data.maker_payment_lock + 3700),
+                _ => ERR!("First swap event must be Started"),
+            },
+            None => ERR!("Can't get maker payment lock, events are empty"),
+        }
+    }
+
+    /// Records the transaction of the recovered funds before the Finished event
+    pub fn add_recovered_event(&mut self, action: &RecoveredSwapAction, tx: TransactionDetails) -> Result<(), String> {
+        if !self.is_finished() { return ERR!("Swap must be finished to record the recovered funds") }
+        let event = match action {
+            RecoveredSwapAction::RefundedMyPayment => MakerSwapEvent::MakerPaymentRefunded(tx),
+            RecoveredSwapAction::SpentOtherPayment => return ERR!("Maker swap funds can be recovered by refund only"),
+        };
+        let finished_idx = self.events.len() - 1;
+        self.events.insert(finished_idx, MakerSavedEvent {
+            timestamp: now_ms(),
+            event,
+        });
+        Ok(())
+    }
 }
 
 /// Starts the maker swap and drives it to completion (until None next command received).
@@ -966,11 +1015,13 @@ pub async fn run_maker_swap(swap: MakerSwap, initial_command: Option<MakerSwapCo
         let res = unwrap!(running_swap.handle_command(command).await, "!handle_command");
         events = res.1;
         for event in events {
+            let timestamp = now_ms();
             let to_save = MakerSavedEvent {
-                timestamp: now_ms(),
+                timestamp,
                 event: event.clone(),
             };
             unwrap!(save_my_maker_swap_event(&ctx, &running_swap, to_save), "!save_my_maker_swap_event");
+            notify_swap_event(&ctx, &uuid, "Maker", timestamp, &event);
             if event.should_ban_taker() { ban_pubkey(&ctx, running_swap.taker.bytes.into()) }
             status.status(swap_tags!(), &event.status_str());
             unwrap!(running_swap.apply_event(event), "!apply_event");
@@ -981,6 +1032,9 @@ pub async fn run_maker_swap(swap: MakerSwap, initial_command: Option<MakerSwapCo
                 if let Err(e) = broadcast_my_swap_status(&uuid, &ctx) {
                     log!("!broadcast_my_swap_status(" (uuid) "): " (e));
                 }
+                if let Err(e) = register_finished_swap(&ctx, &uuid) {
+                    log!("!register_finished_swap(" (uuid) "): " (e));
+                }
                 break;
             },
         }
@@ -0,0 +1,309 @@
+//! Notifications of my swaps events for the backends which would otherwise poll the `my_swap_status`.
+//!
+//! Every event saved by the `run_maker_swap` and `run_taker_swap` is delivered
+//! * to the sink configured with the `"swap_events_sink"` in the MM2 config, either
+//!   `{"type": "Webhook", "data": {"url": "http://127.0.0.1:8000/swaps", "retries": 3, "retry_interval": 5}}`
+//!   POSTing the JSON notification and retrying the failed requests, or
+//!   `{"type": "UnixSocket", "data": {"path": "/tmp/mm2_swaps.sock"}}` writing the notification as a JSON line;
+//! * to the C callback registered with the `mm2_swap_events_cb` of the `mm2_lib`.
+//!
+//! The sink is served by a dedicated thread in the order of the events, so a slow or unavailable sink doesn't hold the swaps.
+//! The sink config is checked by the `lp_init`, the swaps context doesn't depend on it.
+//! The C callback is process-global: it receives the events of every MM2 context running in the process.
+
+use common::block_on;
+use common::mm_ctx::MmArc;
+use common::wio::slurp_reqʹ;
+use http::Request;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::{self as json, Value as Json};
+use std::ffi::CString;
+#[cfg(unix)]
+use std::io::Write;
+use std::os::raw::c_char;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use super::SwapsContext;
+
+fn default_webhook_retries() -> u32 { 3 }
+
+fn default_webhook_retry_interval() -> u64 { 5 }
+
+/// The local sink of my swaps events notifications
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", content = "data")]
+pub enum SwapEventsSink {
+    /// POSTs every notification as JSON to the URL
+    Webhook {
+        url: String,
+        /// How many times the failed request is retried
+        #[serde(default = "default_webhook_retries")]
+        retries: u32,
+        /// The interval between the retries, in seconds
+        #[serde(default = "default_webhook_retry_interval")]
+        retry_interval: u64,
+    },
+    /// Writes every notification as a JSON line to the Unix domain socket
+    UnixSocket { path: String },
+}
+
+impl SwapEventsSink {
+    pub fn from_conf(conf: &Json) -> Result<Option<SwapEventsSink>, String> {
+        if conf["swap_events_sink"].is_null() { return Ok(None) }
+        Ok(Some(try_s!(json::from_value(conf["swap_events_sink"].clone()))))
+    }
+}
+
+/// Checks the "swap_events_sink" of the MM2 config at startup, so the invalid sink fails the start instead of being logged on every event
+pub fn check_swap_events_sink(ctx: &MmArc) -> Result<(), String> {
+    try_s!(SwapEventsSink::from_conf(&ctx.conf));
+    Ok(())
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SwapEventNotification {
+    uuid: String,
+    /// "Maker" or "Taker"
+    swap_type: String,
+    timestamp: u64,
+    /// The swap event with its "type" and "data"
+    event: Json,
+}
+
+lazy_static! {
+    /// The C callback receiving the JSON notifications, registered by the `mm2_lib` user.
+    /// It isn't keyed by the context since the `mm2_lib` runs the single MM2 instance per process.
+    static ref SWAP_EVENTS_CB: Mutex<Option<extern fn (event: *const c_char)>> = Mutex::new(None);
+}
+
+/// Registers the C callback receiving the notifications of my swaps events, `None` unregisters it.
+/// The callback is process-global and replaces the previously registered one.
+pub fn set_swap_events_callback(cb: Option<extern fn (event: *const c_char)>) {
+    *unwrap!(SWAP_EVENTS_CB.lock()) = cb;
+}
+
+async fn post_to_webhook(url: &str, body: Vec<u8>) -> Result<(), String> {
+    let request = try_s!(Request::builder().method("POST").uri(url).header("Content-Type", "application/json").body(body));
+    let (status, _headers, body) = try_s!(slurp_reqʹ(request).await);
+    if !status.is_success() {
+        return ERR!("Webhook responded with {:?}: {}", status, String::from_utf8_lossy(&body));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn write_to_unix_socket(path: &str, mut content: Vec<u8>) -> Result<(), String> {
+    content.push(b'\n');
+    let mut stream = try_s!(UnixStream::connect(path));
+    try_s!(stream.write_all(&content));
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_to_unix_socket(_path: &str, _content: Vec<u8>) -> Result<(), String> {
+    ERR!("Unix sockets are not supported on this platform")
+}
+
+fn deliver_notification(ctx: &MmArc, sink: &SwapEventsSink, notification: &SwapEventNotification) -> Result<(), String> {
+    let content = try_s!(json::to_vec(notification));
+    match sink {
+        SwapEventsSink::Webhook { url, retries, retry_interval } => {
+            let mut attempt = 0;
+            loop {
+                match block_on(post_to_webhook(url, content.clone())) {
+                    Ok(_) => return Ok(()),
+                    Err(e) => {
+                        if attempt >= *retries || ctx.is_stopping() { return ERR!("{}", e) }
+                        attempt += 1;
+                        log!("Error " (e) " posting the swap " (notification.uuid) " event, retry " (attempt) " of " (retries));
+                        thread::sleep(Duration::from_secs(*retry_interval));
+                    },
+                }
+            }
+        },
+        SwapEventsSink::UnixSocket { path } => write_to_unix_socket(path, content),
+    }
+}
+
+fn swap_events_sink_loop(ctx: MmArc, sink: SwapEventsSink, receiver: Receiver<SwapEventNotification>) {
+    loop {
+        match receiver.recv_timeout(Duration::from_secs(1)) {
+            Ok(notification) => if let Err(e) = deliver_notification(&ctx, &sink, &notification) {
+                log!("Error " (e) " delivering the swap " (notification.uuid) " event to " [sink]);
+            },
+            Err(RecvTimeoutError::Timeout) => if ctx.is_stopping() { break },
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+/// Returns the sender to the sink thread, starting the thread on the first call
+fn swap_events_sender(ctx: &MmArc) -> Result<Option<Sender<SwapEventNotification>>, String> {
+    let swap_ctx = try_s!(SwapsContext::from_ctx(ctx));
+    let mut sender = try_s!(swap_ctx.events_sender.lock());
+    if sender.is_none() {
+        let sink = match try_s!(SwapEventsSink::from_conf(&ctx.conf)) {
+            Some(sink) => sink,
+            None => return Ok(None),
+        };
+        let (tx, rx) = channel();
+        let ctx = ctx.clone();
+        try_s!(thread::Builder::new().name("swap_events_sink".into()).spawn(move || swap_events_sink_loop(ctx, sink, rx)));
+        *sender = Some(tx);
+    }
+    Ok(sender.clone())
+}
+
+/// Sends my swap event to the configured sink and the registered callback.
+/// The notification failures are logged only, they must not affect the swap.
+pub fn notify_swap_event<E: Serialize>(ctx: &MmArc, uuid: &str, swap_type: &str, timestamp: u64, event: &E) {
+    let event = match json::to_value(event) {
+        Ok(e) => e,
+        Err(e) => {
+            log!("Error " (e) " serializing the swap " (uuid) " event");
+            return;
+        },
+    };
+    let notification = SwapEventNotification {
+        uuid: uuid.to_owned(),
+        swap_type: swap_type.to_owned(),
+        timestamp,
+        event,
+    };
+
+    // copy the callback to release the lock before calling it
+    let cb = *unwrap!(SWAP_EVENTS_CB.lock());
+    if let Some(cb) = cb {
+        match json::to_string(&notification).map(CString::new) {
+            Ok(Ok(line)) => cb(line.as_ptr()),
+            Ok(Err(e)) => log!("Error " (e) " passing the swap " (uuid) " event to the callback"),
+            Err(e) => log!("Error " (e) " passing the swap " (uuid) " event to the callback"),
+        }
+    }
+
+    match swap_events_sender(ctx) {
+        Ok(Some(sender)) => if let Err(e) = sender.send(notification) {
+            log!("Error " (e) " sending the swap " (uuid) " event to the sink");
+        },
+        Ok(None) => (),
+        Err(e) => log!("Error " (e) " getting the swap events sink"),
+    }
+}
+
+#[cfg(test)]
+mod swap_events_tests {
+    use common::mm_ctx::MmCtxBuilder;
+    use std::ffi::CStr;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use super::*;
+
+    fn notification_for_test() -> SwapEventNotification {
+        SwapEventNotification {
+            uuid: "7e9fd2d2-8ae5-4b27-a56b-b8b4e4b6e3e4".into(),
+            swap_type: "Taker".into(),
+            timestamp: 1571221424000,
+            event: json!({"type": "Negotiated", "data": {"maker_payment_locktime": 1571237024}}),
+        }
+    }
+
+    /// The stand-in of the webhook backend, accepts one connection and returns the request body
+    fn accept_http_request(listener: &TcpListener, status_line: &str) -> Vec<u8> {
+        let (mut stream, _) = unwrap!(listener.accept());
+        let mut request = vec![];
+        let mut buf = [0; 1024];
+        let body_start = loop {
+            let n = unwrap!(stream.read(&mut buf));
+            assert!(n > 0, "Connection is closed before the request headers end");
+            request.extend_from_slice(&buf[..n]);
+            if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") { break pos + 4 }
+        };
+        let headers = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+        let content_length: usize = unwrap!(headers.lines()
+            .find(|line| line.starts_with("content-length:"))
+            .and_then(|line| line["content-length:".len()..].trim().parse().ok()));
+        while request.len() < body_start + content_length {
+            let n = unwrap!(stream.read(&mut buf));
+            assert!(n > 0, "Connection is closed before the request body end");
+            request.extend_from_slice(&buf[..n]);
+        }
+        unwrap!(write!(stream, "{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status_line));
+        request[body_start..].to_vec()
+    }
+
+    #[test]
+    fn test_webhook_sink_retries_failed_request() {
+        let listener = unwrap!(TcpListener::bind("127.0.0.1:0"));
+        let url = format!("http://{}/swaps", unwrap!(listener.local_addr()));
+        let server = thread::spawn(move || {
+            let failed = accept_http_request(&listener, "HTTP/1.1 500 Internal Server Error");
+            let delivered = accept_http_request(&listener, "HTTP/1.1 200 OK");
+            (failed, delivered)
+        });
+
+        let ctx = MmCtxBuilder::default().into_mm_arc();
+        let sink = SwapEventsSink::Webhook { url, retries: 1, retry_interval: 0 };
+        let notification = notification_for_test();
+        unwrap!(deliver_notification(&ctx, &sink, &notification));
+
+        let (failed, delivered) = unwrap!(server.join());
+        assert_eq!(failed, delivered);
+        let actual: SwapEventNotification = unwrap!(json::from_slice(&delivered));
+        assert_eq!(notification, actual);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_sink() {
+        use std::io::{BufRead, BufReader};
+        use std::os::unix::net::UnixListener;
+
+        let path = std::env::temp_dir().join("test_unix_socket_sink.sock");
+        let _ = std::fs::remove_file(&path);
+        let listener = unwrap!(UnixListener::bind(&path));
+
+        let ctx = MmCtxBuilder::default().into_mm_arc();
+        let sink = SwapEventsSink::UnixSocket { path: unwrap!(path.to_str()).to_owned() };
+        let notification = notification_for_test();
+        unwrap!(deliver_notification(&ctx, &sink, &notification));
+
+        let (stream, _) = unwrap!(listener.accept());
+        let mut line = String::new();
+        unwrap!(BufReader::new(stream).read_line(&mut line));
+        unwrap!(std::fs::remove_file(&path));
+        let actual: SwapEventNotification = unwrap!(json::from_str(&line));
+        assert_eq!(notification, actual);
+    }
+
+    #[test]
+    fn test_invalid_sink_does_not_break_swaps_context() {
+        let ctx = MmCtxBuilder::new().with_conf(json!({"swap_events_sink": {"type": "Unknown"}})).into_mm_arc();
+        assert!(check_swap_events_sink(&ctx).is_err());
+        assert!(SwapsContext::from_ctx(&ctx).is_ok());
+        assert!(swap_events_sender(&ctx).is_err());
+    }
+
+    #[test]
+    fn test_notify_swap_event_callback() {
+        static CALLED: AtomicBool = AtomicBool::new(false);
+        extern fn cb(event: *const c_char) {
+            let event = unwrap!(unsafe { CStr::from_ptr(event) }.to_str());
+            let notification: SwapEventNotification = unwrap!(json::from_str(event));
+            if notification == notification_for_test() { CALLED.store(true, Ordering::Relaxed) }
+        }
+
+        let ctx = MmCtxBuilder::default().into_mm_arc();
+        let notification = notification_for_test();
+        set_swap_events_callback(Some(cb));
+        notify_swap_event(&ctx, &notification.uuid, &notification.swap_type, notification.timestamp, &notification.event);
+        set_swap_events_callback(None);
+        assert!(CALLED.load(Ordering::Relaxed));
+    }
+}
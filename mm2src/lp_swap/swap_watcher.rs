@@ -0,0 +1,391 @@
+//! The opt-in watchtower completing the taker swaps on behalf of the offline takers.
+//!
+//! The node started with `"swap_watcher": true` accepts the `TakerSwapWatcherData` handed over by the taker
+//! after the taker payment is sent (see the `taker_swap_watcher_data` RPC) and watches the taker payment.
+//! Once the maker spends the taker payment revealing the secret the watcher spends the maker payment,
+//! if the taker payment isn't spent until the locktime the watcher refunds it.
+//!
+//! The spend and the refund are made by the `SwapOps` of the watcher coins, so they're paid to the watcher address.
+//! The maker payment can be spent by the taker key only, hence the watcher has to run with the taker passphrase
+//! and the swaps of other takers (`taker_pub`) are rejected by the `watch_taker_swap` RPC,
+//! even if the pre-signed refund is given: the watcher couldn't claim the maker payment of such a swap.
+//! The refund can also be signed by the taker in advance (`taker_refund`), such a refund is broadcast as is.
+
+use coins::{lp_coinfindᵃ, FoundSwapTxSpend, MmCoinEnum};
+use common::{now_ms, read_dir, slurp, write};
+use common::executor::{spawn, Timer};
+use common::mm_ctx::MmArc;
+use futures::compat::Future01CompatExt;
+use http::Response;
+use primitives::hash::H264;
+use rpc::v1::types::{Bytes as BytesJson, H160 as H160Json, H264 as H264Json};
+use serde_json::{self as json, Value as Json};
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use super::{my_swap_file_path, SavedSwap};
+
+/// The data required to complete the taker swap, it's available after the taker payment is sent
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TakerSwapWatcherData {
+    pub uuid: String,
+    pub maker_coin: String,
+    pub taker_coin: String,
+    pub maker_pub: H264Json,
+    /// The persistent pubkey of the taker, the watcher must run with the same key
+    pub taker_pub: H264Json,
+    pub secret_hash: H160Json,
+    pub maker_payment: BytesJson,
+    pub maker_payment_lock: u64,
+    pub maker_coin_start_block: u64,
+    pub taker_payment: BytesJson,
+    pub taker_payment_lock: u64,
+    pub taker_coin_start_block: u64,
+    /// The taker payment refund signed by the taker in advance,
+    /// the payment is refunded by the `SwapOps` of the watcher if not set
+    #[serde(default)]
+    pub taker_refund: Option<BytesJson>,
+}
+
+/// How the watched swap was completed
+#[derive(Debug, PartialEq)]
+enum WatchedSwapOutcome {
+    /// The maker payment is spent by the watcher, contains the spend tx hash
+    MakerPaymentSpent(String),
+    /// The taker payment is refunded by the watcher, contains the refund tx hash
+    TakerPaymentRefunded(String),
+    /// The swap was completed by the taker itself
+    CompletedByTaker,
+}
+
+fn watched_swaps_dir(ctx: &MmArc) -> PathBuf { ctx.dbdir().join("SWAPS").join("WATCHED") }
+
+fn watched_swap_file_path(ctx: &MmArc, uuid: &str) -> PathBuf {
+    watched_swaps_dir(ctx).join(format!("{}.json", uuid))
+}
+
+fn is_swap_watcher(ctx: &MmArc) -> bool { ctx.conf["swap_watcher"].as_bool().unwrap_or(false) }
+
+/// The interval between the attempts to find the taker payment spend, in seconds
+const SPEND_SEARCH_RETRY_INTERVAL: f64 = 10.;
+/// The failed spend search is retried for 10 minutes, then the swap is left to be retried on restart
+const SPEND_SEARCH_MAX_ATTEMPTS: u32 = 60;
+
+async fn wait_for_coin(ctx: &MmArc, ticker: &str) -> Result<MmCoinEnum, String> {
+    loop {
+        if ctx.is_stopping() { return ERR!("MM is stopping") }
+        if let Some(coin) = try_s!(lp_coinfindᵃ(ctx, ticker).await) {
+            return Ok(coin);
+        }
+        log!("Can't watch the swap until the coin " (ticker) " is activated");
+        Timer::sleep(5.).await;
+    }
+}
+
+/// Spends the maker payment by the secret revealed in the maker spend of the taker payment
+async fn spend_maker_payment(maker_coin: &MmCoinEnum, data: &TakerSwapWatcherData, secret: &[u8])
+                             -> Result<WatchedSwapOutcome, String> {
+    let found = try_s!(maker_coin.search_for_swap_tx_spend_other(
+        data.maker_payment_lock as u32,
+        &data.maker_pub.0,
+        &data.secret_hash.0,
+        &data.maker_payment.0,
+        data.maker_coin_start_block,
+    ));
+    match found {
+        Some(FoundSwapTxSpend::Spent(_)) => return Ok(WatchedSwapOutcome::CompletedByTaker),
+        Some(FoundSwapTxSpend::Refunded(tx)) => return ERR!("The maker payment is already refunded by {:?}", tx.tx_hash()),
+        None => (),
+    }
+    let transaction = try_s!(maker_coin.send_taker_spends_maker_payment(
+        &data.maker_payment.0,
+        data.maker_payment_lock as u32,
+        &data.maker_pub.0,
+        secret,
+    ).compat().await);
+    Ok(WatchedSwapOutcome::MakerPaymentSpent(hex::encode(&transaction.tx_hash().0)))
+}
+
+/// Refunds the taker payment after the locktime unless it's spent meanwhile
+async fn refund_taker_payment(ctx: &MmArc, maker_coin: &MmCoinEnum, taker_coin: &MmCoinEnum, data: &TakerSwapWatcherData)
+                              -> Result<WatchedSwapOutcome, String> {
+    // have to wait for 1 hour more because some coins have BIP113 activated so these will reject transactions with locktime == present time
+    while now_ms() / 1000 <= data.taker_payment_lock + 3700 {
+        if ctx.is_stopping() { return ERR!("MM is stopping") }
+        Timer::sleep(10.).await;
+    }
+    // the maker could have spent the payment at the last moment or the taker could have refunded it itself.
+    // The search errors are retried, taking them for "not spent" would let the maker payment go.
+    let mut attempts = 0;
+    let spend = loop {
+        match taker_coin.search_for_swap_tx_spend_my(
+            data.taker_payment_lock as u32,
+            &data.maker_pub.0,
+            &data.secret_hash.0,
+            &data.taker_payment.0,
+            data.taker_coin_start_block,
+        ) {
+            Ok(spend) => break spend,
+            Err(e) => {
+                attempts += 1;
+                if ctx.is_stopping() || attempts >= SPEND_SEARCH_MAX_ATTEMPTS {
+                    return ERR!("Error {} searching for the taker payment spend after {} attempts", e, attempts);
+                }
+                log!("Error " (e) " searching for the taker payment spend of the watched swap " (data.uuid) ", retrying");
+                Timer::sleep(SPEND_SEARCH_RETRY_INTERVAL).await;
+            },
+        }
+    };
+    match spend {
+        Some(FoundSwapTxSpend::Spent(tx)) => {
+            let secret = try_s!(tx.extract_secret());
+            return spend_maker_payment(maker_coin, data, &secret).await;
+        },
+        Some(FoundSwapTxSpend::Refunded(_)) => return Ok(WatchedSwapOutcome::CompletedByTaker),
+        None => (),
+    }
+
+    let tx_hash = match &data.taker_refund {
+        Some(refund) => try_s!(taker_coin.send_raw_tx(&hex::encode(&refund.0)).compat().await),
+        None => {
+            let transaction = try_s!(taker_coin.send_taker_refunds_payment(
+                &data.taker_payment.0,
+                data.taker_payment_lock as u32,
+                &data.maker_pub.0,
+                &data.secret_hash.0,
+            ).compat().await);
+            hex::encode(&transaction.tx_hash().0)
+        },
+    };
+    Ok(WatchedSwapOutcome::TakerPaymentRefunded(tx_hash))
+}
+
+/// Waits for the maker to spend the taker payment until the locktime and completes the swap accordingly
+async fn watch_taker_swap_payments(ctx: &MmArc, maker_coin: &MmCoinEnum, taker_coin: &MmCoinEnum, data: &TakerSwapWatcherData)
+                                   -> Result<WatchedSwapOutcome, String> {
+    let spend = taker_coin.wait_for_tx_spend(&data.taker_payment.0, data.taker_payment_lock, data.taker_coin_start_block);
+    match spend.compat().await {
+        Ok(tx) => match tx.extract_secret() {
+            Ok(secret) => spend_maker_payment(maker_coin, data, &secret).await,
+            // the payment is spent without the secret, so it's refunded by the taker
+            Err(_) => Ok(WatchedSwapOutcome::CompletedByTaker),
+        },
+        Err(e) => {
+            log!("Taker payment of the watched swap " (data.uuid) " is not spent: " (e));
+            refund_taker_payment(ctx, maker_coin, taker_coin, data).await
+        },
+    }
+}
+
+async fn complete_watched_swap(ctx: &MmArc, data: &TakerSwapWatcherData) -> Result<WatchedSwapOutcome, String> {
+    let maker_coin = try_s!(wait_for_coin(ctx, &data.maker_coin).await);
+    let taker_coin = try_s!(wait_for_coin(ctx, &data.taker_coin).await);
+    watch_taker_swap_payments(ctx, &maker_coin, &taker_coin, data).await
+}
+
+async fn run_swap_watcher(ctx: MmArc, data: TakerSwapWatcherData) {
+    match complete_watched_swap(&ctx, &data).await {
+        Ok(outcome) => {
+            log!("Watched swap " (data.uuid) " is completed: " [outcome]);
+            if let Err(e) = std::fs::remove_file(watched_swap_file_path(&ctx, &data.uuid)) {
+                log!("Error " (e) " removing the watched swap " (data.uuid));
+            }
+        },
+        // the swap is kept to be retried on restart
+        Err(e) => log!("Error watching the swap " (data.uuid) ": " (e)),
+    }
+}
+
+/// Returns the data of my taker swap to be handed over to the watcher node
+pub async fn taker_swap_watcher_data(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    let uuid = try_s!(req["uuid"].as_str().ok_or("uuid parameter is not set or is not string"));
+    let content = try_s!(slurp(&my_swap_file_path(&ctx, uuid)));
+    if content.is_empty() { return ERR!("swap data is not found") }
+    let swap: SavedSwap = try_s!(json::from_slice(&content));
+    let data = match swap {
+        SavedSwap::Taker(swap) => try_s!(swap.watcher_data()),
+        SavedSwap::Maker(_) => return ERR!("Only the taker swaps can be watched"),
+    };
+    let res = try_s!(json::to_vec(&json!({ "result": data })));
+    Ok(try_s!(Response::builder().body(res)))
+}
+
+/// Starts watching the taker swap, the swap is saved to be watched after restart as well.
+/// Only the swaps of my own key are accepted: the `taker_pub` of the `data` must be the persistent pubkey of the watcher,
+/// i.e. the watcher runs with the taker passphrase. The swaps of other takers are rejected even with the `taker_refund`.
+pub async fn watch_taker_swap(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    if !is_swap_watcher(&ctx) { return ERR!("The node is not started with the swap_watcher option") }
+    let mut data: TakerSwapWatcherData = try_s!(json::from_value(req["data"].clone()));
+    // the uuid is a part of the file path
+    let uuid: Uuid = try_s!(data.uuid.parse());
+    data.uuid = uuid.to_string();
+    let my_persistent_pub: H264Json = H264::from(&**ctx.secp256k1_key_pair().public()).into();
+    if data.taker_pub != my_persistent_pub {
+        return ERR!("The swap {} of other taker pubkey can't be watched, the watcher must run with the taker passphrase", uuid);
+    }
+    let path = watched_swap_file_path(&ctx, &data.uuid);
+    if !try_s!(slurp(&path)).is_empty() { return ERR!("The swap {} is already watched", data.uuid) }
+    try_s!(write(&path, &try_s!(json::to_vec(&data))));
+
+    let uuid = data.uuid.clone();
+    spawn(run_swap_watcher(ctx, data));
+    let res = try_s!(json::to_vec(&json!({ "result": { "uuid": uuid } })));
+    Ok(try_s!(Response::builder().body(res)))
+}
+
+/// Resumes watching the saved swaps, returns the tickers of coins that must be enabled for that
+pub fn watched_swaps_kick_start(ctx: &MmArc) -> Result<HashSet<String>, String> {
+    let mut coins = HashSet::new();
+    if !is_swap_watcher(ctx) { return Ok(coins) }
+    for (_, path) in try_s!(read_dir(&watched_swaps_dir(ctx))) {
+        if path.extension() != Some(OsStr::new("json")) { continue }
+        let data: TakerSwapWatcherData = match json::from_slice(&try_s!(slurp(&path))) {
+            Ok(d) => d,
+            Err(e) => {
+                log!("Error " (e) " parsing the watched swap " (path.display()));
+                continue;
+            },
+        };
+        log!("Resuming watching the swap " (data.uuid));
+        coins.insert(data.maker_coin.clone());
+        coins.insert(data.taker_coin.clone());
+        spawn(run_swap_watcher(ctx.clone(), data));
+    }
+    Ok(coins)
+}
+
+#[cfg(test)]
+mod swap_watcher_tests {
+    use coins::{MarketCoinOps, SwapOps, TestCoin};
+    use coins::eth::{signed_eth_tx_from_bytes, SignedEthTx};
+    use coins::utxo::UtxoTx;
+    use common::block_on;
+    use common::mm_ctx::MmCtxBuilder;
+    use common::privkey::key_pair_from_seed;
+    use mocktopus::mocking::*;
+    use super::*;
+
+    fn eth_tx_for_test() -> SignedEthTx {
+        // raw transaction bytes of https://etherscan.io/tx/0x0869be3e5d4456a29d488a533ad6c118620fef450f36778aecf31d356ff8b41f
+        let tx_bytes = [248, 240, 3, 133, 1, 42, 5, 242, 0, 131, 2, 73, 240, 148, 133, 0, 175, 192, 188, 82, 20, 114, 128, 130, 22, 51, 38, 194, 255, 12, 115, 244, 168, 113, 135, 110, 205, 245, 24, 127, 34, 254, 184, 132, 21, 44, 243, 175, 73, 33, 143, 82, 117, 16, 110, 27, 133, 82, 200, 114, 233, 42, 140, 198, 35, 21, 201, 249, 187, 180, 20, 46, 148, 40, 9, 228, 193, 130, 71, 199, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 152, 41, 132, 9, 201, 73, 19, 94, 237, 137, 35, 61, 4, 194, 207, 239, 152, 75, 175, 245, 157, 174, 10, 214, 161, 207, 67, 70, 87, 246, 231, 212, 47, 216, 119, 68, 237, 197, 125, 141, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 93, 72, 125, 102, 28, 159, 180, 237, 198, 97, 87, 80, 82, 200, 104, 40, 245, 221, 7, 28, 122, 104, 91, 99, 1, 159, 140, 25, 131, 101, 74, 87, 50, 168, 146, 187, 90, 160, 51, 1, 123, 247, 6, 108, 165, 181, 188, 40, 56, 47, 211, 229, 221, 73, 5, 15, 89, 81, 117, 225, 216, 108, 98, 226, 119, 232, 94, 184, 42, 106];
+        unwrap!(signed_eth_tx_from_bytes(&tx_bytes))
+    }
+
+    fn watcher_data_for_test(taker_refund: Option<BytesJson>) -> TakerSwapWatcherData {
+        TakerSwapWatcherData {
+            uuid: "41383f43-46a5-478c-9386-3b2cce0aca20".into(),
+            maker_coin: "MAKER".into(),
+            taker_coin: "TAKER".into(),
+            maker_pub: H264Json::default(),
+            taker_pub: H264Json::default(),
+            secret_hash: H160Json::default(),
+            maker_payment: vec![1].into(),
+            maker_payment_lock: 1564066080,
+            maker_coin_start_block: 1458035,
+            taker_payment: vec![2].into(),
+            // the locktime is expired long ago
+            taker_payment_lock: 1564058279,
+            taker_coin_start_block: 2823448,
+            taker_refund,
+        }
+    }
+
+    #[test]
+    fn test_watch_taker_swap_refunds_by_own_keys() {
+        TestCoin::wait_for_tx_spend.mock_safe(|_, _, _, _| MockResult::Return(Box::new(futures01::future::err("Waited too long".into()))));
+        TestCoin::search_for_swap_tx_spend_my.mock_safe(|_, _, _, _, _, _| MockResult::Return(Ok(None)));
+        static mut REFUND_CALLED: bool = false;
+        TestCoin::send_taker_refunds_payment.mock_safe(|_, _, _, _, _| {
+            unsafe { REFUND_CALLED = true };
+            MockResult::Return(Box::new(futures01::future::ok(eth_tx_for_test().into())))
+        });
+        let ctx = MmCtxBuilder::default().into_mm_arc();
+        let maker_coin = MmCoinEnum::Test(TestCoin {});
+        let taker_coin = MmCoinEnum::Test(TestCoin {});
+
+        let data = watcher_data_for_test(None);
+        let actual = unwrap!(block_on(watch_taker_swap_payments(&ctx, &maker_coin, &taker_coin, &data)));
+        let expected = WatchedSwapOutcome::TakerPaymentRefunded(hex::encode(&eth_tx_for_test().tx_hash().0));
+        assert_eq!(expected, actual);
+        assert!(unsafe { REFUND_CALLED });
+    }
+
+    #[test]
+    fn test_watch_taker_swap_broadcasts_signed_refund() {
+        TestCoin::wait_for_tx_spend.mock_safe(|_, _, _, _| MockResult::Return(Box::new(futures01::future::err("Waited too long".into()))));
+        TestCoin::search_for_swap_tx_spend_my.mock_safe(|_, _, _, _, _, _| MockResult::Return(Ok(None)));
+        TestCoin::send_taker_refunds_payment.mock_safe(|_, _, _, _, _| panic!("The signed refund must be broadcast as is"));
+        TestCoin::send_raw_tx.mock_safe(|_, tx| {
+            assert_eq!("0102", tx);
+            MockResult::Return(Box::new(futures01::future::ok("refund_hash".into())))
+        });
+        let ctx = MmCtxBuilder::default().into_mm_arc();
+        let maker_coin = MmCoinEnum::Test(TestCoin {});
+        let taker_coin = MmCoinEnum::Test(TestCoin {});
+
+        let data = watcher_data_for_test(Some(vec![1, 2].into()));
+        let actual = unwrap!(block_on(watch_taker_swap_payments(&ctx, &maker_coin, &taker_coin, &data)));
+        assert_eq!(WatchedSwapOutcome::TakerPaymentRefunded("refund_hash".into()), actual);
+    }
+
+    #[test]
+    fn test_watch_taker_swap_spends_maker_payment_by_secret_found_after_locktime() {
+        TestCoin::wait_for_tx_spend.mock_safe(|_, _, _, _| MockResult::Return(Box::new(futures01::future::err("Waited too long".into()))));
+        // the maker spent the taker payment revealing the secret after the wait_for_tx_spend timed out
+        TestCoin::search_for_swap_tx_spend_my.mock_safe(|_, _, _, _, _, _| {
+            let tx: UtxoTx = "0100000001de7aa8d29524906b2b54ee2e0281f3607f75662cbc9080df81d1047b78e21dbc00000000d7473044022079b6c50820040b1fbbe9251ced32ab334d33830f6f8d0bf0a40c7f1336b67d5b0220142ccf723ddabb34e542ed65c395abc1fbf5b6c3e730396f15d25c49b668a1a401209da937e5609680cb30bff4a7661364ca1d1851c2506fa80c443f00a3d3bf7365004c6b6304f62b0e5cb175210270e75970bb20029b3879ec76c4acd320a8d0589e003636264d01a7d566504bfbac6782012088a9142fb610d856c19fd57f2d0cffe8dff689074b3d8a882103f368228456c940ac113e53dad5c104cf209f2f102a409207269383b6ab9b03deac68ffffffff01d0dc9800000000001976a9146d9d2b554d768232320587df75c4338ecc8bf37d88ac40280e5c".into();
+            MockResult::Return(Ok(Some(FoundSwapTxSpend::Spent(tx.into()))))
+        });
+        TestCoin::search_for_swap_tx_spend_other.mock_safe(|_, _, _, _, _, _| MockResult::Return(Ok(None)));
+        TestCoin::send_taker_refunds_payment.mock_safe(|_, _, _, _, _| panic!("The spent taker payment must not be refunded"));
+        static mut SPENT_BY_SECRET: Option<Vec<u8>> = None;
+        TestCoin::send_taker_spends_maker_payment.mock_safe(|_, _, _, _, secret| {
+            unsafe { SPENT_BY_SECRET = Some(secret.to_vec()) };
+            MockResult::Return(Box::new(futures01::future::ok(eth_tx_for_test().into())))
+        });
+        let ctx = MmCtxBuilder::default().into_mm_arc();
+        let maker_coin = MmCoinEnum::Test(TestCoin {});
+        let taker_coin = MmCoinEnum::Test(TestCoin {});
+
+        let data = watcher_data_for_test(None);
+        let actual = unwrap!(block_on(watch_taker_swap_payments(&ctx, &maker_coin, &taker_coin, &data)));
+        let expected = WatchedSwapOutcome::MakerPaymentSpent(hex::encode(&eth_tx_for_test().tx_hash().0));
+        assert_eq!(expected, actual);
+        let expected_secret = unwrap!(hex::decode("9da937e5609680cb30bff4a7661364ca1d1851c2506fa80c443f00a3d3bf7365"));
+        assert_eq!(Some(expected_secret), unsafe { SPENT_BY_SECRET.clone() });
+    }
+
+    #[test]
+    fn test_watch_taker_swap_stops_retrying_spend_search() {
+        TestCoin::wait_for_tx_spend.mock_safe(|_, _, _, _| MockResult::Return(Box::new(futures01::future::err("Waited too long".into()))));
+        TestCoin::search_for_swap_tx_spend_my.mock_safe(|_, _, _, _, _, _| MockResult::Return(Err("Electrum is down".into())));
+        TestCoin::send_taker_refunds_payment.mock_safe(|_, _, _, _, _| panic!("The payment must not be refunded if its spend is not checked"));
+        let ctx = MmCtxBuilder::default().into_mm_arc();
+        ctx.stop();
+        let maker_coin = MmCoinEnum::Test(TestCoin {});
+        let taker_coin = MmCoinEnum::Test(TestCoin {});
+
+        let data = watcher_data_for_test(None);
+        assert!(block_on(watch_taker_swap_payments(&ctx, &maker_coin, &taker_coin, &data)).is_err());
+    }
+
+    #[test]
+    fn test_watch_taker_swap_rejects_other_taker_pub() {
+        let key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+        let ctx = MmCtxBuilder::new().with_conf(json!({"swap_watcher": true})).with_secp256k1_key_pair(key_pair).into_mm_arc();
+        // the swap of other taker is rejected even with the pre-signed refund
+        let data = unwrap!(json::to_value(watcher_data_for_test(Some(vec![1, 2].into()))));
+        let err = unwrap!(block_on(watch_taker_swap(ctx, json!({"data": data}))).err());
+        assert!(err.contains("other taker pubkey"), "{}", err);
+    }
+
+    #[test]
+    fn test_watch_taker_swap_rejects_invalid_uuid() {
+        let ctx = MmCtxBuilder::new().with_conf(json!({"swap_watcher": true})).into_mm_arc();
+        let mut data = unwrap!(json::to_value(watcher_data_for_test(None)));
+        data["uuid"] = "../../../MY/41383f43-46a5-478c-9386-3b2cce0aca20".into();
+        assert!(block_on(watch_taker_swap(ctx, json!({"data": data}))).is_err());
+    }
+}
@@ -39,6 +39,14 @@ use std::str;
 pub mod crash_reports;
 use self::crash_reports::init_crash_reports;
 
+#[path = "lp_autoprice.rs"]
+pub mod lp_autoprice;
+
+// the `portfolio` crate is disabled, the autoprice shares the C-free price providers only
+#[allow(dead_code)]
+#[path = "portfolio/price_providers.rs"]
+pub mod price_providers;
+
 #[path = "lp_native_dex.rs"]
 mod lp_native_dex;
 use self::lp_native_dex::{lp_init, lp_ports};
@@ -109,6 +109,17 @@ pub extern fn mm2_main_status() -> i8 {
     } else {0}
 }
 
+/// Registers the callback receiving the JSON notifications of my swaps events,
+/// `{"uuid": …, "swap_type": "Maker" | "Taker", "timestamp": …, "event": {"type": …, "data": …}}`.
+/// The notification string is only valid during the callback invocation.
+/// Passing the null callback unregisters it.
+/// The callback is process-global: it replaces the previously registered one and receives the events of every MM2 instance in the process.
+#[no_mangle]
+#[cfg(feature = "native")]
+pub extern fn mm2_swap_events_cb (cb: Option<extern fn (event: *const c_char)>) {
+    mm2::lp_swap::set_swap_events_callback (cb)
+}
+
 #[no_mangle]
 #[cfg(feature = "native")]
 pub extern fn mm2_test (torch: i32, log_cb: extern fn (line: *const c_char)) -> i32 {
@@ -1,4 +1,6 @@
 use common::mm_ctx::{MmArc, MmCtxBuilder};
+use common::privkey::key_pair_from_seed;
+use futures01::Future;
 use mocktopus::mocking::*;
 use super::*;
 
@@ -16,6 +18,7 @@ fn test_match_maker_order_and_taker_request() {
         price_rat: BigRational::from_integer(1.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
         uuid: Uuid::new_v4(),
     };
 
@@ -31,6 +34,7 @@ fn test_match_maker_order_and_taker_request() {
         rel_amount: 20.into(),
         rel_amount_rat: Some(BigRational::from_integer(20.into())),
         action: TakerAction::Buy,
+        allow_partial_fill: false,
     };
 
     let actual = match_order_and_request(&maker, &request);
@@ -49,6 +53,7 @@ fn test_match_maker_order_and_taker_request() {
         price_rat: BigRational::new(1.into(), 2.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
         uuid: Uuid::new_v4(),
     };
 
@@ -64,6 +69,7 @@ fn test_match_maker_order_and_taker_request() {
         rel_amount: 20.into(),
         rel_amount_rat: Some(BigRational::from_integer(20.into())),
         action: TakerAction::Buy,
+        allow_partial_fill: false,
     };
 
     let actual = match_order_and_request(&maker, &request);
@@ -82,6 +88,7 @@ fn test_match_maker_order_and_taker_request() {
         price_rat: BigRational::new(1.into(), 2.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
         uuid: Uuid::new_v4(),
     };
 
@@ -97,6 +104,7 @@ fn test_match_maker_order_and_taker_request() {
         rel_amount: 2.into(),
         rel_amount_rat: Some(BigRational::from_integer(2.into())),
         action: TakerAction::Buy,
+        allow_partial_fill: false,
     };
 
     let actual = match_order_and_request(&maker, &request);
@@ -115,6 +123,7 @@ fn test_match_maker_order_and_taker_request() {
         price_rat: BigRational::new(1.into(), 2.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
         uuid: Uuid::new_v4(),
     };
 
@@ -130,6 +139,7 @@ fn test_match_maker_order_and_taker_request() {
         rel_amount: 10.into(),
         rel_amount_rat: Some(BigRational::from_integer(10.into())),
         action: TakerAction::Sell,
+        allow_partial_fill: false,
     };
 
     let actual = match_order_and_request(&maker, &request);
@@ -148,6 +158,7 @@ fn test_match_maker_order_and_taker_request() {
         price_rat: BigRational::new(1.into(), 2.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
         uuid: Uuid::new_v4(),
     };
 
@@ -163,6 +174,7 @@ fn test_match_maker_order_and_taker_request() {
         rel_amount: 10.into(),
         rel_amount_rat: Some(BigRational::from_integer(10.into())),
         action: TakerAction::Sell,
+        allow_partial_fill: false,
     };
 
     let actual = match_order_and_request(&maker, &request);
@@ -181,6 +193,7 @@ fn test_match_maker_order_and_taker_request() {
         price_rat: BigRational::from_integer(1.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
         uuid: Uuid::new_v4(),
     };
 
@@ -196,6 +209,7 @@ fn test_match_maker_order_and_taker_request() {
         rel_amount: "0.9".parse().unwrap(),
         rel_amount_rat: Some(BigRational::new(9.into(), 10.into())),
         action: TakerAction::Sell,
+        allow_partial_fill: false,
     };
 
     let actual = match_order_and_request(&maker, &request);
@@ -217,6 +231,7 @@ fn test_maker_order_available_amount() {
         price_rat: BigRational::from_integer(1.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
         uuid: Uuid::new_v4(),
     };
     maker.matches.insert(Uuid::new_v4(), MakerMatch {
@@ -232,6 +247,7 @@ fn test_maker_order_available_amount() {
             dest_pub_key: H256Json::default(),
             method: "request".into(),
             action: TakerAction::Buy,
+            allow_partial_fill: false,
         },
         reserved: MakerReserved {
             method: "reserved".into(),
@@ -263,6 +279,7 @@ fn test_maker_order_available_amount() {
             dest_pub_key: H256Json::default(),
             method: "request".into(),
             action: TakerAction::Buy,
+            allow_partial_fill: false,
         },
         reserved: MakerReserved {
             method: "reserved".into(),
@@ -287,6 +304,92 @@ fn test_maker_order_available_amount() {
     assert_eq!(MmNumber::from(expected), actual);
 }
 
+#[test]
+fn test_maker_order_apply_update() {
+    let uuid = Uuid::new_v4();
+    let mut maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
+        uuid,
+    };
+    maker.matches.insert(Uuid::new_v4(), MakerMatch {
+        request: TakerRequest {
+            uuid: Uuid::new_v4(),
+            base: "BASE".into(),
+            rel: "REL".into(),
+            base_amount: 3.into(),
+            base_amount_rat: Some(BigRational::from_integer(3.into())),
+            rel_amount: 3.into(),
+            rel_amount_rat: Some(BigRational::from_integer(3.into())),
+            sender_pubkey: H256Json::default(),
+            dest_pub_key: H256Json::default(),
+            method: "request".into(),
+            action: TakerAction::Buy,
+            allow_partial_fill: false,
+        },
+        reserved: MakerReserved {
+            method: "reserved".into(),
+            base: "BASE".into(),
+            rel: "REL".into(),
+            base_amount: 3.into(),
+            base_amount_rat: Some(BigRational::from_integer(3.into())),
+            rel_amount: 3.into(),
+            rel_amount_rat: Some(BigRational::from_integer(3.into())),
+            sender_pubkey: H256Json::default(),
+            dest_pub_key: H256Json::default(),
+            maker_order_uuid: uuid,
+            taker_order_uuid: Uuid::new_v4(),
+        },
+        connect: None,
+        connected: None,
+        last_updated: now_ms(),
+    });
+
+    // the volume can't be lower than reserved amount
+    let update = MakerOrderUpdate {
+        uuid,
+        price: None,
+        volume: Some(BigRational::from_integer(2.into()).into()),
+        min_volume: None,
+    };
+    unwrap_err!(maker.apply_update(&update));
+    assert_eq!(BigRational::from_integer(10.into()), maker.max_base_vol_rat);
+
+    // min volume can't be larger than max volume
+    let update = MakerOrderUpdate {
+        uuid,
+        price: None,
+        volume: Some(BigRational::from_integer(5.into()).into()),
+        min_volume: Some(BigRational::from_integer(6.into()).into()),
+    };
+    unwrap_err!(maker.apply_update(&update));
+
+    let update = MakerOrderUpdate {
+        uuid,
+        price: Some(BigRational::from_integer(2.into()).into()),
+        volume: Some(BigRational::from_integer(5.into()).into()),
+        min_volume: Some(BigRational::from_integer(1.into()).into()),
+    };
+    unwrap!(maker.apply_update(&update));
+    assert_eq!(uuid, maker.uuid);
+    assert_eq!(BigRational::from_integer(2.into()), maker.price_rat);
+    assert_eq!(BigDecimal::from(2), maker.price);
+    assert_eq!(BigRational::from_integer(5.into()), maker.max_base_vol_rat);
+    assert_eq!(BigRational::from_integer(1.into()), maker.min_base_vol_rat);
+    assert_eq!(MmNumber::from(BigRational::from_integer(2.into())), maker.available_amount());
+    assert_eq!(1, maker.matches.len());
+}
+
 #[test]
 fn test_taker_match_reserved() {
     let uuid = Uuid::new_v4();
@@ -303,12 +406,17 @@ fn test_taker_match_reserved() {
         rel_amount: 10.into(),
         rel_amount_rat: Some(BigRational::from_integer(10.into())),
         action: TakerAction::Buy,
+        allow_partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
-        created_at: now_ms()
+        created_at: now_ms(),
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
     };
 
     let reserved = MakerReserved {
@@ -340,12 +448,17 @@ fn test_taker_match_reserved() {
         rel_amount: 10.into(),
         rel_amount_rat: Some(BigRational::from_integer(10.into())),
         action: TakerAction::Sell,
+        allow_partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
-        created_at: now_ms()
+        created_at: now_ms(),
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
     };
 
     let reserved = MakerReserved {
@@ -376,12 +489,17 @@ fn test_taker_match_reserved() {
         rel_amount: "0.9".parse().unwrap(),
         rel_amount_rat: Some(BigRational::new(9.into(), 10.into())),
         action: TakerAction::Sell,
+        allow_partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
-        created_at: now_ms()
+        created_at: now_ms(),
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
     };
 
     let reserved = MakerReserved {
@@ -412,12 +530,17 @@ fn test_taker_match_reserved() {
         rel_amount: "0.9".parse().unwrap(),
         rel_amount_rat: Some(BigRational::new(9.into(), 10.into())),
         action: TakerAction::Sell,
+        allow_partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
-        created_at: now_ms()
+        created_at: now_ms(),
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
     };
 
     let reserved = MakerReserved {
@@ -448,12 +571,17 @@ fn test_taker_match_reserved() {
         rel_amount: 2.into(),
         rel_amount_rat: Some(BigRational::from_integer(2.into())),
         action: TakerAction::Buy,
+        allow_partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
-        created_at: now_ms()
+        created_at: now_ms(),
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
     };
 
     let reserved = MakerReserved {
@@ -484,12 +612,17 @@ fn test_taker_match_reserved() {
         rel_amount: 2.into(),
         rel_amount_rat: None,
         action: TakerAction::Buy,
+        allow_partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
-        created_at: now_ms()
+        created_at: now_ms(),
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
     };
 
     let reserved = MakerReserved {
@@ -520,12 +653,17 @@ fn test_taker_match_reserved() {
         rel_amount: 2.into(),
         rel_amount_rat: Some(BigRational::from_integer(2.into())),
         action: TakerAction::Buy,
+        allow_partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
-        created_at: now_ms()
+        created_at: now_ms(),
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
     };
 
     let reserved = MakerReserved {
@@ -556,12 +694,17 @@ fn test_taker_match_reserved() {
         rel_amount: 2.into(),
         rel_amount_rat: Some(BigRational::from_integer(2.into())),
         action: TakerAction::Buy,
+        allow_partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
-        created_at: now_ms()
+        created_at: now_ms(),
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
     };
 
     let reserved = MakerReserved {
@@ -590,12 +733,17 @@ fn test_taker_match_reserved() {
             rel_amount: 1.into(),
             rel_amount_rat: Some(BigRational::from_integer(1.into())),
             action: TakerAction::Buy,
+            allow_partial_fill: false,
             uuid,
             method: "request".into(),
             sender_pubkey: H256Json::default(),
             dest_pub_key: H256Json::default(),
         },
         matches: HashMap::new(),
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
     };
 
     let reserved = MakerReserved {
@@ -629,12 +777,17 @@ fn test_taker_order_cancellable() {
         rel_amount: 2.into(),
         rel_amount_rat: Some(BigRational::from_integer(2.into())),
         action: TakerAction::Buy,
+        allow_partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
-        created_at: now_ms()
+        created_at: now_ms(),
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
     };
 
     assert!(order.is_cancellable());
@@ -651,12 +804,17 @@ fn test_taker_order_cancellable() {
         rel_amount: 2.into(),
         rel_amount_rat: Some(BigRational::from_integer(2.into())),
         action: TakerAction::Buy,
+        allow_partial_fill: false,
     };
 
     let mut order = TakerOrder {
         request,
         matches: HashMap::new(),
-        created_at: now_ms()
+        created_at: now_ms(),
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
     };
 
     order.matches.insert(
@@ -682,6 +840,7 @@ fn test_taker_order_cancellable() {
                 dest_pub_key: H256Json::default(),
                 maker_order_uuid: Uuid::new_v4(),
                 taker_order_uuid: Uuid::new_v4(),
+                swap_uuid: None,
             },
             connected: None,
         }
@@ -708,6 +867,7 @@ fn prepare_for_cancel_by(ctx: &MmArc) {
         price: 0.into(),
         price_rat: BigRational::from_integer(0.into()),
         started_swaps: vec![],
+        strategy: MakerStrategy::FixedPrice,
     });
     maker_orders.insert(Uuid::from_bytes([1; 16]), MakerOrder {
         uuid: Uuid::from_bytes([1; 16]),
@@ -722,6 +882,7 @@ fn prepare_for_cancel_by(ctx: &MmArc) {
         price: 0.into(),
         price_rat: BigRational::from_integer(0.into()),
         started_swaps: vec![],
+        strategy: MakerStrategy::FixedPrice,
     });
     maker_orders.insert(Uuid::from_bytes([2; 16]), MakerOrder {
         uuid: Uuid::from_bytes([2; 16]),
@@ -736,6 +897,7 @@ fn prepare_for_cancel_by(ctx: &MmArc) {
         price: 0.into(),
         price_rat: BigRational::from_integer(0.into()),
         started_swaps: vec![],
+        strategy: MakerStrategy::FixedPrice,
     });
     taker_orders.insert(Uuid::from_bytes([3; 16]), TakerOrder {
         matches: HashMap::new(),
@@ -745,6 +907,7 @@ fn prepare_for_cancel_by(ctx: &MmArc) {
             rel: "BEER".into(),
             uuid: Uuid::from_bytes([3; 16]),
             action: TakerAction::Buy,
+            allow_partial_fill: false,
             base_amount: 0.into(),
             base_amount_rat: Some(BigRational::from_integer(0.into())),
             rel_amount: 0.into(),
@@ -752,7 +915,11 @@ fn prepare_for_cancel_by(ctx: &MmArc) {
             dest_pub_key: H256Json::default(),
             method: "request".into(),
             sender_pubkey: H256Json::default(),
-        }
+        },
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
     });
 }
 
@@ -761,6 +928,12 @@ fn test_cancel_by_single_coin() {
     let ctx = MmCtxBuilder::default().into_mm_arc();
     prepare_for_cancel_by(&ctx);
 
+    add_maker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    add_taker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
     delete_my_maker_order.mock_safe(|_, _| {
         MockResult::Return(())
     });
@@ -780,6 +953,12 @@ fn test_cancel_by_pair() {
     let ctx = MmCtxBuilder::default().into_mm_arc();
     prepare_for_cancel_by(&ctx);
 
+    add_maker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    add_taker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
     delete_my_maker_order.mock_safe(|_, _| {
         MockResult::Return(())
     });
@@ -802,6 +981,12 @@ fn test_cancel_by_all() {
     let ctx = MmCtxBuilder::default().into_mm_arc();
     prepare_for_cancel_by(&ctx);
 
+    add_maker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    add_taker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
     delete_my_maker_order.mock_safe(|_, _| {
         MockResult::Return(())
     });
@@ -815,3 +1000,1676 @@ fn test_cancel_by_all() {
     assert!(cancelled.contains(&Uuid::from_bytes([2; 16])));
     assert!(cancelled.contains(&Uuid::from_bytes([3; 16])));
 }
+
+#[test]
+fn test_price_ping_min_volume() {
+    let key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+    let mut maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
+        uuid: Uuid::new_v4(),
+    };
+
+    // the ping of order without min volume must not contain the field to be verifiable by older nodes
+    let ping = unwrap!(PricePingRequest::new(&ctx, &maker, 10.into()));
+    let ping_json = unwrap!(json::to_value(&ping));
+    assert!(ping_json.get("min_volume").is_none());
+    unwrap!(lp_post_price_recv(&ctx, ping_json).wait());
+
+    maker.min_base_vol = 2.into();
+    maker.min_base_vol_rat = BigRational::from_integer(2.into());
+    let ping = unwrap!(PricePingRequest::new(&ctx, &maker, 10.into()));
+    let mut ping_json = unwrap!(json::to_value(&ping));
+    unwrap!(lp_post_price_recv(&ctx, ping_json.clone()).wait());
+
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
+    let min_volume = unwrap!(ordermatch_ctx.orderbook.lock())[&("BASE".to_owned(), "REL".to_owned())][&maker.uuid].get_min_volume();
+    assert_eq!(MmNumber::from(BigRational::from_integer(2.into())), min_volume);
+
+    // min volume is signed so it can't be changed by anyone else
+    ping_json["min_volume"] = "1".into();
+    unwrap_err!(lp_post_price_recv(&ctx, ping_json).wait());
+}
+
+#[test]
+fn test_is_maker_min_volume_met() {
+    let ctx = MmCtxBuilder::default().into_mm_arc();
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
+    let maker_order_uuid = Uuid::new_v4();
+    let mut reserved = MakerReserved {
+        method: "reserved".into(),
+        base: "BASE".into(),
+        rel: "REL".into(),
+        base_amount: 1.into(),
+        base_amount_rat: Some(BigRational::from_integer(1.into())),
+        rel_amount: 1.into(),
+        rel_amount_rat: Some(BigRational::from_integer(1.into())),
+        sender_pubkey: H256Json::default(),
+        dest_pub_key: H256Json::default(),
+        maker_order_uuid,
+        taker_order_uuid: Uuid::new_v4(),
+    };
+    // the order is not known yet
+    assert!(is_maker_min_volume_met(&ordermatch_ctx, &reserved));
+
+    let mut pair_orders = HashMap::new();
+    pair_orders.insert(maker_order_uuid, PricePingRequest {
+        method: "postprice".into(),
+        pubkey: "".into(),
+        base: "BASE".into(),
+        rel: "REL".into(),
+        price: 1.into(),
+        price_rat: Some(BigRational::from_integer(1.into())),
+        price64: "100000000".into(),
+        timestamp: now_ms() / 1000,
+        pubsecp: "".into(),
+        sig: "".into(),
+        balance: 10.into(),
+        balance_rat: Some(BigRational::from_integer(10.into())),
+        uuid: Some(maker_order_uuid),
+        min_volume: Some(2.into()),
+        min_volume_rat: Some(BigRational::from_integer(2.into())),
+    });
+    unwrap!(ordermatch_ctx.orderbook.lock()).insert(("BASE".into(), "REL".into()), pair_orders);
+    assert!(!is_maker_min_volume_met(&ordermatch_ctx, &reserved));
+
+    reserved.base_amount = 2.into();
+    reserved.base_amount_rat = Some(BigRational::from_integer(2.into()));
+    assert!(is_maker_min_volume_met(&ordermatch_ctx, &reserved));
+}
+
+#[test]
+fn test_price_ping_min_volume_prefers_signed_value() {
+    let mut ping = price_ping_for_test("BASE", "REL", BigRational::from_integer(1.into()));
+    assert_eq!(MmNumber::default(), ping.get_min_volume());
+    // the unsigned min_volume_rat can be altered by anyone relaying the ping
+    ping.min_volume_rat = Some(BigRational::from_integer(5.into()));
+    assert_eq!(MmNumber::default(), ping.get_min_volume());
+    ping.min_volume = Some(2.into());
+    assert_eq!(MmNumber::from(2), ping.get_min_volume());
+}
+
+#[test]
+fn test_check_dest_maker_min_volume() {
+    let ctx = MmCtxBuilder::default().into_mm_arc();
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
+    let maker_pubkey = H256Json::from([1; 32]);
+    {
+        let mut orderbook = unwrap!(ordermatch_ctx.orderbook.lock());
+        let mut asks = HashMap::new();
+        let mut ask = price_ping_for_test("BASE", "REL", BigRational::from_integer(2.into()));
+        ask.pubkey = hex::encode(&maker_pubkey.0);
+        ask.min_volume = Some(3.into());
+        asks.insert(new_uuid(), ask);
+        orderbook.insert(("BASE".into(), "REL".into()), asks);
+    }
+
+    // the request volume is lower than the min volume of the only maker order
+    let mut request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 2, 4);
+    request.dest_pub_key = maker_pubkey.clone();
+    assert!(check_dest_maker_min_volume(&ordermatch_ctx, &request).is_err());
+
+    let mut request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 3, 6);
+    request.dest_pub_key = maker_pubkey.clone();
+    unwrap!(check_dest_maker_min_volume(&ordermatch_ctx, &request));
+
+    // the maker sells BASE, so the volume of our sell request is in REL
+    let mut request = taker_request_for_test(TakerAction::Sell, "REL", "BASE", 4, 2);
+    request.dest_pub_key = maker_pubkey.clone();
+    assert!(check_dest_maker_min_volume(&ordermatch_ctx, &request).is_err());
+
+    // the broadcast requests and the makers unknown from the orderbook are not checked
+    let request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 2, 4);
+    unwrap!(check_dest_maker_min_volume(&ordermatch_ctx, &request));
+    let mut request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 2, 4);
+    request.dest_pub_key = H256Json::from([2; 32]);
+    unwrap!(check_dest_maker_min_volume(&ordermatch_ctx, &request));
+}
+
+fn timed_out_taker_order(uuid: Uuid, order_type: OrderType) -> TakerOrder {
+    TakerOrder {
+        matches: HashMap::new(),
+        created_at: now_ms() - ORDERMATCH_TIMEOUT - 1000,
+        request: TakerRequest {
+            base: "BASE".into(),
+            rel: "REL".into(),
+            uuid,
+            action: TakerAction::Buy,
+            allow_partial_fill: false,
+            base_amount: 1.into(),
+            base_amount_rat: Some(BigRational::from_integer(1.into())),
+            rel_amount: 1.into(),
+            rel_amount_rat: Some(BigRational::from_integer(1.into())),
+            dest_pub_key: H256Json::default(),
+            method: "request".into(),
+            sender_pubkey: H256Json::default(),
+        },
+        order_type,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
+    }
+}
+
+#[test]
+fn test_handle_timed_out_taker_orders() {
+    let ctx = MmCtxBuilder::default().into_mm_arc();
+
+    add_maker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    add_taker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    delete_my_taker_order.mock_safe(|_, _| {
+        MockResult::Return(())
+    });
+    save_my_maker_order.mock_safe(|_, _| {
+        MockResult::Return(())
+    });
+
+    let gtc_uuid = Uuid::from_bytes([0; 16]);
+    let fok_uuid = Uuid::from_bytes([1; 16]);
+    let ioc_uuid = Uuid::from_bytes([2; 16]);
+    let active_uuid = Uuid::from_bytes([3; 16]);
+
+    let mut taker_orders = HashMap::new();
+    taker_orders.insert(gtc_uuid, timed_out_taker_order(gtc_uuid, OrderType::GoodTillCancelled));
+    taker_orders.insert(fok_uuid, timed_out_taker_order(fok_uuid, OrderType::FillOrKill));
+    taker_orders.insert(ioc_uuid, timed_out_taker_order(ioc_uuid, OrderType::ImmediateOrCancel));
+    let mut active_order = timed_out_taker_order(active_uuid, OrderType::FillOrKill);
+    active_order.created_at = now_ms();
+    taker_orders.insert(active_uuid, active_order);
+
+    let mut maker_orders = HashMap::new();
+    handle_timed_out_taker_orders(&ctx, &mut taker_orders, &mut maker_orders);
+
+    // only the order that is not timed out yet is kept
+    assert_eq!(1, taker_orders.len());
+    assert!(taker_orders.contains_key(&active_uuid));
+    // only GoodTillCancelled order is converted to maker
+    assert_eq!(1, maker_orders.len());
+    assert!(maker_orders.contains_key(&gtc_uuid));
+}
+
+#[test]
+fn test_deserialize_taker_order_without_order_type() {
+    let order_json = r#"{"created_at":1568358064115,"request":{"base":"RICK","rel":"MORTY","base_amount":"1","base_amount_rat":null,"rel_amount":"1","rel_amount_rat":null,"action":"Buy","uuid":"3447b727-fe93-4357-8e5a-8cf2699b7e86","method":"request","sender_pubkey":"0000000000000000000000000000000000000000000000000000000000000000","dest_pub_key":"0000000000000000000000000000000000000000000000000000000000000000"},"matches":{}}"#;
+    let order: TakerOrder = unwrap!(json::from_str(order_json));
+    assert_eq!(OrderType::GoodTillCancelled, order.order_type);
+}
+
+#[test]
+fn test_order_status_reports_expired_taker_order() {
+    let dbdir = std::env::temp_dir().join("test_order_status_reports_expired_taker_order");
+    let ctx = MmCtxBuilder::new().with_conf(json!({"dbdir": unwrap!(dbdir.to_str())})).into_mm_arc();
+    let _ = std::fs::remove_dir_all(&dbdir);
+    unwrap!(std::fs::create_dir_all(my_orders_history_dir(&ctx)));
+    delete_my_taker_order.mock_safe(|_, _| MockResult::Return(()));
+
+    let fok_uuid = Uuid::from_bytes([1; 16]);
+    let mut taker_orders = HashMap::new();
+    taker_orders.insert(fok_uuid, timed_out_taker_order(fok_uuid, OrderType::FillOrKill));
+    handle_timed_out_taker_orders(&ctx, &mut taker_orders, &mut HashMap::new());
+    assert!(taker_orders.is_empty());
+
+    let response = unwrap!(order_status(ctx.clone(), json!({"uuid": fok_uuid})).wait());
+    let unknown = unwrap!(order_status(ctx.clone(), json!({"uuid": Uuid::from_bytes([2; 16])})).wait());
+    unwrap!(std::fs::remove_dir_all(&dbdir));
+    assert!(response.status().is_success());
+    let status: Json = unwrap!(json::from_slice(response.body()));
+    assert_eq!("Taker", status["type"]);
+    assert_eq!(json!(fok_uuid), status["order"]["request"]["uuid"]);
+    assert!(status["expired_at"].as_u64().is_some());
+    assert_eq!(404, unknown.status().as_u16());
+}
+
+#[test]
+fn test_fill_or_kill_order_is_not_reserved_partially() {
+    // the order is loaded with the partial fills allowed
+    let mut order = partial_taker_order_for_test(TakerAction::Buy);
+    order.order_type = OrderType::FillOrKill;
+    let uuid = order.request.uuid;
+
+    let reserved = maker_reserved_for_test(uuid, 6, 12);
+    assert_eq!(MatchReservedResult::NotMatched, order.match_reserved(&reserved));
+    let reserved = maker_reserved_for_test(uuid, 10, 20);
+    assert_eq!(MatchReservedResult::Matched, order.match_reserved(&reserved));
+}
+
+fn maker_reserved_for_test(taker_order_uuid: Uuid, base_amount: u64, rel_amount: u64) -> MakerReserved {
+    MakerReserved {
+        method: "reserved".into(),
+        base: "BASE".into(),
+        rel: "REL".into(),
+        base_amount: base_amount.into(),
+        base_amount_rat: Some(BigRational::from_integer(base_amount.into())),
+        rel_amount: rel_amount.into(),
+        rel_amount_rat: Some(BigRational::from_integer(rel_amount.into())),
+        sender_pubkey: H256Json::default(),
+        dest_pub_key: H256Json::default(),
+        maker_order_uuid: Uuid::new_v4(),
+        taker_order_uuid,
+    }
+}
+
+fn partial_taker_order_for_test(action: TakerAction) -> TakerOrder {
+    let (base, rel) = match action {
+        TakerAction::Buy => ("BASE", "REL"),
+        TakerAction::Sell => ("REL", "BASE"),
+    };
+    TakerOrder {
+        created_at: now_ms(),
+        request: TakerRequest {
+            base: base.into(),
+            rel: rel.into(),
+            uuid: Uuid::new_v4(),
+            method: "request".into(),
+            dest_pub_key: H256Json::default(),
+            sender_pubkey: H256Json::default(),
+            base_amount: 10.into(),
+            base_amount_rat: Some(BigRational::from_integer(10.into())),
+            rel_amount: 20.into(),
+            rel_amount_rat: Some(BigRational::from_integer(20.into())),
+            action,
+            allow_partial_fill: true,
+        },
+        matches: HashMap::new(),
+        order_type: OrderType::GoodTillCancelled,
+        pending_reservations: Vec::new(),
+        reservations_window: 0,
+        tie_breaker: TieBreaker::Age,
+    }
+}
+
+#[test]
+fn test_match_maker_order_and_taker_request_partially() {
+    let maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 5.into(),
+        max_base_vol_rat: BigRational::from_integer(5.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
+        uuid: Uuid::new_v4(),
+    };
+
+    let mut request = partial_taker_order_for_test(TakerAction::Buy).request;
+    let actual = match_order_and_request(&maker, &request);
+    let expected = OrderMatchResult::Matched((5.into(), 5.into()));
+    assert_eq!(expected, actual);
+
+    request.allow_partial_fill = false;
+    let actual = match_order_and_request(&maker, &request);
+    assert_eq!(OrderMatchResult::NotMatched, actual);
+
+    // taker sells 10 REL for at least 20 BASE
+    let maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 5.into(),
+        max_base_vol_rat: BigRational::from_integer(5.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: "0.5".parse().unwrap(),
+        price_rat: BigRational::new(1.into(), 2.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
+        uuid: Uuid::new_v4(),
+    };
+    let request = partial_taker_order_for_test(TakerAction::Sell).request;
+    let actual = match_order_and_request(&maker, &request);
+    let expected = OrderMatchResult::Matched((5.into(), MmNumber::from(BigRational::new(5.into(), 2.into()))));
+    assert_eq!(expected, actual);
+
+    // available amount is lower than min volume
+    let maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 5.into(),
+        max_base_vol_rat: BigRational::from_integer(5.into()),
+        min_base_vol: 6.into(),
+        min_base_vol_rat: BigRational::from_integer(6.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
+        uuid: Uuid::new_v4(),
+    };
+    let request = partial_taker_order_for_test(TakerAction::Buy).request;
+    let actual = match_order_and_request(&maker, &request);
+    assert_eq!(OrderMatchResult::NotMatched, actual);
+}
+
+fn taker_request_for_test(action: TakerAction, base: &str, rel: &str, base_amount: u64, rel_amount: u64) -> TakerRequest {
+    TakerRequest {
+        base: base.into(),
+        rel: rel.into(),
+        uuid: Uuid::new_v4(),
+        method: "request".into(),
+        dest_pub_key: H256Json::default(),
+        sender_pubkey: H256Json::default(),
+        base_amount: base_amount.into(),
+        base_amount_rat: Some(BigRational::from_integer(base_amount.into())),
+        rel_amount: rel_amount.into(),
+        rel_amount_rat: Some(BigRational::from_integer(rel_amount.into())),
+        action,
+        allow_partial_fill: false,
+    }
+}
+
+#[test]
+fn test_match_maker_order_and_taker_request_by_strategy() {
+    let mut maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 20.into(),
+        max_base_vol_rat: BigRational::from_integer(20.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 2.into(),
+        price_rat: BigRational::from_integer(2.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        uuid: Uuid::new_v4(),
+        // 2 REL per BASE plus 10% spread is limited by the max price
+        strategy: MakerStrategy::PriceBand {
+            reference_price: 2.into(),
+            spread: 10.into(),
+            min_price: 1.into(),
+            max_price: MmNumber::from(BigRational::new(21.into(), 10.into())),
+        },
+    };
+    assert_eq!(MmNumber::from(BigRational::new(21.into(), 10.into())), maker.price_for_volume(&1.into()));
+    let request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 10, 20);
+    assert_eq!(OrderMatchResult::NotMatched, match_order_and_request(&maker, &request));
+    let request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 10, 21);
+    assert_eq!(OrderMatchResult::Matched((10.into(), 21.into())), match_order_and_request(&maker, &request));
+
+    // the larger requests get the better price, the order price is used for the smaller ones
+    maker.strategy = MakerStrategy::VolumeTiers(vec![
+        VolumeTier { min_volume: 10.into(), price: 1.into() },
+        VolumeTier { min_volume: 5.into(), price: MmNumber::from(BigRational::new(3.into(), 2.into())) },
+    ]);
+    unwrap!(maker.strategy.validate());
+    let request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 4, 6);
+    assert_eq!(OrderMatchResult::NotMatched, match_order_and_request(&maker, &request));
+    let request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 6, 9);
+    assert_eq!(OrderMatchResult::Matched((6.into(), 9.into())), match_order_and_request(&maker, &request));
+    let request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 10, 10);
+    assert_eq!(OrderMatchResult::Matched((10.into(), 10.into())), match_order_and_request(&maker, &request));
+    // taker sells 10 REL for at least 10 BASE
+    let request = taker_request_for_test(TakerAction::Sell, "REL", "BASE", 10, 10);
+    assert_eq!(OrderMatchResult::Matched((10.into(), 10.into())), match_order_and_request(&maker, &request));
+
+    maker.strategy = MakerStrategy::VolumeTiers(vec![]);
+    assert!(maker.strategy.validate().is_err());
+    maker.strategy = MakerStrategy::PriceBand {
+        reference_price: 2.into(),
+        spread: 0.into(),
+        min_price: 3.into(),
+        max_price: 1.into(),
+    };
+    assert!(maker.strategy.validate().is_err());
+}
+
+#[test]
+fn test_reprice_my_maker_orders() {
+    let ctx = MmCtxBuilder::default().into_mm_arc();
+    save_my_maker_order.mock_safe(|_, _| MockResult::Return(()));
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
+    let maker = |base: &str, rel: &str, strategy: MakerStrategy| MakerOrder {
+        base: base.into(),
+        rel: rel.into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        uuid: new_uuid(),
+        strategy,
+    };
+    let fixed = maker("BASE", "REL", MakerStrategy::FixedPrice);
+    let band = maker("BASE", "REL", MakerStrategy::PriceBand {
+        reference_price: 1.into(),
+        spread: 50.into(),
+        min_price: 1.into(),
+        max_price: 10.into(),
+    });
+    let other = maker("REL", "BASE", MakerStrategy::FixedPrice);
+    let (fixed_uuid, band_uuid, other_uuid) = (fixed.uuid, band.uuid, other.uuid);
+    {
+        let mut my_orders = unwrap!(ordermatch_ctx.my_maker_orders.lock());
+        my_orders.insert(fixed.uuid, fixed);
+        my_orders.insert(band.uuid, band);
+        my_orders.insert(other.uuid, other);
+    }
+
+    assert!(reprice_my_maker_orders(&ctx, "BASE", "REL", &2.into(), &MmNumber::default()).is_err());
+    let repriced = unwrap!(reprice_my_maker_orders(&ctx, "BASE", "REL", &2.into(), &3.into()));
+    assert_eq!(2, repriced);
+
+    let my_orders = unwrap!(ordermatch_ctx.my_maker_orders.lock());
+    assert_eq!(BigRational::from_integer(3.into()), my_orders[&fixed_uuid].price_rat);
+    // the band order keeps its spread from the new reference price
+    assert_eq!(BigRational::from_integer(3.into()), my_orders[&band_uuid].price_rat);
+    match &my_orders[&band_uuid].strategy {
+        MakerStrategy::PriceBand { reference_price, .. } => assert_eq!(MmNumber::from(2), *reference_price),
+        strategy => panic!("Unexpected strategy {:?}", strategy),
+    }
+    assert_eq!(BigRational::from_integer(1.into()), my_orders[&other_uuid].price_rat);
+}
+
+#[test]
+fn test_price_band_order_advertises_band_price() {
+    let key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+    let uuid = new_uuid();
+    let mut maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        // the price saved before the band was applied
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        uuid,
+        strategy: MakerStrategy::PriceBand {
+            reference_price: 2.into(),
+            spread: 50.into(),
+            min_price: 1.into(),
+            max_price: 10.into(),
+        },
+    };
+    let ping = unwrap!(PricePingRequest::new(&ctx, &maker, 10.into()));
+    assert_eq!(MmNumber::from(3), ping.get_price());
+    assert_eq!("300000000", ping.price64);
+    unwrap!(verify_price_ping(&ctx, &ping));
+
+    // the new price of the band order is the new reference price
+    let update = MakerOrderUpdate {
+        uuid,
+        price: Some(4.into()),
+        volume: None,
+        min_volume: None,
+    };
+    unwrap!(maker.apply_update(&update));
+    assert_eq!(BigRational::from_integer(6.into()), maker.price_rat);
+    assert_eq!(MmNumber::from(6), maker.price_for_volume(&1.into()));
+    match &maker.strategy {
+        MakerStrategy::PriceBand { reference_price, .. } => assert_eq!(MmNumber::from(4), *reference_price),
+        strategy => panic!("Unexpected strategy {:?}", strategy),
+    }
+    let ping = unwrap!(PricePingRequest::new(&ctx, &maker, 10.into()));
+    assert_eq!(MmNumber::from(6), ping.get_price());
+}
+
+#[test]
+fn test_taker_order_match_reserved_partially() {
+    // buy 10 BASE for 20 REL
+    let mut order = partial_taker_order_for_test(TakerAction::Buy);
+    let uuid = order.request.uuid;
+
+    let reserved = maker_reserved_for_test(uuid, 6, 12);
+    assert_eq!(MatchReservedResult::Matched, order.match_reserved(&reserved));
+
+    // the price is worse than requested
+    let worse_priced = maker_reserved_for_test(uuid, 6, 13);
+    assert_eq!(MatchReservedResult::NotMatched, order.match_reserved(&worse_priced));
+
+    order.matches.insert(reserved.maker_order_uuid, TakerMatch {
+        connect: TakerConnect {
+            method: "connect".into(),
+            sender_pubkey: H256Json::default(),
+            dest_pub_key: H256Json::default(),
+            maker_order_uuid: reserved.maker_order_uuid,
+            taker_order_uuid: uuid,
+            swap_uuid: Some(uuid),
+        },
+        reserved,
+        connected: None,
+        last_updated: now_ms(),
+    });
+    assert_eq!(MmNumber::from(BigRational::from_integer(4.into())), order.remaining_base_amount());
+
+    // the remaining volume is lower than reserved
+    let reserved = maker_reserved_for_test(uuid, 5, 5);
+    assert_eq!(MatchReservedResult::NotMatched, order.match_reserved(&reserved));
+    let reserved = maker_reserved_for_test(uuid, 4, 4);
+    assert_eq!(MatchReservedResult::Matched, order.match_reserved(&reserved));
+
+    // sell 10 REL for 20 BASE, reserved base is what we receive
+    let order = partial_taker_order_for_test(TakerAction::Sell);
+    let reserved = maker_reserved_for_test(order.request.uuid, 10, 5);
+    assert_eq!(MatchReservedResult::Matched, order.match_reserved(&reserved));
+    let reserved = maker_reserved_for_test(order.request.uuid, 9, 5);
+    assert_eq!(MatchReservedResult::NotMatched, order.match_reserved(&reserved));
+    let reserved = maker_reserved_for_test(order.request.uuid, 24, 11);
+    assert_eq!(MatchReservedResult::NotMatched, order.match_reserved(&reserved));
+}
+
+#[test]
+fn test_connect_to_reserved_best_price_first() {
+    let key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+    add_maker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    add_taker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    save_my_taker_order.mock_safe(|_, _| {
+        MockResult::Return(())
+    });
+
+    // buy 10 BASE for 20 REL
+    let mut order = partial_taker_order_for_test(TakerAction::Buy);
+    let uuid = order.request.uuid;
+    let worst = maker_reserved_for_test(uuid, 6, 12);
+    let best = maker_reserved_for_test(uuid, 6, 9);
+    let second = maker_reserved_for_test(uuid, 4, 7);
+    order.pending_reservations = vec![worst.clone(), second.clone(), best.clone()];
+
+    unwrap!(connect_to_reserved(&ctx, &mut order));
+    assert!(order.pending_reservations.is_empty());
+    assert_eq!(2, order.matches.len());
+    assert!(!order.matches.contains_key(&worst.maker_order_uuid));
+    // the first match uses the order uuid for the swap, the next ones get their own uuids
+    assert_eq!(uuid, order.matches[&best.maker_order_uuid].connect.swap_uuid());
+    assert_ne!(uuid, order.matches[&second.maker_order_uuid].connect.swap_uuid());
+    assert_eq!(MmNumber::default(), order.remaining_base_amount());
+}
+
+#[test]
+fn test_connect_to_reserved_fill_or_kill_is_not_overfilled() {
+    let key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+    add_taker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    save_my_taker_order.mock_safe(|_, _| {
+        MockResult::Return(())
+    });
+
+    // buy 10 BASE for 20 REL, the order is loaded with the partial fills allowed
+    let mut order = partial_taker_order_for_test(TakerAction::Buy);
+    order.order_type = OrderType::FillOrKill;
+    let uuid = order.request.uuid;
+    let best = maker_reserved_for_test(uuid, 10, 18);
+    let other = maker_reserved_for_test(uuid, 10, 20);
+    order.pending_reservations = vec![other.clone(), best.clone()];
+
+    unwrap!(connect_to_reserved(&ctx, &mut order));
+    assert_eq!(1, order.matches.len());
+    assert!(order.matches.contains_key(&best.maker_order_uuid));
+}
+
+#[test]
+fn test_timed_out_partially_filled_taker_order() {
+    let ctx = MmCtxBuilder::default().into_mm_arc();
+    add_maker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    add_taker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    delete_my_taker_order.mock_safe(|_, _| {
+        MockResult::Return(())
+    });
+    save_my_maker_order.mock_safe(|_, _| {
+        MockResult::Return(())
+    });
+
+    // buy 10 BASE for 20 REL
+    let mut order = partial_taker_order_for_test(TakerAction::Buy);
+    order.created_at = now_ms() - ORDERMATCH_TIMEOUT - 1000;
+    let uuid = order.request.uuid;
+    for (reserved, connected) in vec![(maker_reserved_for_test(uuid, 4, 8), true), (maker_reserved_for_test(uuid, 3, 6), false)] {
+        let connect = TakerConnect {
+            method: "connect".into(),
+            sender_pubkey: H256Json::default(),
+            dest_pub_key: H256Json::default(),
+            maker_order_uuid: reserved.maker_order_uuid,
+            taker_order_uuid: uuid,
+            swap_uuid: Some(Uuid::new_v4()),
+        };
+        let connected = if connected {
+            Some(MakerConnected {
+                method: "connected".into(),
+                sender_pubkey: H256Json::default(),
+                dest_pub_key: H256Json::default(),
+                maker_order_uuid: reserved.maker_order_uuid,
+                taker_order_uuid: uuid,
+            })
+        } else {
+            None
+        };
+        order.matches.insert(reserved.maker_order_uuid, TakerMatch {
+            reserved,
+            connect,
+            connected,
+            last_updated: now_ms(),
+        });
+    }
+
+    let mut taker_orders = HashMap::new();
+    taker_orders.insert(uuid, order);
+    let mut maker_orders = HashMap::new();
+    handle_timed_out_taker_orders(&ctx, &mut taker_orders, &mut maker_orders);
+    assert!(taker_orders.is_empty());
+
+    // only the connected match volume is excluded, the maker order sells REL for BASE
+    let maker_order = &maker_orders[&uuid];
+    assert_eq!("REL", maker_order.base);
+    assert_eq!(BigRational::from_integer(12.into()), maker_order.max_base_vol_rat);
+    assert_eq!(BigRational::new(1.into(), 2.into()), maker_order.price_rat);
+}
+
+#[test]
+fn test_connect_to_reserved_waits_for_reservations_window() {
+    let key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+    add_maker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    add_taker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    save_my_taker_order.mock_safe(|_, _| {
+        MockResult::Return(())
+    });
+
+    let mut order = partial_taker_order_for_test(TakerAction::Buy);
+    order.reservations_window = 10000;
+    let uuid = order.request.uuid;
+    order.pending_reservations = vec![maker_reserved_for_test(uuid, 6, 12)];
+
+    unwrap!(connect_to_reserved(&ctx, &mut order));
+    assert_eq!(1, order.pending_reservations.len());
+    assert!(order.matches.is_empty());
+
+    order.created_at = now_ms() - 10000;
+    unwrap!(connect_to_reserved(&ctx, &mut order));
+    assert!(order.pending_reservations.is_empty());
+    assert_eq!(1, order.matches.len());
+}
+
+#[test]
+fn test_connect_to_reserved_tie_breaker() {
+    let key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+    add_maker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    add_taker_order_history_event.mock_safe(|_, _, _| {
+        MockResult::Return(())
+    });
+    save_my_taker_order.mock_safe(|_, _| {
+        MockResult::Return(())
+    });
+    successful_swaps_count.mock_safe(|_, pubkey| {
+        let count = if pubkey.0 == [2; 32] { 5 } else { 0 };
+        MockResult::Return(Ok(count))
+    });
+
+    let mut order = partial_taker_order_for_test(TakerAction::Buy);
+    order.request.allow_partial_fill = false;
+    let uuid = order.request.uuid;
+    let mut first = maker_reserved_for_test(uuid, 10, 20);
+    first.sender_pubkey = H256Json::from([1; 32]);
+    let mut second = maker_reserved_for_test(uuid, 10, 20);
+    second.sender_pubkey = H256Json::from([2; 32]);
+
+    // the first received reservation is connected by default
+    order.pending_reservations = vec![first.clone(), second.clone()];
+    unwrap!(connect_to_reserved(&ctx, &mut order));
+    assert_eq!(1, order.matches.len());
+    assert!(order.matches.contains_key(&first.maker_order_uuid));
+
+    order.matches.clear();
+    order.tie_breaker = TieBreaker::Reputation;
+    order.pending_reservations = vec![first.clone(), second.clone()];
+    unwrap!(connect_to_reserved(&ctx, &mut order));
+    assert_eq!(1, order.matches.len());
+    assert!(order.matches.contains_key(&second.maker_order_uuid));
+}
+
+fn orderbook_entry_for_test(price: BigRational, volume: u64) -> OrderbookEntry {
+    OrderbookEntry {
+        coin: "BASE".into(),
+        address: Some("address".into()),
+        price: from_ratio_to_dec(&price),
+        price_rat: price,
+        max_volume: volume.into(),
+        max_volume_rat: BigRational::from_integer(volume.into()),
+        min_volume: 0.into(),
+        min_volume_rat: BigRational::zero(),
+        pubkey: "pubkey".into(),
+        age: 0,
+        zcredits: 0,
+    }
+}
+
+#[test]
+fn test_aggregate_price_levels() {
+    let asks = vec![
+        orderbook_entry_for_test(BigRational::new(1.into(), 1.into()), 1),
+        orderbook_entry_for_test(BigRational::new(1.into(), 1.into()), 2),
+        orderbook_entry_for_test(BigRational::new(21.into(), 20.into()), 3),
+        orderbook_entry_for_test(BigRational::new(2.into(), 1.into()), 4),
+    ];
+
+    let levels = aggregate_price_levels(&asks, None, true, None);
+    assert_eq!(3, levels.len());
+    assert_eq!(BigRational::from_integer(1.into()), levels[0].price_rat);
+    assert_eq!(BigRational::from_integer(3.into()), levels[0].volume_rat);
+    assert_eq!(BigRational::from_integer(3.into()), levels[0].depth_rat);
+    assert_eq!(2, levels[0].num_orders);
+    assert_eq!(BigRational::from_integer(6.into()), levels[1].depth_rat);
+    assert_eq!(BigRational::from_integer(10.into()), levels[2].depth_rat);
+
+    // asks are rounded up to the tick so 1.05 joins the 1.1 level
+    let tick = BigRational::new(1.into(), 10.into());
+    let levels = aggregate_price_levels(&asks, Some(&tick), true, None);
+    assert_eq!(3, levels.len());
+    assert_eq!(BigRational::new(11.into(), 10.into()), levels[1].price_rat);
+
+    // bids are rounded down to the tick so 1.05 joins the 1 level
+    let tick = BigRational::new(1.into(), 10.into());
+    let levels = aggregate_price_levels(&asks, Some(&tick), false, None);
+    assert_eq!(2, levels.len());
+    assert_eq!(BigRational::from_integer(1.into()), levels[0].price_rat);
+    assert_eq!(BigRational::from_integer(6.into()), levels[0].volume_rat);
+    assert_eq!(3, levels[0].num_orders);
+
+    let levels = aggregate_price_levels(&asks, None, true, Some(2));
+    assert_eq!(2, levels.len());
+    assert_eq!(BigRational::from_integer(6.into()), levels[1].depth_rat);
+}
+
+#[test]
+fn test_orderbook_updates_since() {
+    let mut updates = OrderbookUpdates::default();
+    // a new subscriber of the empty orderbook doesn't miss anything
+    assert_eq!(Some(0), updates.updates_since("BASE", "REL", 0).map(|u| u.len()));
+
+    let first = new_uuid();
+    let second = new_uuid();
+    updates.push(OrderbookUpdateKind::Added, "BASE", "REL", first, None);
+    updates.push(OrderbookUpdateKind::Added, "OTHER", "REL", new_uuid(), None);
+    updates.push(OrderbookUpdateKind::Added, "REL", "BASE", second, None);
+    updates.push(OrderbookUpdateKind::Removed, "BASE", "REL", first, None);
+    assert_eq!(4, updates.seq);
+
+    let pair_updates = unwrap!(updates.updates_since("BASE", "REL", 0));
+    assert_eq!(vec![1, 3, 4], pair_updates.iter().map(|u| u.seq).collect::<Vec<_>>());
+    let pair_updates = unwrap!(updates.updates_since("REL", "BASE", 3));
+    assert_eq!(1, pair_updates.len());
+    assert_eq!(OrderbookUpdateKind::Removed, pair_updates[0].kind);
+    assert_eq!(first, pair_updates[0].uuid);
+    assert!(unwrap!(updates.updates_since("BASE", "REL", 4)).is_empty());
+    // the subscriber is ahead of the node, e.g. the node has been restarted
+    assert!(updates.updates_since("BASE", "REL", 5).is_none());
+
+    // the subscriber has fallen behind the history
+    updates.history.pop_front();
+    assert!(updates.updates_since("BASE", "REL", 0).is_none());
+    assert!(updates.updates_since("BASE", "REL", 1).is_some());
+}
+
+#[test]
+fn test_my_order_history_swaps() {
+    let uuid = new_uuid();
+    let first_swap = new_uuid();
+    let second_swap = new_uuid();
+    let mut history = MyOrderHistory::new(uuid);
+    let events = vec![
+        OrderHistoryEvent::Created,
+        OrderHistoryEvent::Matched { other_order_uuid: new_uuid() },
+        OrderHistoryEvent::SwapStarted { swap_uuid: first_swap },
+        OrderHistoryEvent::Matched { other_order_uuid: new_uuid() },
+        OrderHistoryEvent::SwapStarted { swap_uuid: second_swap },
+        OrderHistoryEvent::Filled,
+    ];
+    history.events = events.into_iter().map(|event| OrderHistoryEntry { event, timestamp: now_ms() }).collect();
+    assert_eq!(vec![first_swap, second_swap], history.swaps());
+
+    let json = unwrap!(json::to_value(MyOrderHistoryForRpc::from(&history)));
+    assert_eq!(json!(uuid), json["uuid"]);
+    assert_eq!(json!([first_swap, second_swap]), json["swaps"]);
+    assert_eq!(json!({"type": "SwapStarted", "data": {"swap_uuid": first_swap}}), json["events"][2]["event"]);
+    assert!(json.get("taker_order").is_none());
+
+    let deserialized: MyOrderHistory = unwrap!(json::from_value(json));
+    assert_eq!(history.events.len(), deserialized.events.len());
+    assert_eq!(OrderHistoryEvent::Filled, deserialized.events[5].event);
+}
+
+fn conditional_order_for_test(method: ConditionalOrderMethod, trigger: ConditionalTrigger, trigger_price: u64) -> ConditionalOrder {
+    ConditionalOrder {
+        uuid: new_uuid(),
+        base: "BASE".into(),
+        rel: "REL".into(),
+        method,
+        trigger,
+        trigger_price: trigger_price.into(),
+        price: trigger_price.into(),
+        volume: 1.into(),
+        created_at: now_ms(),
+        submitted_at: 0,
+        last_error: None,
+        submitting: false,
+    }
+}
+
+fn price_ping_for_test(base: &str, rel: &str, price: BigRational) -> PricePingRequest {
+    PricePingRequest {
+        method: "postprice".into(),
+        pubkey: "".into(),
+        base: base.into(),
+        rel: rel.into(),
+        price: from_ratio_to_dec(&price),
+        price_rat: Some(price),
+        price64: "".into(),
+        timestamp: now_ms() / 1000,
+        pubsecp: "".into(),
+        sig: "".into(),
+        balance: 10.into(),
+        balance_rat: Some(BigRational::from_integer(10.into())),
+        uuid: None,
+        min_volume: None,
+        min_volume_rat: None,
+    }
+}
+
+#[test]
+fn test_conditional_order_is_triggered() {
+    let price = |n: u64| MmNumber::from(n);
+
+    let stop_sell = conditional_order_for_test(ConditionalOrderMethod::Sell, ConditionalTrigger::StopLoss, 10);
+    assert!(!stop_sell.is_triggered(None, Some(&price(5))));
+    assert!(!stop_sell.is_triggered(Some(&price(11)), None));
+    assert!(stop_sell.is_triggered(Some(&price(10)), None));
+    assert!(stop_sell.is_triggered(Some(&price(9)), None));
+
+    let profit_sell = conditional_order_for_test(ConditionalOrderMethod::SetPrice, ConditionalTrigger::TakeProfit, 10);
+    assert!(!profit_sell.is_triggered(Some(&price(9)), Some(&price(20))));
+    assert!(profit_sell.is_triggered(Some(&price(11)), None));
+
+    let stop_buy = conditional_order_for_test(ConditionalOrderMethod::Buy, ConditionalTrigger::StopLoss, 10);
+    assert!(!stop_buy.is_triggered(Some(&price(20)), Some(&price(9))));
+    assert!(stop_buy.is_triggered(None, Some(&price(10))));
+
+    let profit_buy = conditional_order_for_test(ConditionalOrderMethod::Buy, ConditionalTrigger::TakeProfit, 10);
+    assert!(!profit_buy.is_triggered(None, Some(&price(11))));
+    assert!(profit_buy.is_triggered(None, Some(&price(9))));
+}
+
+#[test]
+fn test_triggered_conditional_orders() {
+    let key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+    let my_pubsecp = hex::encode(&**ctx.secp256k1_key_pair().public());
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
+    {
+        let mut orderbook = unwrap!(ordermatch_ctx.orderbook.lock());
+        let mut asks = HashMap::new();
+        asks.insert(new_uuid(), price_ping_for_test("BASE", "REL", BigRational::from_integer(12.into())));
+        asks.insert(new_uuid(), price_ping_for_test("BASE", "REL", BigRational::from_integer(11.into())));
+        orderbook.insert(("BASE".into(), "REL".into()), asks);
+        // the bid of 8 REL per BASE is the ask of 1/8 BASE per REL
+        let mut bids = HashMap::new();
+        bids.insert(new_uuid(), price_ping_for_test("REL", "BASE", BigRational::new(1.into(), 8.into())));
+        bids.insert(new_uuid(), price_ping_for_test("REL", "BASE", BigRational::new(1.into(), 4.into())));
+        orderbook.insert(("REL".into(), "BASE".into()), bids);
+
+        // my own orders don't trigger my conditional orders
+        let mut my_ask = price_ping_for_test("BASE", "REL", BigRational::from_integer(1.into()));
+        my_ask.pubsecp = my_pubsecp.clone();
+        unwrap!(orderbook.get_mut(&("BASE".to_owned(), "REL".to_owned()))).insert(new_uuid(), my_ask);
+
+        let (best_bid, best_ask) = best_bid_ask(&orderbook, "BASE", "REL", Some(&my_pubsecp));
+        assert_eq!(Some(MmNumber::from(8)), best_bid);
+        assert_eq!(Some(MmNumber::from(11)), best_ask);
+        assert_eq!(Some(MmNumber::from(1)), best_bid_ask(&orderbook, "BASE", "REL", None).1);
+    }
+
+    let stop_sell = conditional_order_for_test(ConditionalOrderMethod::Sell, ConditionalTrigger::StopLoss, 9);
+    let profit_sell = conditional_order_for_test(ConditionalOrderMethod::Sell, ConditionalTrigger::TakeProfit, 9);
+    let stop_buy = conditional_order_for_test(ConditionalOrderMethod::Buy, ConditionalTrigger::StopLoss, 11);
+    {
+        let mut conditional_orders = unwrap!(ordermatch_ctx.my_conditional_orders.lock());
+        for order in vec![stop_sell.clone(), profit_sell.clone(), stop_buy.clone()] {
+            conditional_orders.insert(order.uuid, order);
+        }
+    }
+
+    let mut triggered: Vec<_> = unwrap!(triggered_conditional_orders(&ctx)).into_iter().map(|order| order.uuid).collect();
+    triggered.sort();
+    let mut expected = vec![stop_sell.uuid, stop_buy.uuid];
+    expected.sort();
+    assert_eq!(expected, triggered);
+    // the orders being submitted are not triggered again
+    assert!(unwrap!(triggered_conditional_orders(&ctx)).is_empty());
+    // the triggered orders are kept until the submission succeeds
+    let conditional_orders = unwrap!(ordermatch_ctx.my_conditional_orders.lock());
+    assert_eq!(3, conditional_orders.len());
+    assert!(conditional_orders[&stop_sell.uuid].submitting);
+    assert!(!conditional_orders[&profit_sell.uuid].submitting);
+}
+
+#[test]
+fn test_finish_conditional_order_submission() {
+    let ctx = MmCtxBuilder::default().into_mm_arc();
+    save_my_conditional_order.mock_safe(|_, _| MockResult::Return(()));
+    delete_my_conditional_order.mock_safe(|_, _| MockResult::Return(()));
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
+    let mut order = conditional_order_for_test(ConditionalOrderMethod::Sell, ConditionalTrigger::StopLoss, 9);
+    order.submitting = true;
+    order.submitted_at = now_ms();
+    let uuid = order.uuid;
+    unwrap!(ordermatch_ctx.my_conditional_orders.lock()).insert(uuid, order);
+
+    // the failed submission is reported and retried later
+    unwrap!(finish_conditional_order_submission(&ctx, &uuid, Err("Not enough balance".into())));
+    {
+        let conditional_orders = unwrap!(ordermatch_ctx.my_conditional_orders.lock());
+        assert!(!conditional_orders[&uuid].submitting);
+        assert_eq!(Some("Not enough balance".to_owned()), conditional_orders[&uuid].last_error);
+    }
+    let response = unwrap!(order_status(ctx.clone(), json!({"uuid": uuid})).wait());
+    let status: Json = unwrap!(json::from_slice(response.body()));
+    assert_eq!("Conditional", status["type"]);
+    assert_eq!("Not enough balance", status["order"]["last_error"]);
+
+    unwrap!(finish_conditional_order_submission(&ctx, &uuid, Ok(())));
+    assert!(unwrap!(ordermatch_ctx.my_conditional_orders.lock()).is_empty());
+}
+
+#[test]
+fn test_pair_limits() {
+    let limits = unwrap!(PairLimits::from_conf(&Json::Null));
+    assert!(limits.is_pair_allowed("RICK", "MORTY"));
+    assert!(limits.check_open_volume("RICK", 1000.into(), &1000.into()).is_ok());
+    assert!(limits.check_swaps_count("RICK", "MORTY", 1000).is_ok());
+
+    let conf = json!({
+        "netid": 9999,
+        "allowed_pairs": ["RICK/MORTY", "RICK/BTC"],
+        "denied_pairs": ["BTC/RICK"],
+        "max_open_volume": {"RICK": "10"},
+        "max_swaps_per_pair": 2,
+    });
+    let limits = unwrap!(PairLimits::from_conf(&conf));
+    assert!(limits.is_pair_allowed("RICK", "MORTY"));
+    assert!(limits.is_pair_allowed("MORTY", "RICK"));
+    // the denied pair has a priority over allowed
+    assert!(!limits.is_pair_allowed("RICK", "BTC"));
+    assert!(!limits.is_pair_allowed("MORTY", "BTC"));
+    assert!(limits.check_pair("MORTY", "BTC").is_err());
+
+    assert!(limits.check_open_volume("RICK", 6.into(), &4.into()).is_ok());
+    assert!(limits.check_open_volume("RICK", 6.into(), &5.into()).is_err());
+    assert!(limits.check_open_volume("MORTY", 6.into(), &5.into()).is_ok());
+
+    assert!(limits.check_swaps_count("RICK", "MORTY", 1).is_ok());
+    assert!(limits.check_swaps_count("RICK", "MORTY", 2).is_err());
+
+    assert!(PairLimits::from_conf(&json!({"allowed_pairs": ["RICK"]})).is_err());
+    assert!(PairLimits::from_conf(&json!({"denied_pairs": ["RICK/MORTY/BTC"]})).is_err());
+    assert!(PairLimits::from_conf(&json!({"max_swaps_per_pair": -1})).is_err());
+
+    // the invalid limits are reported by the startup init
+    let ctx = MmCtxBuilder::new().with_conf(json!({"allowed_pairs": ["RICK"]})).into_mm_arc();
+    assert!(init_ordermatch_context(&ctx).is_err());
+}
+
+#[test]
+fn test_my_open_volume() {
+    let maker = MakerOrder {
+        base: "RICK".into(),
+        rel: "MORTY".into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
+        uuid: new_uuid(),
+    };
+    let mut sell = partial_taker_order_for_test(TakerAction::Sell);
+    sell.request.base = "RICK".into();
+    sell.request.rel = "MORTY".into();
+    let mut buy = partial_taker_order_for_test(TakerAction::Buy);
+    buy.request.base = "MORTY".into();
+    buy.request.rel = "RICK".into();
+    let maker_orders = vec![maker];
+    let taker_orders = vec![sell, buy];
+
+    // 10 of the maker order, 10 base of the sell and 20 rel of the buy
+    let volume = my_open_volume(maker_orders.iter(), taker_orders.iter(), "RICK");
+    assert_eq!(MmNumber::from(40), volume);
+    let volume = my_open_volume(maker_orders.iter(), taker_orders.iter(), "MORTY");
+    assert_eq!(MmNumber::default(), volume);
+}
+
+#[test]
+fn test_sweep_market_offers() {
+    let offer = |pubkey: u8, price: u64, min_volume: u64| MarketOffer {
+        pubkey: H256Json::from([pubkey; 32]),
+        price: price.into(),
+        max_volume: 5.into(),
+        min_volume: min_volume.into(),
+    };
+    // the second offer is skipped as the remaining 3 is less than its min volume
+    let offers = vec![offer(1, 1, 0), offer(2, 2, 4), offer(3, 3, 0)];
+    let fills = unwrap!(sweep_market_offers(&offers, &8.into()));
+    let expected = vec![
+        MarketOrderFill { dest_pub_key: H256Json::from([1; 32]), price: 1.into(), volume: 5.into() },
+        MarketOrderFill { dest_pub_key: H256Json::from([3; 32]), price: 3.into(), volume: 3.into() },
+    ];
+    assert_eq!(expected, fills);
+    assert_eq!(MmNumber::from(BigRational::new(7.into(), 4.into())), market_fills_price(&fills));
+
+    assert!(sweep_market_offers(&offers, &16.into()).is_err());
+}
+
+#[test]
+fn test_market_offers() {
+    let key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+    let our_pubkey = hex::encode(&unwrap!(ctx.public_id()).bytes);
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
+    {
+        let mut orderbook = unwrap!(ordermatch_ctx.orderbook.lock());
+        let mut asks = HashMap::new();
+        let mut ask = price_ping_for_test("BASE", "REL", BigRational::from_integer(12.into()));
+        ask.pubkey = hex::encode(&[1; 32]);
+        asks.insert(new_uuid(), ask);
+        let mut ask = price_ping_for_test("BASE", "REL", BigRational::from_integer(11.into()));
+        ask.pubkey = hex::encode(&[2; 32]);
+        asks.insert(new_uuid(), ask);
+        // our own order must not be offered
+        let mut ask = price_ping_for_test("BASE", "REL", BigRational::from_integer(10.into()));
+        ask.pubkey = our_pubkey;
+        asks.insert(new_uuid(), ask);
+        orderbook.insert(("BASE".into(), "REL".into()), asks);
+
+        // the maker sells 10 REL for 1/4 BASE each, so it buys 5/2 BASE for 4 REL each
+        let mut bids = HashMap::new();
+        let mut bid = price_ping_for_test("REL", "BASE", BigRational::new(1.into(), 4.into()));
+        bid.pubkey = hex::encode(&[3; 32]);
+        bid.min_volume = Some(2.into());
+        bid.min_volume_rat = Some(BigRational::from_integer(2.into()));
+        bids.insert(new_uuid(), bid);
+        let mut bid = price_ping_for_test("REL", "BASE", BigRational::new(1.into(), 8.into()));
+        bid.pubkey = hex::encode(&[4; 32]);
+        bids.insert(new_uuid(), bid);
+        orderbook.insert(("REL".into(), "BASE".into()), bids);
+    }
+
+    let asks = unwrap!(market_offers(&ctx, "BASE", "REL", &TakerAction::Buy));
+    assert_eq!(2, asks.len());
+    assert_eq!(H256Json::from([2; 32]), asks[0].pubkey);
+    assert_eq!(MmNumber::from(11), asks[0].price);
+    assert_eq!(MmNumber::from(12), asks[1].price);
+    assert_eq!(MmNumber::from(10), asks[1].max_volume);
+
+    let bids = unwrap!(market_offers(&ctx, "BASE", "REL", &TakerAction::Sell));
+    assert_eq!(2, bids.len());
+    assert_eq!(H256Json::from([4; 32]), bids[0].pubkey);
+    assert_eq!(MmNumber::from(8), bids[0].price);
+    assert_eq!(MmNumber::from(BigRational::new(5.into(), 4.into())), bids[0].max_volume);
+    assert_eq!(MmNumber::from(4), bids[1].price);
+    assert_eq!(MmNumber::from(BigRational::new(5.into(), 2.into())), bids[1].max_volume);
+    assert_eq!(MmNumber::from(BigRational::new(1.into(), 2.into())), bids[1].min_volume);
+}
+
+#[test]
+fn test_max_trade_amount() {
+    let ctx = MmCtxBuilder::default().into_mm_arc();
+    let fee: BigDecimal = unwrap!("0.1".parse());
+    let available = BigDecimal::from(10);
+
+    assert_eq!(available, unwrap!(max_trade_amount(&ctx, available.clone(), None, None)));
+    assert_eq!(unwrap!("9.9".parse::<BigDecimal>()), unwrap!(max_trade_amount(&ctx, available.clone(), Some(&fee), None)));
+
+    // the taker pays the fee twice and the dex fee
+    let amount: BigDecimal = unwrap!("9.8".parse());
+    let expected = unwrap!(unwrap!(max_taker_vol_from_available(&ctx, &amount, Some("RICK"), "MORTY")));
+    assert_eq!(expected, unwrap!(max_trade_amount(&ctx, available, Some(&fee), Some(("RICK", "MORTY")))));
+
+    assert_eq!(BigDecimal::from(0), unwrap!(max_trade_amount(&ctx, fee.clone(), Some(&fee), Some(("RICK", "MORTY")))));
+}
+
+#[test]
+fn test_orderbook_tickers() {
+    let mut orderbook = HashMap::new();
+    let mut asks = HashMap::new();
+    let mut ask = price_ping_for_test("BASE", "REL", BigRational::from_integer(12.into()));
+    ask.pubkey = "maker1".into();
+    asks.insert(new_uuid(), ask);
+    let mut ask = price_ping_for_test("BASE", "REL", BigRational::from_integer(11.into()));
+    ask.pubkey = "maker2".into();
+    asks.insert(new_uuid(), ask);
+    orderbook.insert(("BASE".to_owned(), "REL".to_owned()), asks);
+    // the bid of 8 REL per BASE is the ask of 1/8 BASE per REL
+    let mut bids = HashMap::new();
+    let mut bid = price_ping_for_test("REL", "BASE", BigRational::new(1.into(), 8.into()));
+    bid.pubkey = "maker1".into();
+    bids.insert(new_uuid(), bid);
+    orderbook.insert(("REL".to_owned(), "BASE".to_owned()), bids);
+    let mut asks = HashMap::new();
+    asks.insert(new_uuid(), price_ping_for_test("RICK", "MORTY", BigRational::from_integer(1.into())));
+    orderbook.insert(("RICK".to_owned(), "MORTY".to_owned()), asks);
+
+    let tickers = orderbook_tickers(&orderbook);
+    assert_eq!(2, tickers.len());
+
+    let ticker = &tickers[0];
+    assert_eq!(("BASE", "REL"), (ticker.base.as_str(), ticker.rel.as_str()));
+    assert_eq!(Some(BigDecimal::from(8)), ticker.best_bid);
+    assert_eq!(Some(BigDecimal::from(11)), ticker.best_ask);
+    assert_eq!(Some(BigDecimal::from(3)), ticker.spread);
+    assert_eq!(BigDecimal::from(20), ticker.ask_volume);
+    assert_eq!(unwrap!("1.25".parse::<BigDecimal>()), ticker.bid_volume);
+    assert_eq!(2, ticker.num_makers);
+
+    // the pair is listed with the coins sorted alphabetically, so RICK asks are the MORTY bids
+    let ticker = &tickers[1];
+    assert_eq!(("MORTY", "RICK"), (ticker.base.as_str(), ticker.rel.as_str()));
+    assert_eq!(Some(BigDecimal::from(1)), ticker.best_bid);
+    assert_eq!(None, ticker.best_ask);
+    assert_eq!(None, ticker.spread);
+    assert_eq!(BigDecimal::from(10), ticker.bid_volume);
+    assert_eq!(1, ticker.num_makers);
+}
+
+#[test]
+fn test_sign_and_verify_ordermatch_message() {
+    let key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
+    let our_pubkey = H256Json::from(unwrap!(ctx.public_id()).bytes);
+    let connected = MakerConnected {
+        taker_order_uuid: new_uuid(),
+        maker_order_uuid: new_uuid(),
+        method: "connected".into(),
+        sender_pubkey: our_pubkey.clone(),
+        dest_pub_key: H256Json::default(),
+    };
+
+    let signed: Json = unwrap!(json::from_str(&unwrap!(sign_ordermatch_message(&ctx, &connected))));
+    let parsed: MakerConnected = unwrap!(json::from_value(signed.clone()));
+    assert_eq!(connected.taker_order_uuid, parsed.taker_order_uuid);
+    // the message claiming to be sent by other node
+    let mut other_sender = parsed.clone();
+    other_sender.sender_pubkey = H256Json::from([1; 32]);
+    assert!(verify_ordermatch_message(&ctx, &ordermatch_ctx, &signed, &other_sender).is_err());
+
+    let mut tampered = parsed.clone();
+    tampered.maker_order_uuid = new_uuid();
+    assert!(verify_ordermatch_message(&ctx, &ordermatch_ctx, &signed, &tampered).is_err());
+
+    let mut stale = signed.clone();
+    stale["timestamp"] = json!(now_ms() / 1000 - MESSAGE_TIMESTAMP_TOLERANCE - 10);
+    assert!(verify_ordermatch_message(&ctx, &ordermatch_ctx, &stale, &parsed).is_err());
+
+    let mut unknown_version = signed.clone();
+    unknown_version["sig_version"] = json!(ORDERMATCH_SIG_VERSION + 1);
+    assert!(verify_ordermatch_message(&ctx, &ordermatch_ctx, &unknown_version, &parsed).is_err());
+
+    // the signature can't be stripped from the signed message
+    let mut stripped = signed.clone();
+    unwrap!(stripped.as_object_mut()).remove("sig");
+    assert!(verify_ordermatch_message(&ctx, &ordermatch_ctx, &stripped, &parsed).is_err());
+
+    unwrap!(verify_ordermatch_message(&ctx, &ordermatch_ctx, &signed, &parsed));
+    // the replayed message is rejected
+    assert!(verify_ordermatch_message(&ctx, &ordermatch_ctx, &signed, &parsed).is_err());
+
+    // the unsigned messages are rejected by default
+    let unsigned = unwrap!(json::to_value(&connected));
+    assert!(verify_ordermatch_message(&ctx, &ordermatch_ctx, &unsigned, &connected).is_err());
+
+    // the legacy unsigned messages are accepted if enabled by config,
+    // but not from the node that has sent the signed message already
+    let legacy_ctx = MmCtxBuilder::new().with_conf(json!({"accept_unsigned_ordermatch_messages": true})).into_mm_arc();
+    let legacy_ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&legacy_ctx));
+    unwrap!(verify_ordermatch_message(&legacy_ctx, &legacy_ordermatch_ctx, &unsigned, &connected));
+    assert!(verify_ordermatch_message(&legacy_ctx, &ordermatch_ctx, &unsigned, &connected).is_err());
+    let resigned: Json = unwrap!(json::from_str(&unwrap!(sign_ordermatch_message(&ctx, &connected))));
+    unwrap!(verify_ordermatch_message(&legacy_ctx, &legacy_ordermatch_ctx, &resigned, &connected));
+    assert!(verify_ordermatch_message(&legacy_ctx, &legacy_ordermatch_ctx, &unsigned, &connected).is_err());
+}
+
+#[test]
+fn test_forged_unsigned_reservation_is_dropped() {
+    let key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
+    let mut order = partial_taker_order_for_test(TakerAction::Buy);
+    order.request.allow_partial_fill = false;
+    let uuid = order.request.uuid;
+    unwrap!(ordermatch_ctx.my_taker_orders.lock()).insert(uuid, order);
+
+    // the reservation claiming to be sent by other maker without the signature
+    let mut reserved = maker_reserved_for_test(uuid, 10, 20);
+    reserved.sender_pubkey = H256Json::from([1; 32]);
+    reserved.dest_pub_key = H256Json::from(unwrap!(ctx.public_id()).bytes);
+    lp_trade_command(ctx.clone(), unwrap!(json::to_value(&reserved)));
+
+    let my_taker_orders = unwrap!(ordermatch_ctx.my_taker_orders.lock());
+    assert!(my_taker_orders[&uuid].pending_reservations.is_empty());
+}
+
+#[test]
+fn test_orderbook_snapshot_recv() {
+    let key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+    let maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        strategy: MakerStrategy::FixedPrice,
+        uuid: Uuid::new_v4(),
+    };
+    let ping = unwrap!(PricePingRequest::new(&ctx, &maker, 10.into()));
+    let mut forged = ping.clone();
+    forged.uuid = Some(Uuid::new_v4());
+    forged.price = 2.into();
+    forged.price_rat = Some(BigRational::from_integer(2.into()));
+    forged.price64 = "200000000".into();
+    let mut other_pair = ping.clone();
+    other_pair.uuid = Some(Uuid::new_v4());
+    other_pair.rel = "OTHER".into();
+
+    let snapshot = OrderbookSnapshot {
+        method: "orderbook_snapshot".into(),
+        base: "REL".into(),
+        rel: "BASE".into(),
+        dest_pub_key: H256Json::from(unwrap!(ctx.public_id()).bytes),
+        orders: vec![ping, forged.clone(), other_pair.clone()],
+    };
+    unwrap!(lp_orderbook_snapshot_recv(&ctx, unwrap!(json::to_value(&snapshot))).wait());
+
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
+    let orderbook = unwrap!(ordermatch_ctx.orderbook.lock());
+    let pair_orders = &orderbook[&("BASE".to_owned(), "REL".to_owned())];
+    assert_eq!(1, pair_orders.len());
+    assert!(pair_orders.contains_key(&maker.uuid));
+    assert!(!orderbook.contains_key(&("BASE".to_owned(), "OTHER".to_owned())));
+}
+
+#[test]
+fn test_orderbook_snapshot_request_must_be_signed() {
+    let seed_ctx = MmCtxBuilder::new().with_conf(json!({"i_am_seed": true})).into_mm_arc();
+    let requester_key_pair = unwrap!(key_pair_from_seed("spice describe gravity federal blast come thank unfair canal monkey style afraid"));
+    let requester_ctx = MmCtxBuilder::default().with_secp256k1_key_pair(requester_key_pair).into_mm_arc();
+    let request = OrderbookSnapshotRequest {
+        method: "orderbook_snapshot_request".into(),
+        base: "BASE".into(),
+        rel: "REL".into(),
+        sender_pubkey: H256Json::from(unwrap!(requester_ctx.public_id()).bytes),
+    };
+
+    // the unsigned request could exhaust the rate limit of any node
+    unwrap!(lp_orderbook_snapshot_request_recv(&seed_ctx, unwrap!(json::to_value(&request))).wait());
+    assert!(seed_ctx.client_p2p_channel.1.try_recv().is_err());
+
+    // the request claiming to be sent by other node
+    let signed: Json = unwrap!(json::from_str(&unwrap!(sign_ordermatch_message(&requester_ctx, &request))));
+    let mut spoofed = signed.clone();
+    spoofed["sender_pubkey"] = unwrap!(json::to_value(H256Json::from([1; 32])));
+    unwrap!(lp_orderbook_snapshot_request_recv(&seed_ctx, spoofed).wait());
+    assert!(seed_ctx.client_p2p_channel.1.try_recv().is_err());
+
+    unwrap!(lp_orderbook_snapshot_request_recv(&seed_ctx, signed).wait());
+    let snapshot: OrderbookSnapshot = unwrap!(json::from_slice(&unwrap!(seed_ctx.client_p2p_channel.1.try_recv())));
+    assert_eq!("orderbook_snapshot", snapshot.method);
+    assert_eq!(request.sender_pubkey, snapshot.dest_pub_key);
+}
+
+#[test]
+fn test_taker_request_limits() {
+    let ctx = MmCtxBuilder::default().into_mm_arc();
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
+    let taker_pubkey = H256Json::from([1; 32]);
+
+    let mut counter = TakerRequestsCounter::default();
+    for i in 0..MAX_TAKER_REQUESTS_PER_WINDOW as u64 {
+        assert!(counter.register_request(1000 + i));
+    }
+    assert!(!counter.register_request(2000));
+    // the requests expire after the window
+    assert!(counter.register_request(1000 + TAKER_REQUESTS_WINDOW));
+
+    let mut my_orders = HashMap::new();
+    let mut maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        uuid: new_uuid(),
+        strategy: MakerStrategy::FixedPrice,
+    };
+    for _ in 0..MAX_RESERVATIONS_PER_TAKER {
+        let mut request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 1, 1);
+        request.sender_pubkey = taker_pubkey.clone();
+        let reserved = MakerReserved {
+            method: "reserved".into(),
+            base: "BASE".into(),
+            rel: "REL".into(),
+            base_amount: 1.into(),
+            base_amount_rat: Some(BigRational::from_integer(1.into())),
+            rel_amount: 1.into(),
+            rel_amount_rat: Some(BigRational::from_integer(1.into())),
+            sender_pubkey: H256Json::default(),
+            dest_pub_key: taker_pubkey.clone(),
+            maker_order_uuid: maker.uuid,
+            taker_order_uuid: request.uuid,
+        };
+        maker.matches.insert(request.uuid, MakerMatch {
+            request,
+            reserved,
+            connect: None,
+            connected: None,
+            last_updated: now_ms(),
+        });
+    }
+    my_orders.insert(maker.uuid, maker);
+    assert_eq!(MAX_RESERVATIONS_PER_TAKER, taker_reservations_count(&my_orders, &taker_pubkey));
+    assert_eq!(0, taker_reservations_count(&my_orders, &H256Json::default()));
+
+    // the other takers are not affected
+    unwrap!(check_taker_request_limits(&ctx, &ordermatch_ctx, &my_orders, &H256Json::default()));
+    for _ in 1..MAX_TAKER_VIOLATIONS {
+        assert!(check_taker_request_limits(&ctx, &ordermatch_ctx, &my_orders, &taker_pubkey).is_err());
+        assert!(!is_pubkey_banned(&ctx, &taker_pubkey.clone().into()));
+    }
+    assert!(check_taker_request_limits(&ctx, &ordermatch_ctx, &my_orders, &taker_pubkey).is_err());
+    assert!(is_pubkey_banned(&ctx, &taker_pubkey.clone().into()));
+    assert!(!is_pubkey_banned(&ctx, &H256::default()));
+}
+
+#[test]
+fn test_targeted_request_is_reserved_by_dest_maker() {
+    let maker_key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let maker_ctx = MmCtxBuilder::default().with_secp256k1_key_pair(maker_key_pair).into_mm_arc();
+    let taker_key_pair = unwrap!(key_pair_from_seed("spice describe gravity federal blast come thank unfair canal monkey style afraid"));
+    let taker_ctx = MmCtxBuilder::default().with_secp256k1_key_pair(taker_key_pair).into_mm_arc();
+    let maker_pubkey = H256Json::from(unwrap!(maker_ctx.public_id()).bytes);
+    let taker_pubkey = H256Json::from(unwrap!(taker_ctx.public_id()).bytes);
+
+    save_my_maker_order.mock_safe(|_, _| MockResult::Return(()));
+    add_maker_order_history_event.mock_safe(|_, _, _| MockResult::Return(()));
+
+    let maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        uuid: new_uuid(),
+        strategy: MakerStrategy::FixedPrice,
+    };
+    let maker_uuid = maker.uuid;
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&maker_ctx));
+    unwrap!(ordermatch_ctx.my_maker_orders.lock()).insert(maker_uuid, maker);
+
+    // the request targeting other maker is skipped
+    let mut request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 1, 1);
+    request.sender_pubkey = taker_pubkey.clone();
+    request.dest_pub_key = H256Json::from([1; 32]);
+    let signed: Json = unwrap!(json::from_str(&unwrap!(sign_ordermatch_message(&taker_ctx, &request))));
+    lp_trade_command(maker_ctx.clone(), signed);
+    assert!(maker_ctx.client_p2p_channel.1.try_recv().is_err());
+
+    let mut request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 1, 1);
+    request.sender_pubkey = taker_pubkey.clone();
+    request.dest_pub_key = maker_pubkey.clone();
+    let signed: Json = unwrap!(json::from_str(&unwrap!(sign_ordermatch_message(&taker_ctx, &request))));
+    lp_trade_command(maker_ctx.clone(), signed);
+
+    let reserved: MakerReserved = unwrap!(json::from_slice(&unwrap!(maker_ctx.client_p2p_channel.1.try_recv())));
+    assert_eq!("reserved", reserved.method);
+    assert_eq!(taker_pubkey, reserved.dest_pub_key);
+    assert_eq!(maker_pubkey, reserved.sender_pubkey);
+    assert_eq!(request.uuid, reserved.taker_order_uuid);
+    assert_eq!(maker_uuid, reserved.maker_order_uuid);
+    assert!(unwrap!(ordermatch_ctx.my_maker_orders.lock())[&maker_uuid].matches.contains_key(&request.uuid));
+}
+
+#[test]
+fn test_connect_from_other_taker_is_rejected() {
+    let maker_key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let maker_ctx = MmCtxBuilder::default().with_secp256k1_key_pair(maker_key_pair).into_mm_arc();
+    let attacker_key_pair = unwrap!(key_pair_from_seed("spice describe gravity federal blast come thank unfair canal monkey style afraid"));
+    let attacker_ctx = MmCtxBuilder::default().with_secp256k1_key_pair(attacker_key_pair).into_mm_arc();
+    let maker_pubkey = H256Json::from(unwrap!(maker_ctx.public_id()).bytes);
+    let attacker_pubkey = H256Json::from(unwrap!(attacker_ctx.public_id()).bytes);
+    let taker_pubkey = H256Json::from([1; 32]);
+
+    let mut request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 1, 1);
+    request.sender_pubkey = taker_pubkey.clone();
+    let mut maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        uuid: new_uuid(),
+        strategy: MakerStrategy::FixedPrice,
+    };
+    let reserved = MakerReserved {
+        method: "reserved".into(),
+        base: "BASE".into(),
+        rel: "REL".into(),
+        base_amount: 1.into(),
+        base_amount_rat: Some(BigRational::from_integer(1.into())),
+        rel_amount: 1.into(),
+        rel_amount_rat: Some(BigRational::from_integer(1.into())),
+        sender_pubkey: maker_pubkey.clone(),
+        dest_pub_key: taker_pubkey.clone(),
+        maker_order_uuid: maker.uuid,
+        taker_order_uuid: request.uuid,
+    };
+    let taker_order_uuid = request.uuid;
+    let maker_uuid = maker.uuid;
+    maker.matches.insert(taker_order_uuid, MakerMatch {
+        request,
+        reserved,
+        connect: None,
+        connected: None,
+        last_updated: now_ms(),
+    });
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&maker_ctx));
+    unwrap!(ordermatch_ctx.my_maker_orders.lock()).insert(maker_uuid, maker);
+
+    // the attacker signs the connect message for the match of other taker by its own key
+    let connect = TakerConnect {
+        taker_order_uuid,
+        maker_order_uuid: maker_uuid,
+        method: "connect".into(),
+        sender_pubkey: attacker_pubkey,
+        dest_pub_key: maker_pubkey,
+        swap_uuid: Some(new_uuid()),
+    };
+    let signed: Json = unwrap!(json::from_str(&unwrap!(sign_ordermatch_message(&attacker_ctx, &connect))));
+    lp_trade_command(maker_ctx.clone(), signed);
+
+    assert!(maker_ctx.client_p2p_channel.1.try_recv().is_err());
+    let maker_orders = unwrap!(ordermatch_ctx.my_maker_orders.lock());
+    assert!(maker_orders[&maker_uuid].matches[&taker_order_uuid].connect.is_none());
+    assert!(maker_orders[&maker_uuid].started_swaps.is_empty());
+}
+
+#[test]
+fn test_repeated_connect_is_skipped() {
+    let maker_key_pair = unwrap!(key_pair_from_seed("also shoot benefit prefer juice shell elder veteran woman mimic image kidney"));
+    let maker_ctx = MmCtxBuilder::default().with_secp256k1_key_pair(maker_key_pair).into_mm_arc();
+    let taker_key_pair = unwrap!(key_pair_from_seed("spice describe gravity federal blast come thank unfair canal monkey style afraid"));
+    let taker_ctx = MmCtxBuilder::default().with_secp256k1_key_pair(taker_key_pair).into_mm_arc();
+    let maker_pubkey = H256Json::from(unwrap!(maker_ctx.public_id()).bytes);
+    let taker_pubkey = H256Json::from(unwrap!(taker_ctx.public_id()).bytes);
+
+    let mut request = taker_request_for_test(TakerAction::Buy, "BASE", "REL", 1, 1);
+    request.sender_pubkey = taker_pubkey.clone();
+    let mut maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        uuid: new_uuid(),
+        strategy: MakerStrategy::FixedPrice,
+    };
+    let taker_order_uuid = request.uuid;
+    let maker_uuid = maker.uuid;
+    let reserved = MakerReserved {
+        method: "reserved".into(),
+        base: "BASE".into(),
+        rel: "REL".into(),
+        base_amount: 1.into(),
+        base_amount_rat: Some(BigRational::from_integer(1.into())),
+        rel_amount: 1.into(),
+        rel_amount_rat: Some(BigRational::from_integer(1.into())),
+        sender_pubkey: maker_pubkey.clone(),
+        dest_pub_key: taker_pubkey.clone(),
+        maker_order_uuid: maker_uuid,
+        taker_order_uuid,
+    };
+    let connect = TakerConnect {
+        taker_order_uuid,
+        maker_order_uuid: maker_uuid,
+        method: "connect".into(),
+        sender_pubkey: taker_pubkey.clone(),
+        dest_pub_key: maker_pubkey.clone(),
+        swap_uuid: Some(new_uuid()),
+    };
+    let connected = MakerConnected {
+        taker_order_uuid,
+        maker_order_uuid: maker_uuid,
+        method: "connected".into(),
+        sender_pubkey: maker_pubkey.clone(),
+        dest_pub_key: taker_pubkey.clone(),
+    };
+    maker.started_swaps.push(connect.swap_uuid());
+    maker.matches.insert(taker_order_uuid, MakerMatch {
+        request,
+        reserved,
+        connect: Some(connect.clone()),
+        connected: Some(connected),
+        last_updated: now_ms(),
+    });
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&maker_ctx));
+    unwrap!(ordermatch_ctx.my_maker_orders.lock()).insert(maker_uuid, maker);
+
+    // the taker (or anyone replaying its message) sends the connect again, with the new swap uuid
+    let repeated = TakerConnect { swap_uuid: Some(new_uuid()), ..connect };
+    let signed: Json = unwrap!(json::from_str(&unwrap!(sign_ordermatch_message(&taker_ctx, &repeated))));
+    lp_trade_command(maker_ctx.clone(), signed);
+
+    assert!(maker_ctx.client_p2p_channel.1.try_recv().is_err());
+    let maker_orders = unwrap!(ordermatch_ctx.my_maker_orders.lock());
+    assert_eq!(1, maker_orders[&maker_uuid].started_swaps.len());
+}
+
+#[test]
+fn test_lp_auto_buy_partial_fill_is_opt_in() {
+    let key_pair = unwrap!(key_pair_from_seed("spice describe gravity federal blast come thank unfair canal monkey style afraid"));
+    let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+    save_my_taker_order.mock_safe(|_, _| MockResult::Return(()));
+    add_taker_order_history_event.mock_safe(|_, _, _| MockResult::Return(()));
+
+    let auto_buy_input = |extra: Json| -> AutoBuyInput {
+        let mut input = json!({"method": "buy", "base": "BASE", "rel": "REL", "price": "1", "volume": "1"});
+        for (key, value) in unwrap!(extra.as_object()) {
+            input[key] = value.clone();
+        }
+        unwrap!(json::from_value(input))
+    };
+
+    // the existing GTC and IOC orders keep requesting the whole volume
+    for order_type in &["GoodTillCancelled", "ImmediateOrCancel"] {
+        let res: Json = unwrap!(json::from_str(&unwrap!(lp_auto_buy(&ctx, auto_buy_input(json!({"order_type": order_type}))))));
+        let request: TakerRequest = unwrap!(json::from_value(res["result"].clone()));
+        assert!(!request.allow_partial_fill);
+    }
+
+    let input = auto_buy_input(json!({"order_type": "ImmediateOrCancel", "allow_partial_fill": true}));
+    let res: Json = unwrap!(json::from_str(&unwrap!(lp_auto_buy(&ctx, input))));
+    let request: TakerRequest = unwrap!(json::from_value(res["result"].clone()));
+    assert!(request.allow_partial_fill);
+
+    let input = auto_buy_input(json!({"order_type": "FillOrKill", "allow_partial_fill": true}));
+    assert!(lp_auto_buy(&ctx, input).is_err());
+}
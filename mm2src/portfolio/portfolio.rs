@@ -26,8 +26,10 @@
 #[macro_use] extern crate serde_derive;
 #[macro_use] extern crate unwrap;
 
+pub mod price_providers;
+use self::price_providers::{lp_btcprice, Coins, CoinId, ExternalPrices, PricingProvider, PriceUnit};
 pub mod prices;
-use self::prices::{lp_btcprice, lp_fundvalue, Coins, CoinId, ExternalPrices, FundvalueRes, PricingProvider, PriceUnit};
+use self::prices::{lp_fundvalue, FundvalueRes};
 
 #[doc(hidden)]
 pub mod portfolio_tests;
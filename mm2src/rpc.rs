@@ -46,10 +46,14 @@ use std::net::SocketAddr;
 #[cfg(feature = "native")]
 use tokio_core::net::TcpListener;
 
+use crate::mm2::lp_autoprice::autoprice;
 use crate::mm2::lp_network;
-use crate::mm2::lp_ordermatch::{buy, cancel_all_orders, cancel_order, my_orders, order_status, orderbook, sell, set_price};
-use crate::mm2::lp_swap::{coins_needed_for_kick_start, import_swaps,  my_swap_status, my_recent_swaps,
-                          recover_funds_of_swap, stats_swap_status};
+use crate::mm2::lp_ordermatch::{buy, cancel_all_orders, cancel_conditional_order, cancel_order, create_conditional_order,
+                                market_buy, market_sell, my_conditional_orders, my_orders, my_orders_history,
+                                order_status, orderbook, orderbook_updates, sell, set_price, tickers,
+                                trade_preimage, update_maker_order};
+use crate::mm2::lp_swap::{coins_needed_for_kick_start, import_swaps, max_taker_vol, my_swap_status, my_recent_swaps,
+                          recover_funds_of_swap, stats_swap_status, taker_swap_watcher_data, watch_taker_swap};
 
 #[path = "rpc/lp_commands.rs"]
 pub mod lp_commands;
@@ -70,6 +74,7 @@ const PUBLIC_METHODS: &[Option<&str>] = &[  // Sorted alphanumerically (on the f
     Some("help"),
     Some("notify"),  // Manually checks the peer's public key.
     Some("orderbook"),
+    Some("orderbook_updates"),
     Some("passphrase"),  // Manually checks the "passphrase".
     Some("pricearray"),
     Some("psock"),
@@ -77,6 +82,7 @@ const PUBLIC_METHODS: &[Option<&str>] = &[  // Sorted alphanumerically (on the f
     Some("stats_swap_status"),
     Some("tradesarray"),
     Some("ticker"),
+    Some("tickers"),
     None
 ];
 
@@ -199,11 +205,13 @@ pub fn dispatcher (req: Json, ctx: MmArc) -> DispatcherRes {
         _ => return DispatcherRes::NoMatch (req)
     };
     DispatcherRes::Match (match &method[..] {  // Sorted alphanumerically (on the first latter) for readability.
-        // "autoprice" => lp_autoprice (ctx, req),
+        "autoprice" => hyres(autoprice(ctx, req)),
         "buy" => hyres(buy(ctx, req)),
         "cancel_all_orders" => cancel_all_orders (ctx, req),
+        "cancel_conditional_order" => cancel_conditional_order(ctx, req),
         "cancel_order" => cancel_order (ctx, req),
         "coins_needed_for_kick_start" => hyres (coins_needed_for_kick_start (ctx)),
+        "create_conditional_order" => hyres(create_conditional_order(ctx, req)),
         "disable_coin" => disable_coin(ctx, req),
         "enable" => hyres (enable (ctx, req)),
         "electrum" => hyres (electrum (ctx, req)),
@@ -218,11 +226,17 @@ pub fn dispatcher (req: Json, ctx: MmArc) -> DispatcherRes {
             #[cfg(not(feature = "native"))] {return DispatcherRes::NoMatch (req)}
         },
         // "inventory" => inventory (ctx, req),
+        "market_buy" => hyres(market_buy(ctx, req)),
+        "market_sell" => hyres(market_sell(ctx, req)),
+        "max_taker_vol" => hyres(max_taker_vol(ctx, req)),
         "my_orders" => my_orders (ctx),
+        "my_orders_history" => my_orders_history(ctx, req),
         "my_balance" => my_balance (ctx, req),
+        "my_conditional_orders" => my_conditional_orders(ctx),
         "my_tx_history" => my_tx_history(ctx, req),
         "notify" => lp_signatures::lp_notify_recv (ctx, req),  // Invoked usually from the `lp_command_q_loop`
         "orderbook" => hyres(orderbook(ctx, req)),
+        "orderbook_updates" => hyres(orderbook_updates(ctx, req)),
         "order_status" => order_status (ctx, req),
         // "passphrase" => passphrase (ctx, req),
         "sell" => hyres(sell(ctx, req)),
@@ -239,7 +253,12 @@ pub fn dispatcher (req: Json, ctx: MmArc) -> DispatcherRes {
         },
         "set_required_confirmations" => hyres(set_required_confirmations(ctx, req)),
         "stats_swap_status" => stats_swap_status(ctx, req),
+        "taker_swap_watcher_data" => hyres(taker_swap_watcher_data(ctx, req)),
+        "tickers" => tickers(ctx),
+        "trade_preimage" => hyres(trade_preimage(ctx, req)),
+        "update_maker_order" => hyres(update_maker_order(ctx, req)),
         "version" => version(),
+        "watch_taker_swap" => hyres(watch_taker_swap(ctx, req)),
         "withdraw" => hyres (withdraw (ctx, req)),
         _ => return DispatcherRes::NoMatch (req)
     })